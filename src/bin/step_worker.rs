@@ -0,0 +1,9 @@
+// Pipeline web worker. Trunk builds this target with `data-type="worker"`;
+// the library start function notices it has no window and registers
+// `StepWorker` instead of mounting the app.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+
+use stepvisualizer as _;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {}
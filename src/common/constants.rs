@@ -1,58 +1,97 @@
+pub use stepviz_core::constants::*;
+
 pub const CACHE_SIZE: usize = 5;
 pub const MAX_FILE_BYTES: f64 = 20.0 * 1024.0 * 1024.0; //20mb max (text file ...)
+// emitted by trunk from src/bin/step_worker.rs (see index.html), relative to
+// the document base so it follows trunk's --public-url
+pub const STEP_WORKER_PATH: &str = "step_worker.js";
 pub const LS_INDEX_KEY: &str = "stepviz:index";
+// models persisted by older versions, migrated to IndexedDB on startup
+pub const LS_MODEL_PREFIX: &str = "stepviz:model:";
+pub const IDB_NAME: &str = "stepviz";
+pub const IDB_VERSION: u32 = 1;
+pub const IDB_STORE: &str = "entries";
 pub const NA: &str = "N/A";
-pub const STEP_TRACER: &str = "[STEP_TRACER]";
 pub const WGSL_SHADER: &str = r#"
 struct VertexInput {
 @location(0) position: vec3<f32>,
 @location(1) normal: vec3<f32>,
+@location(2) color: vec3<f32>,
 };
 
 struct VertexOutput {
 @builtin(position) clip_position: vec4<f32>,
 @location(0) normal: vec3<f32>,
+@location(1) color: vec3<f32>,
+@location(2) world_position: vec3<f32>,
+};
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    // xyz = eye position, w = number of clip planes in use
+    eye: vec4<f32>,
+    // xyz = unit normal, w = offset, dot(normal, p) > offset is cut away
+    // (size is MAX_CLIP_PLANES)
+    clip_planes: array<vec4<f32>, 4>,
+};
+
+// one slot per part, selected with a dynamic offset
+struct Part {
+    model_matrix: mat4x4<f32>,
+    // rgb = part color, w = 1.0 when per-vertex (face style) colors should be used
+    color: vec4<f32>,
+    // rgb = selection color, w = how much of it replaces the shaded color
+    highlight: vec4<f32>,
 };
 
 @group(0) @binding(0)
-var<uniform> mvp_matrix: mat4x4<f32>;
+var<uniform> camera: Camera;
 
 @group(0) @binding(1)
-var<uniform> model_matrix: mat4x4<f32>;
-
-@group(0) @binding(2)
-var<uniform> color: vec3<f32>;
+var<uniform> part: Part;
 
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
 var out: VertexOutput;
-    out.clip_position = mvp_matrix * vec4<f32>(input.position, 1.0);
-    out.normal = (model_matrix * vec4<f32>(input.normal, 0.0)).xyz;
+    let world_position = part.model_matrix * vec4<f32>(input.position, 1.0);
+    out.clip_position = camera.view_projection * world_position;
+    out.normal = (part.model_matrix * vec4<f32>(input.normal, 0.0)).xyz;
+    out.color = input.color;
+    out.world_position = world_position.xyz;
     return out;
 }
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    let plane_count = u32(camera.eye.w);
+    for (var i = 0u; i < plane_count; i++) {
+        let plane = camera.clip_planes[i];
+        if dot(plane.xyz, in.world_position) > plane.w {
+            discard;
+        }
+    }
     let light_dir = normalize(vec3<f32>(1.0, 1.0, 1.0));
     let intensity = max(dot(normalize(in.normal), light_dir), 0.0);
-    let shaded_color = color * intensity;
-    return vec4<f32>(shaded_color, 1.0);
+    let base_color = mix(part.color.rgb, in.color, part.color.w);
+    var shaded_color = base_color * intensity;
+    // once cut open, back faces (triangles are wound outward) are the inside
+    // of a solid seen through the cut, drawn flat they cap the cross-section
+    if plane_count > 0u && !front_facing {
+        shaded_color = base_color * 0.6;
+    }
+    // selected parts keep some shading so their shape stays readable
+    let highlighted = part.highlight.rgb * (0.4 + 0.6 * intensity);
+    return vec4<f32>(mix(shaded_color, highlighted, part.highlight.w), 1.0);
 }
 "#;
 
-pub const NEAR_PLANE: f32 = 0.1;
-pub const DEFAULT_TOLERANCE: f64 = 0.1;
+// the near plane never gets closer than this fraction of the model radius,
+// which bounds the far/near ratio and with it the depth precision loss
+pub const NEAR_PLANE_MIN_RATIO: f32 = 1e-3;
+pub const MSAA_SAMPLE_COUNT: u32 = 4;
+// section planes the shader takes, see WGSL_SHADER
+pub const MAX_CLIP_PLANES: usize = 4;
+pub const CAMERA_FOV_Y: f32 = std::f32::consts::FRAC_PI_3;
+// rgb and mix amount of selected parts, see WGSL_SHADER
+pub const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 0.6];
 pub const CLEAR_COLOR_RGB: (f64, f64, f64) = (0.165, 0.165, 0.165);
-
-pub const COLORS: [[f32; 3]; 10] = [
-    [0.8, 0.2, 0.2],
-    [0.2, 0.8, 0.2],
-    [0.2, 0.2, 0.8],
-    [0.8, 0.8, 0.2],
-    [0.8, 0.2, 0.8],
-    [0.2, 0.8, 0.8],
-    [0.6, 0.4, 0.2],
-    [0.4, 0.6, 0.8],
-    [0.8, 0.6, 0.4],
-    [0.6, 0.8, 0.4],
-];
@@ -0,0 +1,183 @@
+use crate::trace_span;
+use js_sys::{Function, Promise, Uint8Array};
+use std::cell::RefCell;
+use wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{DomException, IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode};
+
+use super::storage::{KeyValueStore, StorageError};
+use crate::common::constants::{IDB_NAME, IDB_STORE, IDB_VERSION};
+
+// IndexedDB backend: one object store of byte arrays keyed by string.
+// The database is opened on first use and kept open.
+#[derive(Default)]
+pub struct IdbStore {
+    db: RefCell<Option<IdbDatabase>>,
+}
+
+impl IdbStore {
+    async fn database(&self) -> Result<IdbDatabase, StorageError> {
+        if let Some(db) = self.db.borrow().as_ref() {
+            return Ok(db.clone());
+        }
+        trace_span!("IdbStore::open");
+        let factory = web_sys::window()
+            .and_then(|window| window.indexed_db().ok().flatten())
+            .ok_or_else(|| StorageError::Unavailable("IndexedDB is not supported".to_string()))?;
+        let request = factory
+            .open_with_u32(IDB_NAME, IDB_VERSION)
+            .map_err(|e| StorageError::Unavailable(js_error(&e)))?;
+
+        let on_upgrade = Closure::<dyn FnMut()>::new({
+            let request = request.clone();
+            move || {
+                if let Ok(result) = request.result() {
+                    let db: IdbDatabase = result.unchecked_into();
+                    if !db.object_store_names().contains(IDB_STORE) {
+                        let _ = db.create_object_store(IDB_STORE);
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        let opened = request_outcome(&request).await;
+        request.set_onupgradeneeded(None);
+
+        let db: IdbDatabase = opened.map_err(StorageError::Unavailable)?.unchecked_into();
+        *self.db.borrow_mut() = Some(db.clone());
+        Ok(db)
+    }
+
+    async fn read(
+        &self,
+        op: impl FnOnce(&IdbObjectStore) -> Result<IdbRequest, JsValue>,
+    ) -> Result<JsValue, StorageError> {
+        let db = self.database().await?;
+        let transaction = db.transaction_with_str(IDB_STORE).map_err(backend_error)?;
+        let store = transaction.object_store(IDB_STORE).map_err(backend_error)?;
+        let request = op(&store).map_err(backend_error)?;
+        request_outcome(&request)
+            .await
+            .map_err(StorageError::Backend)
+    }
+
+    // resolves once the transaction is committed, so quota errors (raised when
+    // the transaction aborts) are reported to the caller
+    async fn write(
+        &self,
+        op: impl FnOnce(&IdbObjectStore) -> Result<IdbRequest, JsValue>,
+    ) -> Result<(), StorageError> {
+        let db = self.database().await?;
+        let transaction = db
+            .transaction_with_str_and_mode(IDB_STORE, IdbTransactionMode::Readwrite)
+            .map_err(backend_error)?;
+        let store = transaction.object_store(IDB_STORE).map_err(backend_error)?;
+        op(&store).map_err(backend_error)?;
+        let committed = wait_for(|done, failed| {
+            transaction.set_oncomplete(done);
+            transaction.set_onerror(failed);
+            transaction.set_onabort(failed);
+        })
+        .await;
+        if committed {
+            Ok(())
+        } else {
+            let reason = transaction
+                .error()
+                .map(|e| dom_error(&e))
+                .unwrap_or_else(|| "transaction aborted".to_string());
+            Err(StorageError::Backend(reason))
+        }
+    }
+}
+
+impl KeyValueStore for IdbStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        trace_span!("IdbStore::get");
+        let value = self.read(|store| store.get(&key.into())).await?;
+        if value.is_undefined() {
+            return Ok(None);
+        }
+        let bytes = value
+            .dyn_into::<Uint8Array>()
+            .map_err(|_| StorageError::Corrupted(format!("{key} is not a byte array")))?;
+        Ok(Some(bytes.to_vec()))
+    }
+
+    async fn put(&self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
+        trace_span!("IdbStore::put");
+        let bytes = Uint8Array::from(value.as_slice());
+        self.write(|store| store.put_with_key(&bytes, &key.into()))
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        trace_span!("IdbStore::delete");
+        self.write(|store| store.delete(&key.into())).await
+    }
+
+    async fn clear(&self) -> Result<(), StorageError> {
+        trace_span!("IdbStore::clear");
+        self.write(|store| store.clear()).await
+    }
+}
+
+// result of a request once it fired success, or its error
+async fn request_outcome(request: &IdbRequest) -> Result<JsValue, String> {
+    let succeeded = wait_for(|done, failed| {
+        request.set_onsuccess(done);
+        request.set_onerror(failed);
+    })
+    .await;
+    if succeeded {
+        request.result().map_err(|e| js_error(&e))
+    } else {
+        Err(request
+            .error()
+            .ok()
+            .flatten()
+            .map(|e| dom_error(&e))
+            .unwrap_or_else(|| "request failed".to_string()))
+    }
+}
+
+// Installs a success and a failure handler through `install`, waits for one
+// of them to fire and uninstalls both. Returns whether success fired.
+async fn wait_for(install: impl Fn(Option<&Function>, Option<&Function>)) -> bool {
+    let mut handlers = Vec::new();
+    let promise = Promise::new(&mut |resolve: Function, _reject: Function| {
+        let done = Closure::<dyn FnMut()>::new({
+            let resolve = resolve.clone();
+            move || {
+                let _ = resolve.call1(&JsValue::NULL, &JsValue::TRUE);
+            }
+        });
+        let failed = Closure::<dyn FnMut()>::new(move || {
+            let _ = resolve.call1(&JsValue::NULL, &JsValue::FALSE);
+        });
+        install(
+            Some(done.as_ref().unchecked_ref()),
+            Some(failed.as_ref().unchecked_ref()),
+        );
+        handlers.push(done);
+        handlers.push(failed);
+    });
+    let outcome = JsFuture::from(promise).await;
+    install(None, None);
+    outcome.is_ok_and(|value| value.is_truthy())
+}
+
+fn dom_error(error: &DomException) -> String {
+    format!("{}: {}", error.name(), error.message())
+}
+
+fn js_error(error: &JsValue) -> String {
+    match error.dyn_ref::<DomException>() {
+        Some(error) => dom_error(error),
+        None => format!("{:?}", error),
+    }
+}
+
+fn backend_error(error: JsValue) -> StorageError {
+    StorageError::Backend(js_error(&error))
+}
@@ -1,15 +1,13 @@
+// Parsing, tessellation and analysis live in stepviz-core so they build and
+// test natively, what is left here needs a browser
 pub mod cache;
 pub mod constants;
-pub mod math;
-pub mod parser;
-pub mod render;
+pub mod idb;
 pub mod storage;
-pub mod types;
+
+pub use stepviz_core::*;
 
 pub use cache::LruCache;
 pub use constants::*;
-pub use math::{create_look_at_matrix, create_perspective_matrix, multiply_matrices};
-pub use parser::{compute_bounding_box, convert_header, parse_units};
-pub use render::{GpuVertex, RenderablePart, step_extract_wsgl_reqs};
-pub use storage::{delete_model, hash_text_to_id, load_index, load_model, save_index, save_model};
-pub use types::{FileIndexItem, Metadata, StepModel};
+pub use idb::IdbStore;
+pub use storage::{ModelStore, StorageError, migrate_local_storage};
@@ -1,48 +1,371 @@
 use crate::trace_span;
 use gloo_storage::{LocalStorage, Storage};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::Hasher;
+use std::fmt;
 
+use super::encoding::{decode_model, encode_model};
+use super::error::StepVizError;
 use super::types::{FileIndexItem, StepModel};
 
-use crate::common::constants::LS_INDEX_KEY;
+use crate::common::constants::{LS_INDEX_KEY, LS_MODEL_PREFIX};
 
-pub fn save_index(index: &[FileIndexItem]) {
-    trace_span!("save_index");
-    let _ = LocalStorage::set(LS_INDEX_KEY, index);
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageError {
+    // no backend in this environment (private browsing, blocked IndexedDB ...)
+    Unavailable(String),
+    // the backend refused the operation, quota errors end up here
+    Backend(String),
+    // stored bytes could not be decoded
+    Corrupted(String),
 }
 
-pub fn load_index() -> Vec<FileIndexItem> {
-    trace_span!("load_index");
-    LocalStorage::get(LS_INDEX_KEY).unwrap_or_else(|_| vec![])
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Unavailable(msg) => write!(f, "storage unavailable: {}", msg),
+            StorageError::Backend(msg) => write!(f, "storage error: {}", msg),
+            StorageError::Corrupted(msg) => write!(f, "corrupted stored data: {}", msg),
+        }
+    }
 }
 
+impl From<StorageError> for StepVizError {
+    fn from(err: StorageError) -> Self {
+        StepVizError::storage(match err {
+            StorageError::Unavailable(msg) => format!("unavailable: {}", msg),
+            StorageError::Backend(msg) => msg,
+            StorageError::Corrupted(msg) => format!("corrupted stored data: {}", msg),
+        })
+    }
+}
+
+// Byte-oriented key/value backend, IndexedDB in the browser (see idb.rs)
+#[allow(async_fn_in_trait)]
+pub trait KeyValueStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+    async fn put(&self, key: &str, value: Vec<u8>) -> Result<(), StorageError>;
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+    async fn clear(&self) -> Result<(), StorageError>;
+}
+
+// Backend kept in memory, `quota` (in bytes) mimics browser storage limits
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: std::cell::RefCell<std::collections::HashMap<String, Vec<u8>>>,
+    quota: Option<usize>,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn with_quota(quota: usize) -> Self {
+        Self {
+            quota: Some(quota),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl KeyValueStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        Ok(self.entries.borrow().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, value: Vec<u8>) -> Result<(), StorageError> {
+        let mut entries = self.entries.borrow_mut();
+        if let Some(quota) = self.quota {
+            let used: usize = entries
+                .iter()
+                .filter(|(k, _)| k.as_str() != key)
+                .map(|(_, v)| v.len())
+                .sum();
+            if used + value.len() > quota {
+                return Err(StorageError::Backend("QuotaExceededError".to_string()));
+            }
+        }
+        entries.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.entries.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), StorageError> {
+        self.entries.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+const INDEX_KEY: &str = "index";
+
 fn model_key(id: &str) -> String {
     trace_span!("model_key");
-    format!("stepviz:model:{}", id)
+    format!("model:{}", id)
 }
 
-pub fn save_model(model: &StepModel) {
-    trace_span!("save_model");
-    let key = model_key(&model.id);
-    let _ = LocalStorage::set(key, model);
+// File history and models on top of a key/value backend. Models use the
+// binary encoding from encoding.rs, the index stays JSON.
+pub struct ModelStore<S> {
+    backend: S,
 }
 
-pub fn load_model(id: &str) -> Option<StepModel> {
-    trace_span!("load_model");
-    let key = model_key(id);
-    LocalStorage::get::<StepModel>(key).ok()
+impl<S: KeyValueStore> ModelStore<S> {
+    pub fn new(backend: S) -> Self {
+        Self { backend }
+    }
+
+    pub async fn save_index(&self, index: &[FileIndexItem]) -> Result<(), StorageError> {
+        trace_span!("save_index");
+        let bytes = serde_json::to_vec(index).map_err(|e| StorageError::Backend(e.to_string()))?;
+        self.backend.put(INDEX_KEY, bytes).await
+    }
+
+    pub async fn load_index(&self) -> Result<Vec<FileIndexItem>, StorageError> {
+        trace_span!("load_index");
+        match self.backend.get(INDEX_KEY).await? {
+            Some(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| StorageError::Corrupted(format!("file index: {e}"))),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub async fn save_model(&self, model: &StepModel) -> Result<(), StorageError> {
+        trace_span!("save_model");
+        self.backend
+            .put(&model_key(&model.id), encode_model(model))
+            .await
+    }
+
+    pub async fn load_model(&self, id: &str) -> Result<Option<StepModel>, StorageError> {
+        trace_span!("load_model");
+        match self.backend.get(&model_key(id)).await? {
+            Some(bytes) => decode_model(&bytes)
+                .map(Some)
+                .map_err(|e| StorageError::Corrupted(format!("model {id}: {e}"))),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn delete_model(&self, id: &str) -> Result<(), StorageError> {
+        trace_span!("delete_model");
+        self.backend.delete(&model_key(id)).await
+    }
+
+    pub async fn clear(&self) -> Result<(), StorageError> {
+        trace_span!("clear");
+        self.backend.clear().await
+    }
+
+    // Moves entries written by the LocalStorage persistence (JSON models under
+    // `stepviz:model:<id>`, JSON index under `stepviz:index`). Returns the
+    // legacy keys that are dealt with and can be removed. Models that don't
+    // parse are left where they are, an index that doesn't parse as well.
+    // History entries are only taken over for models that are in the store,
+    // others would point to nothing.
+    pub async fn import_legacy(
+        &self,
+        entries: Vec<(String, String)>,
+    ) -> Result<Vec<String>, StorageError> {
+        trace_span!("import_legacy");
+        let mut done = Vec::new();
+        let mut imported = Vec::new();
+        let mut legacy_index = None;
+        for (key, json) in entries {
+            if key == LS_INDEX_KEY {
+                legacy_index = Some((key, json));
+                continue;
+            }
+            let Some(id) = key.strip_prefix(LS_MODEL_PREFIX) else {
+                continue;
+            };
+            if let Ok(model) = serde_json::from_str::<StepModel>(&json) {
+                self.save_model(&model).await?;
+                imported.push(id.to_string());
+                done.push(key);
+            }
+        }
+        if let Some((key, json)) = legacy_index
+            && let Ok(legacy) = serde_json::from_str::<Vec<FileIndexItem>>(&json)
+        {
+            let mut index = self.load_index().await?;
+            let known_len = index.len();
+            for item in legacy {
+                if index[..known_len].iter().any(|known| known.id == item.id) {
+                    continue;
+                }
+                if imported.contains(&item.id)
+                    || self.backend.get(&model_key(&item.id)).await?.is_some()
+                {
+                    index.push(item);
+                }
+            }
+            self.save_index(&index).await?;
+            done.push(key);
+        }
+        Ok(done)
+    }
 }
 
-pub fn delete_model(id: &str) {
-    trace_span!("delete_model");
-    let key = model_key(id);
-    let _ = LocalStorage::delete(key);
+// Moves whatever the old LocalStorage persistence left behind into `store`
+pub async fn migrate_local_storage<S: KeyValueStore>(
+    store: &ModelStore<S>,
+) -> Result<usize, StorageError> {
+    trace_span!("migrate_local_storage");
+    let storage = LocalStorage::raw();
+    let len = storage.length().unwrap_or(0);
+    let entries: Vec<(String, String)> = (0..len)
+        .filter_map(|i| storage.key(i).ok().flatten())
+        .filter(|key| key == LS_INDEX_KEY || key.starts_with(LS_MODEL_PREFIX))
+        .filter_map(|key| {
+            let value = storage.get_item(&key).ok().flatten()?;
+            Some((key, value))
+        })
+        .collect();
+    if entries.is_empty() {
+        return Ok(0);
+    }
+    let done = store.import_legacy(entries).await?;
+    for key in &done {
+        LocalStorage::delete(key);
+    }
+    Ok(done.len())
 }
 
-pub fn hash_text_to_id(text: &str) -> String {
-    trace_span!("hash_text_to_id");
-    let mut hasher = DefaultHasher::new();
-    std::hash::Hash::hash(&text, &mut hasher);
-    format!("{:016x}", hasher.finish())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::fixtures::sample_model;
+
+    fn index_item(id: &str) -> FileIndexItem {
+        FileIndexItem {
+            id: id.to_string(),
+            name: format!("{id}.stp"),
+            entity_count: 1,
+            time_stamp: String::new(),
+        }
+    }
+
+    #[test]
+    fn models_and_index_round_trip() {
+        let store = ModelStore::new(MemoryStore::default());
+        pollster::block_on(async {
+            assert_eq!(store.load_index().await, Ok(vec![]));
+            assert_eq!(store.load_model("a").await, Ok(None));
+
+            let model = sample_model("a");
+            store.save_model(&model).await.unwrap();
+            store.save_index(&[index_item("a")]).await.unwrap();
+            assert_eq!(store.load_model("a").await, Ok(Some(model)));
+            assert_eq!(store.load_index().await, Ok(vec![index_item("a")]));
+
+            store.delete_model("a").await.unwrap();
+            assert_eq!(store.load_model("a").await, Ok(None));
+            store.clear().await.unwrap();
+            assert_eq!(store.load_index().await, Ok(vec![]));
+        });
+    }
+
+    #[test]
+    fn write_failures_are_reported() {
+        let store = ModelStore::new(MemoryStore::with_quota(64));
+        let result = pollster::block_on(store.save_model(&sample_model("a")));
+        assert!(
+            matches!(result, Err(StorageError::Backend(_))),
+            "{result:?}"
+        );
+        assert_eq!(pollster::block_on(store.load_model("a")), Ok(None));
+    }
+
+    #[test]
+    fn corrupted_entries_are_reported() {
+        let backend = MemoryStore::default();
+        pollster::block_on(backend.put("model:a", b"SVZM\x01garbage".to_vec())).unwrap();
+        let store = ModelStore::new(backend);
+        let result = pollster::block_on(store.load_model("a"));
+        assert!(
+            matches!(result, Err(StorageError::Corrupted(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn legacy_entries_are_imported() {
+        let store = ModelStore::new(MemoryStore::default());
+        let model = sample_model("a");
+        let legacy = vec![
+            (
+                format!("{LS_MODEL_PREFIX}a"),
+                serde_json::to_string(&model).unwrap(),
+            ),
+            (format!("{LS_MODEL_PREFIX}broken"), "{".to_string()),
+            (
+                LS_INDEX_KEY.to_string(),
+                serde_json::to_string(&[index_item("a"), index_item("b"), index_item("broken")])
+                    .unwrap(),
+            ),
+            ("unrelated".to_string(), "x".to_string()),
+        ];
+        pollster::block_on(async {
+            store.save_index(&[index_item("b")]).await.unwrap();
+            let mut done = store.import_legacy(legacy).await.unwrap();
+            done.sort();
+            assert_eq!(
+                done,
+                vec![LS_INDEX_KEY.to_string(), format!("{LS_MODEL_PREFIX}a")]
+            );
+            assert_eq!(store.load_model("a").await, Ok(Some(model)));
+            assert_eq!(store.load_model("broken").await, Ok(None));
+            // entries already in the new index keep their place, the broken
+            // model stays in LocalStorage without a history entry
+            assert_eq!(
+                store.load_index().await,
+                Ok(vec![index_item("b"), index_item("a")])
+            );
+        });
+    }
+
+    #[test]
+    fn legacy_history_needs_a_stored_model() {
+        let store = ModelStore::new(MemoryStore::default());
+        let legacy = vec![(
+            LS_INDEX_KEY.to_string(),
+            serde_json::to_string(&[index_item("gone"), index_item("c")]).unwrap(),
+        )];
+        pollster::block_on(async {
+            store.save_model(&sample_model("c")).await.unwrap();
+            let done = store.import_legacy(legacy).await.unwrap();
+            assert_eq!(done, vec![LS_INDEX_KEY.to_string()]);
+            assert_eq!(store.load_index().await, Ok(vec![index_item("c")]));
+        });
+    }
+
+    #[test]
+    fn unreadable_legacy_index_is_kept() {
+        let store = ModelStore::new(MemoryStore::default());
+        let legacy = vec![
+            (
+                format!("{LS_MODEL_PREFIX}a"),
+                serde_json::to_string(&sample_model("a")).unwrap(),
+            ),
+            (LS_INDEX_KEY.to_string(), "[{".to_string()),
+        ];
+        pollster::block_on(async {
+            store.save_index(&[index_item("b")]).await.unwrap();
+            let done = store.import_legacy(legacy).await.unwrap();
+            assert_eq!(done, vec![format!("{LS_MODEL_PREFIX}a")]);
+            assert_eq!(store.load_index().await, Ok(vec![index_item("b")]));
+        });
+    }
+
+    #[test]
+    fn failed_import_keeps_legacy_entries() {
+        let store = ModelStore::new(MemoryStore::with_quota(64));
+        let legacy = vec![(
+            format!("{LS_MODEL_PREFIX}a"),
+            serde_json::to_string(&sample_model("a")).unwrap(),
+        )];
+        assert!(pollster::block_on(store.import_legacy(legacy)).is_err());
+    }
 }
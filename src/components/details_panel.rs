@@ -1,5 +1,8 @@
-use crate::common::{Metadata, NA};
+use crate::common::mesh_check::MeshCheck;
+use crate::common::section::{Section, cross_section};
+use crate::common::{ClipPlane, Metadata, NA, StepModel};
 use crate::trace_span;
+use std::rc::Rc;
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -7,11 +10,67 @@ pub struct DetailsPanelProps {
     pub metadata: Option<Metadata>,
     pub on_calculate_volume: Callback<()>,
     pub on_calculate_surface: Callback<()>,
+    #[prop_or_default]
+    pub model: Option<Rc<StepModel>>,
+    #[prop_or_default]
+    pub clip_planes: Vec<ClipPlane>,
+    #[prop_or_default]
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
 }
 
 #[function_component(DetailsPanel)]
 pub fn details_panel(props: &DetailsPanelProps) -> Html {
     trace_span!("details_panel");
+    // models are compared by pointer, comparing them by value walks every vertex
+    let model_ptr = props.model.as_ref().map(|model| Rc::as_ptr(model) as usize);
+    let sections = {
+        let model = props.model.clone();
+        use_memo(
+            (model_ptr, props.clip_planes.clone()),
+            move |(_, planes)| {
+                model
+                    .map(|model| {
+                        planes
+                            .iter()
+                            .map(|plane| (*plane, cross_section(&model.render_parts, plane)))
+                            .collect::<Vec<(ClipPlane, Section)>>()
+                    })
+                    .unwrap_or_default()
+            },
+        )
+    };
+    let section_item = |(plane, section): &(ClipPlane, Section)| {
+        let unit = props
+            .metadata
+            .as_ref()
+            .and_then(|meta| meta.length_unit())
+            .unwrap_or("");
+        html! {
+            <div class="detail-item">
+                <dt class="detail-label">
+                    { format!("Section {} @ {:.3} :", plane.label(), plane.offset) }
+                </dt>
+                <dd>
+                    <span class="section-value">{ format!("Area: {:.4} {}²", section.area, unit) }</span>
+                    <br/>
+                    <span class="section-value">
+                        { format!("Perimeter: {:.4} {}", section.perimeter, unit) }
+                    </span>
+                    if section.open_contours > 0 {
+                        <br/>
+                        <span class="section-warning">
+                            { format!("{} open contour(s) left out", section.open_contours) }
+                        </span>
+                    }
+                </dd>
+            </div>
+        }
+    };
+    let open_parts = props
+        .mesh_checks
+        .iter()
+        .filter(|check| !check.volume_reliable())
+        .count();
     html! {
         <div class="panel panel-details">
             <div class="panel-header">
@@ -65,6 +124,21 @@ pub fn details_panel(props: &DetailsPanelProps) -> Html {
                             <dt class="detail-label">{ "Entity count :" }</dt>
                             <dd>{ meta.entity_count }</dd>
                         </div>
+                        if meta.section_entity_counts.len() > 1 {
+                            <div class="detail-item">
+                                <dt class="detail-label">{ "Data sections :" }</dt>
+                                <dd>
+                                    { for meta.section_entity_counts.iter().enumerate().map(|(i, count)| html! {
+                                        <>
+                                            <span class="section-count">
+                                                { format!("#{}: {} entities", i, count) }
+                                            </span>
+                                            <br/>
+                                        </>
+                                    }) }
+                                </dd>
+                            </div>
+                        }
                         <div class="detail-item">
                             <dt class="detail-label">{ "Bounding box :" }</dt>
                             <dd>
@@ -89,7 +163,7 @@ pub fn details_panel(props: &DetailsPanelProps) -> Html {
                         </div>
                         <div class="detail-item">
                             <dt class="detail-label">{ "Units :" }</dt>
-                            <dd>{ meta.units.clone().unwrap_or_else(|| NA.to_string()) }</dd>
+                            <dd>{ meta.length_unit().unwrap_or(NA).to_string() }</dd>
                         </div>
 
                         <div class="detail-item">
@@ -102,7 +176,7 @@ pub fn details_panel(props: &DetailsPanelProps) -> Html {
                         </div>
                         <div class="detail-item">
                             <dt class="detail-label">{ "Unit system :" }</dt>
-                            <dd>{ meta.units.clone().unwrap_or_else(|| NA.to_string()) }</dd>
+                            <dd>{ meta.length_unit().unwrap_or(NA).to_string() }</dd>
                         </div>
                         if let Some(bb) = &meta.bounding_box {
                             <div class="detail-item">
@@ -123,8 +197,18 @@ pub fn details_panel(props: &DetailsPanelProps) -> Html {
                             <dd>
                                 {
                                     if let Some(vol) = meta.volume {
-                                        let unit = meta.units.as_deref().unwrap_or("");
-                                        html! { format!("{:.4} {}³", vol, unit) }
+                                        let unit = meta.length_unit().unwrap_or("");
+                                        html! {
+                                            <>
+                                                { format!("{:.4} {}³", vol, unit) }
+                                                if open_parts > 0 {
+                                                    <br/>
+                                                    <span class="section-warning">
+                                                        { format!("unreliable, {} part(s) not closed or flipped", open_parts) }
+                                                    </span>
+                                                }
+                                            </>
+                                        }
                                     } else {
                                         let on_click = props.on_calculate_volume.clone();
                                         html! {
@@ -144,7 +228,7 @@ pub fn details_panel(props: &DetailsPanelProps) -> Html {
                             <dd>
                                 {
                                     if let Some(area) = meta.surface_area {
-                                        let unit = meta.units.as_deref().unwrap_or("");
+                                        let unit = meta.length_unit().unwrap_or("");
                                         html! { format!("{:.4} {}²", area, unit) }
                                     } else {
                                         let on_click = props.on_calculate_surface.clone();
@@ -160,6 +244,7 @@ pub fn details_panel(props: &DetailsPanelProps) -> Html {
                                 }
                             </dd>
                         </div>
+                        { for sections.iter().map(section_item) }
                     </dl>
                 } else {
                    <div class="empty-files-message">{ "No file loaded/Selected yet. Please select or upload a file" }</div>
@@ -0,0 +1,52 @@
+use crate::common::{Diagnostic, Severity};
+use crate::trace_span;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct DiagnosticsPanelProps {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[function_component(DiagnosticsPanel)]
+pub fn diagnostics_panel(props: &DiagnosticsPanelProps) -> Html {
+    trace_span!("diagnostics_panel");
+    if props.diagnostics.is_empty() {
+        return Html::default();
+    }
+    let errors = props
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    let warnings = props.diagnostics.len() - errors;
+
+    let row = |diagnostic: &Diagnostic| {
+        let severity = match diagnostic.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        html! {
+            <li class={classes!("diagnostic", format!("diagnostic-{severity}"))}>
+                <span class="diagnostic-kind">{ diagnostic.error.kind() }</span>
+                { diagnostic.error.to_string() }
+            </li>
+        }
+    };
+
+    html! {
+        <div class="panel panel-diagnostics">
+            <div class="panel-header">
+                <span>{ "Diagnostics " }</span>
+                <span class="icon fas fa-triangle-exclamation"></span>
+            </div>
+            <div class="panel-content">
+                <div class="diagnostics-summary">
+                    { format!("{errors} error(s), {warnings} warning(s)") }
+                </div>
+                <ul class="diagnostics-list">
+                    { for props.diagnostics.iter().map(row) }
+                </ul>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,33 @@
+use crate::trace_span;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct ExplodeToolbarProps {
+    // 0 assembled, 1 fully exploded
+    pub amount: f32,
+    pub on_change: Callback<f32>,
+}
+
+#[function_component(ExplodeToolbar)]
+pub fn explode_toolbar(props: &ExplodeToolbarProps) -> Html {
+    trace_span!("explode_toolbar");
+    let on_explode = props.on_change.reform(|e: InputEvent| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        input.value_as_number() as f32
+    });
+    html! {
+        <div class="camera-toolbar">
+            <span class="section-title">{ "Explode" }</span>
+            <input
+                type="range"
+                class="section-slider"
+                min="0"
+                max="1"
+                step="0.01"
+                value={props.amount.to_string()}
+                oninput={on_explode}
+            />
+        </div>
+    }
+}
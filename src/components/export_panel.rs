@@ -0,0 +1,183 @@
+use crate::common::StepModel;
+use crate::common::export::{ExportFormat, ExportOptions, exported_parts};
+use crate::common::stl::StlFormat;
+use crate::common::zip::{unique_file_names, write_zip};
+use crate::trace_span;
+use gloo::file::{Blob, ObjectUrl};
+use gloo::timers::callback::Timeout;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlElement, HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+// (file name, content, mime type) of the parts `options` selects in
+// `format`: a single file as is, several files (one per part, or OBJ with its
+// MTL) in a zip
+fn export(
+    model: &StepModel,
+    format: ExportFormat,
+    options: ExportOptions,
+    per_part: bool,
+) -> (String, Vec<u8>, String) {
+    trace_span!("export");
+    let stem = model
+        .metadata
+        .header
+        .file_name
+        .rsplit_once('.')
+        .map_or(model.metadata.header.file_name.as_str(), |(stem, _)| stem)
+        .to_string();
+    let parts = exported_parts(model, options);
+
+    let files = if per_part {
+        let names = unique_file_names(
+            parts.iter().map(|&index| model.part_name(index)),
+            format.extension(),
+        );
+        parts
+            .iter()
+            .zip(names)
+            .flat_map(|(&index, name)| {
+                let part_stem = name
+                    .rsplit_once('.')
+                    .map_or(name.as_str(), |(stem, _)| stem);
+                format.write(model, options, part_stem, &[index])
+            })
+            .collect()
+    } else {
+        format.write(model, options, &stem, &parts)
+    };
+    match <[_; 1]>::try_from(files) {
+        Ok([(file_name, content)]) => (file_name, content, format.mime().to_string()),
+        Err(files) => {
+            let zip = write_zip(
+                files
+                    .iter()
+                    .map(|(name, content)| (name.as_str(), content.as_slice())),
+            );
+            (format!("{stem}.zip"), zip, "application/zip".to_string())
+        }
+    }
+}
+
+// hands `content` to the browser as a file download
+fn download(file_name: &str, content: &[u8], mime: &str) -> Result<(), String> {
+    let url = ObjectUrl::from(Blob::new_with_options(content, Some(mime)));
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let link = document
+        .create_element("a")
+        .map_err(|e| format!("{e:?}"))?
+        .dyn_into::<HtmlElement>()
+        .map_err(|_| "not an element")?;
+    link.set_attribute("href", &url)
+        .and_then(|_| link.set_attribute("download", file_name))
+        .map_err(|e| format!("{e:?}"))?;
+    link.click();
+    // the download reads the blob after the click returns
+    Timeout::new(60_000, move || drop(url)).forget();
+    Ok(())
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ExportPanelProps {
+    pub model: Option<Rc<StepModel>>,
+}
+
+#[function_component(ExportPanel)]
+pub fn export_panel(props: &ExportPanelProps) -> Html {
+    trace_span!("export_panel");
+    let format = use_state(|| ExportFormat::Stl(StlFormat::Binary));
+    let per_part = use_state(|| false);
+    let options = use_state(ExportOptions::default);
+    let error = use_state(|| None::<String>);
+
+    let Some(model) = props.model.clone() else {
+        return Html::default();
+    };
+    let count = exported_parts(&model, *options).len();
+
+    let on_format = {
+        let format = format.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            if let Some(choice) = ExportFormat::ALL.get(select.selected_index().max(0) as usize) {
+                format.set(*choice);
+            }
+        })
+    };
+    let on_per_part = {
+        let per_part = per_part.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            per_part.set(input.checked());
+        })
+    };
+    let on_option = |set: fn(&mut ExportOptions, bool)| {
+        let options = options.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut changed = *options;
+            set(&mut changed, input.checked());
+            options.set(changed);
+        })
+    };
+    let on_visible_only = on_option(|options, checked| options.visible_only = checked);
+    let on_apply_transforms = on_option(|options, checked| options.apply_transforms = checked);
+    let on_export = {
+        let format = *format;
+        let options = *options;
+        let per_part = *per_part;
+        let error = error.clone();
+        Callback::from(move |_| {
+            let (file_name, content, mime) = export(&model, format, options, per_part);
+            error.set(download(&file_name, &content, &mime).err());
+        })
+    };
+
+    html! {
+        <div class="panel panel-export">
+            <div class="panel-header">
+                <span>{ "Export " }</span>
+                <span class="icon fas fa-file-export"></span>
+            </div>
+            <div class="panel-content export-controls">
+                <select onchange={on_format}>
+                    { for ExportFormat::ALL.into_iter().map(|choice| html! {
+                        <option selected={choice == *format}>{ choice.label() }</option>
+                    }) }
+                </select>
+                <label>
+                    <input
+                        type="checkbox"
+                        checked={options.visible_only}
+                        onchange={on_visible_only}
+                    />
+                    { " Visible parts only" }
+                </label>
+                if format.has_transform_option() {
+                    <label>
+                        <input
+                            type="checkbox"
+                            checked={options.apply_transforms}
+                            onchange={on_apply_transforms}
+                        />
+                        { " Apply transforms" }
+                    </label>
+                }
+                <label>
+                    <input type="checkbox" checked={*per_part} onchange={on_per_part} />
+                    { " One file per part (zip)" }
+                </label>
+                <button class="btn btn-small" disabled={count == 0} onclick={on_export}>
+                    <span class="fas fa-download"></span>
+                    { format!(" Export {count} part(s)") }
+                </button>
+                if let Some(error) = error.as_ref() {
+                    <span class="section-warning">{ format!("Export failed: {error}") }</span>
+                }
+            </div>
+        </div>
+    }
+}
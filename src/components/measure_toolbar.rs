@@ -0,0 +1,107 @@
+use crate::common::Measurement;
+use crate::trace_span;
+use yew::prelude::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MeasureTool {
+    Distance,
+    Edge,
+    Angle,
+    Radius,
+}
+
+impl MeasureTool {
+    const ALL: [MeasureTool; 4] = [
+        MeasureTool::Distance,
+        MeasureTool::Edge,
+        MeasureTool::Angle,
+        MeasureTool::Radius,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            MeasureTool::Distance => "Distance",
+            MeasureTool::Edge => "Edge",
+            MeasureTool::Angle => "Angle",
+            MeasureTool::Radius => "Radius",
+        }
+    }
+
+    fn hint(self, pending: bool) -> &'static str {
+        match (self, pending) {
+            (MeasureTool::Distance, false) => "Click the first point",
+            (MeasureTool::Distance, true) => "Click the second point",
+            (MeasureTool::Edge, _) => "Click an edge",
+            (MeasureTool::Angle, false) => "Click a planar face",
+            (MeasureTool::Angle, true) => "Click the second planar face",
+            (MeasureTool::Radius, _) => "Click a cylindrical face",
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct MeasureToolbarProps {
+    pub tool: Option<MeasureTool>,
+    // the first pick of a two pick measurement is done
+    #[prop_or(false)]
+    pub pending: bool,
+    pub measurements: Vec<Measurement>,
+    // length unit of the model, see Metadata::length_unit
+    #[prop_or_default]
+    pub units: Option<String>,
+    // None leaves measuring, clicks select parts again
+    pub on_tool_change: Callback<Option<MeasureTool>>,
+    pub on_measurements_change: Callback<Vec<Measurement>>,
+    // drops all measurements and the pending pick
+    pub on_clear: Callback<()>,
+}
+
+#[function_component(MeasureToolbar)]
+pub fn measure_toolbar(props: &MeasureToolbarProps) -> Html {
+    trace_span!("measure_toolbar");
+    let tool_button = |tool: MeasureTool| {
+        let on_tool_change = props.on_tool_change.clone();
+        let active = props.tool == Some(tool);
+        html! {
+            <button
+                class={classes!("camera-button", active.then_some("camera-button-active"))}
+                // clicking the active tool leaves measuring
+                onclick={Callback::from(move |_| on_tool_change.emit((!active).then_some(tool)))}
+            >{ tool.label() }</button>
+        }
+    };
+    let remove = |index: usize| {
+        let measurements = props.measurements.clone();
+        let on_change = props.on_measurements_change.clone();
+        Callback::from(move |_| {
+            let mut measurements = measurements.clone();
+            measurements.remove(index);
+            on_change.emit(measurements);
+        })
+    };
+    let on_clear = props.on_clear.reform(|_: MouseEvent| ());
+
+    html! {
+        <div class="measure-panel">
+            <div class="camera-toolbar">
+                { for MeasureTool::ALL.into_iter().map(tool_button) }
+                if !props.measurements.is_empty() {
+                    <button class="camera-button" onclick={on_clear}>{ "Clear" }</button>
+                }
+            </div>
+            if let Some(tool) = props.tool {
+                <div class="measure-hint">{ tool.hint(props.pending) }</div>
+            }
+            if !props.measurements.is_empty() {
+                <ul class="measure-list">
+                    { for props.measurements.iter().enumerate().map(|(index, measurement)| html! {
+                        <li>
+                            <span>{ measurement.label(props.units.as_deref()) }</span>
+                            <button class="measure-remove" onclick={remove(index)}>{ "×" }</button>
+                        </li>
+                    }) }
+                </ul>
+            }
+        </div>
+    }
+}
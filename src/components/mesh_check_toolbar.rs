@@ -0,0 +1,44 @@
+use crate::common::mesh_check::MeshCheck;
+use crate::trace_span;
+use std::rc::Rc;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct MeshCheckToolbarProps {
+    // per render part
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
+    // problem edges are drawn over the model
+    pub active: bool,
+    pub on_toggle: Callback<bool>,
+}
+
+#[function_component(MeshCheckToolbar)]
+pub fn mesh_check_toolbar(props: &MeshCheckToolbarProps) -> Html {
+    trace_span!("mesh_check_toolbar");
+    let active = props.active;
+    let on_toggle = props.on_toggle.reform(move |_: MouseEvent| !active);
+    let problem_parts = props
+        .mesh_checks
+        .iter()
+        .filter(|check| check.problem_edges().next().is_some())
+        .count();
+    html! {
+        <div class="camera-toolbar">
+            <button
+                class={classes!("camera-button", active.then_some("camera-button-active"))}
+                onclick={on_toggle}
+            >{ "Mesh check" }</button>
+            if active {
+                <span class="section-title">
+                    {
+                        if problem_parts == 0 {
+                            "all parts closed".to_string()
+                        } else {
+                            format!("{problem_parts} part(s) with open, non-manifold or flipped edges")
+                        }
+                    }
+                </span>
+            }
+        </div>
+    }
+}
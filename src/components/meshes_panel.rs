@@ -1,5 +1,8 @@
+use crate::common::mass::MassProperties;
 use crate::trace_span;
-use web_sys::HtmlInputElement;
+use web_sys::{
+    Element, HtmlInputElement, ScrollBehavior, ScrollIntoViewOptions, ScrollLogicalPosition,
+};
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -9,11 +12,50 @@ pub struct MeshItemProps {
     pub triangle_count: usize,
     pub vertex_count: usize,
     pub visible: bool,
+    #[prop_or(false)]
+    pub selected: bool,
+    pub mass: MassProperties,
+    // what the mesh check found, empty for a clean mesh
+    #[prop_or_default]
+    pub mesh_issues: String,
+    #[prop_or(true)]
+    pub volume_reliable: bool,
+    #[prop_or_default]
+    pub unit: String,
+    #[prop_or_default]
+    pub density: Option<f64>,
     pub on_toggle_visibility: Callback<(String, bool)>,
+    // (id, extend the selection)
+    #[prop_or_default]
+    pub on_select: Callback<(String, bool)>,
 }
 
 #[function_component(MeshItem)]
 fn mesh_item(props: &MeshItemProps) -> Html {
+    let item_ref = use_node_ref();
+
+    // parts picked in the viewport are brought into view in the list
+    {
+        let item_ref = item_ref.clone();
+        use_effect_with(props.selected, move |selected| {
+            if let Some(item) = item_ref.cast::<Element>().filter(|_| *selected) {
+                let options = ScrollIntoViewOptions::new();
+                options.set_block(ScrollLogicalPosition::Nearest);
+                options.set_behavior(ScrollBehavior::Smooth);
+                item.scroll_into_view_with_scroll_into_view_options(&options);
+            }
+            || ()
+        });
+    }
+
+    let on_click = {
+        let id = props.id.clone();
+        let on_select = props.on_select.clone();
+        Callback::from(move |e: MouseEvent| {
+            on_select.emit((id.clone(), e.ctrl_key() || e.meta_key()));
+        })
+    };
+
     let on_visibility_change = {
         let id = props.id.clone();
         let on_toggle = props.on_toggle_visibility.clone();
@@ -25,12 +67,18 @@ fn mesh_item(props: &MeshItemProps) -> Html {
     };
 
     html! {
-        <div class="mesh-item">
+        <div
+            ref={item_ref}
+            class={classes!("mesh-item", props.selected.then_some("mesh-item-selected"))}
+            onclick={on_click}
+        >
             <div class="mesh-header">
                 <input
                     type="checkbox"
                     checked={props.visible}
                     onchange={on_visibility_change}
+                    // toggling visibility doesn't change the selection
+                    onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
                     class="mesh-visibility"
                 />
                 <span class="mesh-name">{&props.name}</span>
@@ -41,17 +89,92 @@ fn mesh_item(props: &MeshItemProps) -> Html {
                     {" | "}
                     {format!("{} vertices", props.vertex_count)}
                 </span>
+                if !props.mesh_issues.is_empty() {
+                    <div class="mesh-warning">{ &props.mesh_issues }</div>
+                }
+                // opening the properties doesn't change the selection
+                <details
+                    class="mesh-mass"
+                    onclick={Callback::from(|e: MouseEvent| e.stop_propagation())}
+                >
+                    <summary>{ "Mass properties" }</summary>
+                    { mass_details(&props.mass, props.volume_reliable, &props.unit, props.density) }
+                </details>
             </div>
         </div>
     }
 }
 
+fn format_vector(v: [f64; 3]) -> String {
+    format!("({:.4}, {:.4}, {:.4})", v[0], v[1], v[2])
+}
+
+fn mass_details(
+    mass: &MassProperties,
+    volume_reliable: bool,
+    unit: &str,
+    density: Option<f64>,
+) -> Html {
+    let (moments, axes) = mass.principal_axes();
+    // mass moments once a density is given, geometric ones otherwise
+    let (scale, moment_unit) = match density {
+        Some(density) => (density, format!("mass·{unit}²")),
+        None => (1.0, format!("{unit}⁵")),
+    };
+    let row = |label: &str, value: String| {
+        html! {
+            <div class="detail-item">
+                <dt class="detail-label">{ label }</dt>
+                <dd>{ value }</dd>
+            </div>
+        }
+    };
+    html! {
+        <dl class="details-list mesh-mass-list">
+            { row("Volume :", format!("{:.4} {unit}³", mass.volume)) }
+            if !volume_reliable {
+                <div class="mesh-warning">
+                    { "Open or inconsistently oriented mesh, the volume based values are unreliable" }
+                </div>
+            }
+            { row("Surface area :", format!("{:.4} {unit}²", mass.area)) }
+            if let Some(density) = density {
+                { row("Mass :", format!("{:.4}", mass.volume * density)) }
+            }
+            { row("Center of mass :", format_vector(mass.centroid)) }
+            <div class="detail-item">
+                <dt class="detail-label">{ format!("Inertia ({moment_unit}) :") }</dt>
+                <dd class="mesh-mass-matrix">
+                    { for mass.inertia.iter().map(|row| html! {
+                        <div>{ format_vector(row.map(|x| x * scale)) }</div>
+                    }) }
+                </dd>
+            </div>
+            <div class="detail-item">
+                <dt class="detail-label">{ "Principal moments :" }</dt>
+                <dd>{ format_vector(moments.map(|x| x * scale)) }</dd>
+            </div>
+            <div class="detail-item">
+                <dt class="detail-label">{ "Principal axes :" }</dt>
+                <dd class="mesh-mass-matrix">
+                    { for axes.iter().map(|axis| html! { <div>{ format_vector(*axis) }</div> }) }
+                </dd>
+            </div>
+        </dl>
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct MeshesPanelProps {
     pub meshes: Vec<MeshData>,
     pub on_visibility_change: Callback<(String, bool)>,
+    #[prop_or_default]
+    pub on_select: Callback<(String, bool)>,
     pub on_show_all: Callback<()>,
     pub on_hide_all: Callback<()>,
+    // length unit of the model, for the mass properties
+    #[prop_or_default]
+    pub unit: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
@@ -61,31 +184,56 @@ pub struct MeshData {
     pub triangle_count: usize,
     pub vertex_count: usize,
     pub visible: bool,
+    pub selected: bool,
+    pub mass: MassProperties,
+    pub mesh_issues: String,
+    pub volume_reliable: bool,
 }
 
-// FIXME : Scene need to be centered on visible meshes
-// example if only one mesh is visible, center on that mesh
-// if multiple meshes are visible, center on the bounding box of all visible meshes
-
-// TODO: Implement actual centering logic based on visible meshes
-// Consider performance - only recompute when visible meshes change
-
 #[function_component(MeshesPanel)]
 pub fn meshes_panel(props: &MeshesPanelProps) -> Html {
     trace_span!("meshes_panel");
 
+    // mass per unit volume, in the model's unit
+    let density = use_state(|| None::<f64>);
+    let on_density_input = {
+        let density = density.clone();
+        Callback::from(move |e: InputEvent| {
+            if let Some(input) = e.target_dyn_into::<HtmlInputElement>() {
+                let value = input.value().trim().parse::<f64>().ok();
+                density.set(value.filter(|value| value.is_finite() && *value > 0.0));
+            }
+        })
+    };
+    let all_reliable = props.meshes.iter().all(|mesh| mesh.volume_reliable);
+    let total = MassProperties::combine(
+        &props
+            .meshes
+            .iter()
+            .map(|mesh| mesh.mass)
+            .collect::<Vec<_>>(),
+    );
+
     let meshes_list = props
         .meshes
         .iter()
         .map(|mesh| {
             html! {
                 <MeshItem
+                    key={mesh.id.clone()}
                     id={mesh.id.clone()}
                     name={mesh.name.clone()}
                     triangle_count={mesh.triangle_count}
                     vertex_count={mesh.vertex_count}
                     visible={mesh.visible}
+                    selected={mesh.selected}
+                    mass={mesh.mass}
+                    mesh_issues={mesh.mesh_issues.clone()}
+                    volume_reliable={mesh.volume_reliable}
+                    unit={props.unit.clone()}
+                    density={*density}
                     on_toggle_visibility={props.on_visibility_change.clone()}
+                    on_select={props.on_select.clone()}
                 />
             }
         })
@@ -115,6 +263,24 @@ pub fn meshes_panel(props: &MeshesPanelProps) -> Html {
                         <span class="fas fa-eye-slash"></span> {" Hide All"}
                     </button>
                 </div>
+                <div class="mesh-density">
+                    <label>
+                        { format!("Density (per {}³) ", props.unit) }
+                        <input
+                            type="number"
+                            min="0"
+                            step="any"
+                            placeholder="none"
+                            oninput={on_density_input}
+                        />
+                    </label>
+                </div>
+                if props.meshes.len() > 1 {
+                    <details class="mesh-mass mesh-mass-total">
+                        <summary>{ "Total mass properties" }</summary>
+                        { mass_details(&total, all_reliable, &props.unit, *density) }
+                    </details>
+                }
                 <div class="meshes-list">
                     {meshes_list}
                 </div>
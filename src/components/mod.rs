@@ -1,4 +1,13 @@
 pub mod details_panel;
+pub mod diagnostics_panel;
+pub mod explode_toolbar;
+pub mod export_panel;
 pub mod file_history_panel;
+pub mod measure_toolbar;
+pub mod mesh_check_toolbar;
 pub mod meshes_panel;
+pub mod product_tree_panel;
+pub mod section_toolbar;
+pub mod selection_panel;
 pub mod stepmesh_panel;
+pub mod validation_panel;
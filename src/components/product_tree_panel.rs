@@ -0,0 +1,123 @@
+use crate::common::{ProductNode, StepModel};
+use crate::trace_span;
+use std::rc::Rc;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+struct ProductTreeItemProps {
+    node: ProductNode,
+    model: Rc<StepModel>,
+    depth: usize,
+    on_toggle_visibility: Callback<(usize, bool)>,
+}
+
+// (parts under the subtree, of which visible)
+fn subtree_visibility(node: &ProductNode, model: &StepModel) -> (usize, usize) {
+    let ids = node.subtree_ids();
+    model
+        .render_parts
+        .iter()
+        .filter(|part| part.node_id.is_some_and(|id| ids.contains(&id)))
+        .fold((0, 0), |(total, visible), part| {
+            (total + 1, visible + usize::from(part.visible))
+        })
+}
+
+#[function_component(ProductTreeItem)]
+fn product_tree_item(props: &ProductTreeItemProps) -> Html {
+    let expanded = use_state(|| props.depth < 2);
+    let node = &props.node;
+    let (part_count, visible_count) = subtree_visibility(node, &props.model);
+
+    let on_visibility_change = {
+        let id = node.id;
+        let on_toggle = props.on_toggle_visibility.clone();
+        Callback::from(move |e: Event| {
+            if let Some(input) = e.target_dyn_into::<HtmlInputElement>() {
+                on_toggle.emit((id, input.checked()));
+            }
+        })
+    };
+
+    let on_expand = {
+        let expanded = expanded.clone();
+        Callback::from(move |_| expanded.set(!*expanded))
+    };
+
+    let has_children = !node.children.is_empty();
+    let toggle_class = match (has_children, *expanded) {
+        (false, _) => "toggle toggle-leaf",
+        (true, true) => "toggle toggle-open",
+        (true, false) => "toggle toggle-closed",
+    };
+    let label = match &node.instance_name {
+        Some(instance) if instance != &node.name => format!("{} ({})", node.name, instance),
+        _ => node.name.clone(),
+    };
+
+    html! {
+        <li class="product-node">
+            <div class="product-node-header" style={format!("padding-left: {}px", props.depth * 14)}>
+                <span class={toggle_class} onclick={on_expand}></span>
+                <input
+                    type="checkbox"
+                    class="mesh-visibility"
+                    checked={part_count > 0 && visible_count == part_count}
+                    disabled={part_count == 0}
+                    onchange={on_visibility_change}
+                />
+                <span class="product-node-name" title={label.clone()}>{ label }</span>
+                if node.instance_count > 1 {
+                    <span class="product-node-count">{ format!("×{}", node.instance_count) }</span>
+                }
+            </div>
+            if has_children && *expanded {
+                <ul class="product-tree">
+                    { for node.children.iter().map(|child| html! {
+                        <ProductTreeItem
+                            node={child.clone()}
+                            model={props.model.clone()}
+                            depth={props.depth + 1}
+                            on_toggle_visibility={props.on_toggle_visibility.clone()}
+                        />
+                    }) }
+                </ul>
+            }
+        </li>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ProductTreePanelProps {
+    pub model: Rc<StepModel>,
+    pub on_node_visibility_change: Callback<(usize, bool)>,
+}
+
+#[function_component(ProductTreePanel)]
+pub fn product_tree_panel(props: &ProductTreePanelProps) -> Html {
+    trace_span!("product_tree_panel");
+
+    html! {
+        <div class="panel panel-product-tree">
+            <div class="panel-header">
+                <span class="panel-header-left">
+                    <span>{"Product structure "}</span>
+                    <span class="icon fas fa-sitemap"></span>
+                </span>
+            </div>
+            <div class="panel-content">
+                <ul class="product-tree product-tree-root">
+                    { for props.model.product_tree.iter().map(|node| html! {
+                        <ProductTreeItem
+                            node={node.clone()}
+                            model={props.model.clone()}
+                            depth={0}
+                            on_toggle_visibility={props.on_node_visibility_change.clone()}
+                        />
+                    }) }
+                </ul>
+            </div>
+        </div>
+    }
+}
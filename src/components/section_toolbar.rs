@@ -0,0 +1,121 @@
+use crate::common::{ClipPlane, MAX_CLIP_PLANES, section::offset_range};
+use crate::rendering::camera::Bounds;
+use crate::trace_span;
+use std::rc::Rc;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct SectionToolbarProps {
+    pub clip_planes: Vec<ClipPlane>,
+    // world bounds of the visible parts, the sliders span them
+    pub visible_bounds: Rc<Vec<Bounds>>,
+    // normal of a plane facing the viewer, cutting away the near half
+    pub view_normal: [f32; 3],
+    pub on_clip_planes_change: Callback<Vec<ClipPlane>>,
+}
+
+#[function_component(SectionToolbar)]
+pub fn section_toolbar(props: &SectionToolbarProps) -> Html {
+    trace_span!("section_toolbar");
+    let planes = &props.clip_planes;
+    // offsets the slider of a plane with `normal` spans
+    let range = |bounds: &[Bounds], normal: [f32; 3]| {
+        offset_range(bounds.iter().copied(), normal).unwrap_or((-1.0, 1.0))
+    };
+    let add_button = |label: &str, axis: Option<usize>| {
+        let planes = planes.clone();
+        let on_change = props.on_clip_planes_change.clone();
+        let view_normal = props.view_normal;
+        let visible_bounds = props.visible_bounds.clone();
+        let full = planes.len() >= MAX_CLIP_PLANES;
+        let onclick = Callback::from(move |_| {
+            let plane = match axis {
+                Some(axis) => ClipPlane::axis(axis, 0.0),
+                None => ClipPlane {
+                    normal: view_normal,
+                    offset: 0.0,
+                },
+            };
+            let (low, high) = range(&visible_bounds, plane.normal);
+            let mut planes = planes.clone();
+            planes.push(ClipPlane {
+                offset: (low + high) * 0.5,
+                ..plane
+            });
+            on_change.emit(planes);
+        });
+        html! {
+            <button
+                class="camera-button"
+                disabled={full}
+                onclick={onclick}
+            >{ label }</button>
+        }
+    };
+    let update = |index: usize, change: fn(ClipPlane, f32) -> ClipPlane| {
+        let planes = planes.clone();
+        let on_change = props.on_clip_planes_change.clone();
+        move |value: f32| {
+            let mut planes = planes.clone();
+            planes[index] = change(planes[index], value);
+            on_change.emit(planes);
+        }
+    };
+    let plane_row = |(index, plane): (usize, &ClipPlane)| {
+        let (low, high) = range(&props.visible_bounds, plane.normal);
+        let on_offset = {
+            let update = update(index, |plane, offset| ClipPlane { offset, ..plane });
+            Callback::from(move |e: InputEvent| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                update(input.value_as_number() as f32);
+            })
+        };
+        let on_flip = {
+            let update = update(index, |plane, _| plane.flipped());
+            Callback::from(move |_| update(0.0))
+        };
+        let on_remove = {
+            let planes = planes.clone();
+            let on_change = props.on_clip_planes_change.clone();
+            Callback::from(move |_| {
+                let mut planes = planes.clone();
+                planes.remove(index);
+                on_change.emit(planes);
+            })
+        };
+        html! {
+            <li>
+                <span class="section-label">{ plane.label() }</span>
+                <input
+                    type="range"
+                    class="section-slider"
+                    min={low.to_string()}
+                    max={high.to_string()}
+                    step={((high - low) / 1000.0).max(f32::EPSILON).to_string()}
+                    value={plane.offset.to_string()}
+                    oninput={on_offset}
+                />
+                <button class="measure-remove" title="Flip" onclick={on_flip}>{ "⇅" }</button>
+                <button class="measure-remove" title="Remove" onclick={on_remove}>{ "×" }</button>
+            </li>
+        }
+    };
+
+    html! {
+        <div class="measure-panel">
+            <div class="camera-toolbar">
+                <span class="section-title">{ "Section" }</span>
+                { add_button("X", Some(0)) }
+                { add_button("Y", Some(1)) }
+                { add_button("Z", Some(2)) }
+                { add_button("View", None) }
+            </div>
+            if !planes.is_empty() {
+                <ul class="measure-list">
+                    { for planes.iter().enumerate().map(plane_row) }
+                </ul>
+            }
+        </div>
+    }
+}
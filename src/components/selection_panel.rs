@@ -0,0 +1,161 @@
+use crate::common::mass::MassProperties;
+use crate::common::mesh_check::MeshCheck;
+use crate::common::{NA, StepModel};
+use crate::trace_span;
+use std::rc::Rc;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct SelectionPanelProps {
+    pub model: Option<Rc<StepModel>>,
+    // indices into the model's render parts
+    pub selected_parts: Vec<usize>,
+    #[prop_or_default]
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
+}
+
+#[derive(Clone, PartialEq)]
+struct PartStats {
+    name: String,
+    triangle_count: usize,
+    vertex_count: usize,
+    size: Option<[f32; 3]>,
+    volume: f64,
+    surface_area: f64,
+    // mesh check findings, empty for a closed and consistent mesh
+    mesh_issues: String,
+    volume_reliable: bool,
+}
+
+#[function_component(SelectionPanel)]
+pub fn selection_panel(props: &SelectionPanelProps) -> Html {
+    trace_span!("selection_panel");
+
+    let stats = use_memo(
+        (
+            props.model.clone(),
+            props.selected_parts.clone(),
+            props.mesh_checks.clone(),
+        ),
+        |(model, selected, checks)| {
+            let Some(model) = model else {
+                return Vec::new();
+            };
+            selected
+                .iter()
+                .filter_map(|&index| {
+                    let part = model.render_parts.get(index)?;
+                    let mass = MassProperties::of_part(part);
+                    Some(PartStats {
+                        name: model.part_name(index),
+                        triangle_count: part.mesh.indices.len() / 3,
+                        vertex_count: part.mesh.vertices.len(),
+                        size: part
+                            .world_bounds()
+                            .map(|(min, max)| std::array::from_fn(|axis| max[axis] - min[axis])),
+                        volume: mass.volume,
+                        surface_area: mass.area,
+                        mesh_issues: checks
+                            .get(index)
+                            .map(MeshCheck::summary)
+                            .unwrap_or_default(),
+                        volume_reliable: checks.get(index).is_none_or(MeshCheck::volume_reliable),
+                    })
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+
+    if stats.is_empty() {
+        return Html::default();
+    }
+    let unit = props
+        .model
+        .as_ref()
+        .and_then(|model| model.metadata.length_unit())
+        .unwrap_or_default();
+
+    let part_details = |part: &PartStats| {
+        html! {
+            <dl class="details-list selection-part">
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Name :" }</dt>
+                    <dd>{ &part.name }</dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Triangles :" }</dt>
+                    <dd>{ part.triangle_count }</dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Vertices :" }</dt>
+                    <dd>{ part.vertex_count }</dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Size :" }</dt>
+                    <dd>
+                        {
+                            part.size
+                                .map(|size| format!("{:.2} x {:.2} x {:.2} {}", size[0], size[1], size[2], unit))
+                                .unwrap_or_else(|| NA.to_string())
+                        }
+                    </dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Volume:" }</dt>
+                    <dd>
+                        { format!("{:.4} {}³", part.volume, unit) }
+                        if !part.volume_reliable {
+                            <span class="section-warning">{ " (unreliable)" }</span>
+                        }
+                    </dd>
+                </div>
+                if !part.mesh_issues.is_empty() {
+                    <div class="detail-item">
+                        <dt class="detail-label">{ "Mesh :" }</dt>
+                        <dd class="section-warning">{ &part.mesh_issues }</dd>
+                    </div>
+                }
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Surface:" }</dt>
+                    <dd>{ format!("{:.4} {}²", part.surface_area, unit) }</dd>
+                </div>
+            </dl>
+        }
+    };
+
+    let totals = (stats.len() > 1).then(|| {
+        html! {
+            <dl class="details-list selection-part">
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Selected parts :" }</dt>
+                    <dd>{ stats.len() }</dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Triangles :" }</dt>
+                    <dd>{ stats.iter().map(|part| part.triangle_count).sum::<usize>() }</dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Volume:" }</dt>
+                    <dd>{ format!("{:.4} {}³", stats.iter().map(|part| part.volume).sum::<f64>(), unit) }</dd>
+                </div>
+                <div class="detail-item">
+                    <dt class="detail-label">{ "Surface:" }</dt>
+                    <dd>{ format!("{:.4} {}²", stats.iter().map(|part| part.surface_area).sum::<f64>(), unit) }</dd>
+                </div>
+            </dl>
+        }
+    });
+
+    html! {
+        <div class="panel panel-selection">
+            <div class="panel-header">
+                <span>{ "Selection " }</span>
+                <span class="icon fas fa-arrow-pointer"></span>
+            </div>
+            <div class="panel-content">
+                { totals }
+                { for stats.iter().map(part_details) }
+            </div>
+        </div>
+    }
+}
@@ -1,6 +1,11 @@
+use crate::common::mass::MassProperties;
+use crate::common::mesh_check::MeshCheck;
 use crate::common::types::StepModel;
 use crate::{
-    components::meshes_panel::{MeshData, MeshesPanel},
+    components::{
+        meshes_panel::{MeshData, MeshesPanel},
+        product_tree_panel::ProductTreePanel,
+    },
     trace_span,
 };
 use std::rc::Rc;
@@ -11,30 +16,68 @@ pub struct StepMeshPanelProps {
     pub on_deselect: Callback<()>,
     pub model: Option<Rc<StepModel>>,
     pub on_visibility_change: Callback<(usize, bool)>,
+    pub on_node_visibility_change: Callback<(usize, bool)>,
+    pub selected_parts: Vec<usize>,
+    pub on_select_part: Callback<(Option<usize>, bool)>,
     pub on_show_all: Callback<()>,
     pub on_hide_all: Callback<()>,
+    #[prop_or_default]
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
 }
 
 #[function_component(StepMeshPanel)]
 pub fn step_mesh_panel(props: &StepMeshPanelProps) -> Html {
     trace_span!("step_mesh_panel");
 
-    let meshes = use_memo((props.model.clone(),), |(model,)| {
-        model.as_ref().map_or_else(Vec::new, |m| {
-            m.render_parts
-                .iter()
-                .enumerate()
-                .filter(|(_, part)| !part.vertices.is_empty() && !part.indices.is_empty())
-                .map(|(i, part)| MeshData {
-                    id: i.to_string(),
-                    name: format!("Mesh {}", i + 1),
-                    triangle_count: part.indices.len() / 3,
-                    vertex_count: part.vertices.len(),
-                    visible: part.visible,
+    // geometry only changes with the model, not with visibility or selection
+    let mass = use_memo(
+        props
+            .model
+            .as_ref()
+            .map(|model| (model.id.clone(), model.render_parts.len())),
+        {
+            let model = props.model.clone();
+            move |_| {
+                model.as_ref().map_or_else(Vec::new, |m| {
+                    m.render_parts
+                        .iter()
+                        .map(MassProperties::of_part)
+                        .collect::<Vec<_>>()
                 })
-                .collect()
-        })
-    });
+            }
+        },
+    );
+
+    let meshes = use_memo(
+        (
+            props.model.clone(),
+            props.selected_parts.clone(),
+            mass.clone(),
+            props.mesh_checks.clone(),
+        ),
+        |(model, selected, mass, checks)| {
+            model.as_ref().map_or_else(Vec::new, |m| {
+                m.render_parts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, part)| {
+                        !part.mesh.vertices.is_empty() && !part.mesh.indices.is_empty()
+                    })
+                    .map(|(i, part)| MeshData {
+                        id: i.to_string(),
+                        name: m.part_name(i),
+                        triangle_count: part.mesh.indices.len() / 3,
+                        vertex_count: part.mesh.vertices.len(),
+                        visible: part.visible,
+                        selected: selected.contains(&i),
+                        mass: mass.get(i).copied().unwrap_or_default(),
+                        mesh_issues: checks.get(i).map(MeshCheck::summary).unwrap_or_default(),
+                        volume_reliable: checks.get(i).is_none_or(MeshCheck::volume_reliable),
+                    })
+                    .collect()
+            })
+        },
+    );
 
     let on_visibility_change = {
         let cb = props.on_visibility_change.clone();
@@ -45,6 +88,15 @@ pub fn step_mesh_panel(props: &StepMeshPanelProps) -> Html {
         })
     };
 
+    let on_select = {
+        let cb = props.on_select_part.clone();
+        Callback::from(move |(id, extend): (String, bool)| {
+            if let Ok(index) = id.parse::<usize>() {
+                cb.emit((Some(index), extend));
+            }
+        })
+    };
+
     html! {
         <div class="panel panel-meshes">
             <div class="panel-content">
@@ -54,9 +106,17 @@ pub fn step_mesh_panel(props: &StepMeshPanelProps) -> Html {
                 >
                     <span class="fas fa-arrow-left"></span> { " Back"}
                 </button>
+                if let Some(model) = props.model.as_ref().filter(|m| !m.product_tree.is_empty()) {
+                    <ProductTreePanel
+                        model={model.clone()}
+                        on_node_visibility_change={props.on_node_visibility_change.clone()}
+                    />
+                }
                 <MeshesPanel
                     meshes={(*meshes).clone()}
+                    unit={props.model.as_ref().and_then(|m| m.metadata.length_unit()).unwrap_or_default().to_string()}
                     on_visibility_change={on_visibility_change}
+                    on_select={on_select}
                     on_show_all={props.on_show_all.clone()}
                     on_hide_all={props.on_hide_all.clone()}
                 />
@@ -0,0 +1,73 @@
+use crate::common::StepModel;
+use crate::common::validation::{ValidationCheck, validate};
+use crate::trace_span;
+use std::rc::Rc;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct ValidationPanelProps {
+    pub model: Option<Rc<StepModel>>,
+}
+
+#[function_component(ValidationPanel)]
+pub fn validation_panel(props: &ValidationPanelProps) -> Html {
+    trace_span!("validation_panel");
+    // geometry only changes with the model, not with visibility
+    let checks = {
+        let model = props.model.clone();
+        use_memo(
+            props
+                .model
+                .as_ref()
+                .map(|model| (model.id.clone(), model.render_parts.len())),
+            move |_| model.map(|model| validate(&model)).unwrap_or_default(),
+        )
+    };
+
+    if checks.is_empty() {
+        return Html::default();
+    }
+    let failed = checks.iter().filter(|check| !check.passed).count();
+
+    let row = |check: &ValidationCheck| {
+        html! {
+            <tr class={classes!(if check.passed { "validation-pass" } else { "validation-fail" })}>
+                <td>{ &check.name }</td>
+                <td>{ check.expected.label() }</td>
+                <td>{ check.expected.to_string() }</td>
+                <td>{ check.computed.to_string() }</td>
+                <td>{ format!("{:.3}%", check.relative_error * 100.0) }</td>
+                <td>{ if check.passed { "pass" } else { "fail" } }</td>
+            </tr>
+        }
+    };
+
+    html! {
+        <div class="panel panel-validation">
+            <div class="panel-header">
+                <span>{ "Validation properties " }</span>
+                <span class="icon fas fa-clipboard-check"></span>
+            </div>
+            <div class="panel-content">
+                <div class={classes!("validation-summary", (failed > 0).then_some("validation-fail"))}>
+                    { format!("{} of {} checks passed", checks.len() - failed, checks.len()) }
+                </div>
+                <table class="validation-table">
+                    <thead>
+                        <tr>
+                            <th>{ "Product" }</th>
+                            <th>{ "Property" }</th>
+                            <th>{ "File" }</th>
+                            <th>{ "Computed" }</th>
+                            <th>{ "Error" }</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for checks.iter().map(row) }
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
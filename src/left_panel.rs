@@ -1,3 +1,4 @@
+use crate::common::mesh_check::MeshCheck;
 use crate::common::types::StepModel;
 use crate::{
     common::FileIndexItem,
@@ -17,8 +18,13 @@ pub struct LeftPanelProps {
     pub on_deselect: Callback<()>,
     pub on_clear_history: Callback<()>,
     pub on_visibility_change: Callback<(usize, bool)>,
+    pub on_node_visibility_change: Callback<(usize, bool)>,
+    pub selected_parts: Vec<usize>,
+    pub on_select_part: Callback<(Option<usize>, bool)>,
     pub on_show_all: Callback<()>,
     pub on_hide_all: Callback<()>,
+    #[prop_or_default]
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
 }
 
 #[function_component(LeftPanel)]
@@ -38,8 +44,12 @@ pub fn left_panel(props: &LeftPanelProps) -> Html {
                     on_deselect={props.on_deselect.clone()}
                     model={props.model.clone()}
                     on_visibility_change={props.on_visibility_change.clone()}
+                    on_node_visibility_change={props.on_node_visibility_change.clone()}
+                    selected_parts={props.selected_parts.clone()}
+                    on_select_part={props.on_select_part.clone()}
                     on_show_all={props.on_show_all.clone()}
                     on_hide_all={props.on_hide_all.clone()}
+                    mesh_checks={props.mesh_checks.clone()}
                 />
             }
         </div>
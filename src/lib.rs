@@ -8,12 +8,15 @@ mod left_panel;
 mod main_panel;
 mod rendering;
 mod right_panel;
+mod worker;
 mod workspace;
 use apptracing::AppTracer;
 use apptracing::AppTracerTrait;
+use gloo::worker::Registrable;
 use header::Header;
 use main_panel::AppStepviz;
 use right_panel::RightPanel as MetadataPanel;
+use worker::StepWorker;
 use workspace::use_step_workspace;
 
 #[function_component(App)]
@@ -24,13 +27,6 @@ fn app() -> Html {
         .metadata
         .as_ref()
         .map(|m| m.header.file_name.clone());
-    let render_error_callback = {
-        let result = workspace.result.clone();
-        Callback::from(move |msg: String| {
-            result.set(Some(msg));
-        })
-    };
-
     html! {
         <div class="app-container">
             <Header file_name={current_file_name} />
@@ -46,8 +42,12 @@ fn app() -> Html {
                 on_deselect={workspace.actions.on_deselect.clone()}
                 on_clear_history={workspace.actions.on_clear_history.clone()}
                 on_visibility_change={workspace.actions.on_visibility_change.clone()}
+                on_node_visibility_change={workspace.actions.on_node_visibility_change.clone()}
+                selected_parts={(*workspace.selected_parts).clone()}
+                on_select_part={workspace.actions.on_select_part.clone()}
                 on_show_all={workspace.actions.on_show_all.clone()}
                 on_hide_all={workspace.actions.on_hide_all.clone()}
+                mesh_checks={workspace.mesh_checks.clone()}
             />
             </aside>
 
@@ -59,28 +59,44 @@ fn app() -> Html {
                         type="file"
                         accept=".step,.stp"
                         id="file-input"
-                        disabled={*workspace.is_processing}
                         onchange={workspace.actions.on_file_change.clone()}
                     />
                     {
                         if *workspace.is_processing {
-                            html! { <span class="processing-hint">{ "Processing STEP..." }</span> }
+                            let hint = workspace
+                                .progress
+                                .map(|progress| progress.to_string())
+                                .unwrap_or_else(|| "Processing STEP...".to_string());
+                            let on_cancel = workspace.actions.on_cancel_processing.clone();
+                            html! {
+                                <>
+                                    <span class="processing-hint">{ hint }</span>
+                                    <button
+                                        class="processing-cancel"
+                                        onclick={Callback::from(move |_| on_cancel.emit(()))}
+                                    >
+                                        { "Cancel" }
+                                    </button>
+                                </>
+                            }
                         } else {
                             Html::default()
                         }
                     }
                 </div>
 
-                //FIXME : invistigate the window resize issue
-                // when window is resized, the canvas doesn't resize properly
-                // i mean the scene resizes and it causes distortion in the rendering
-                // this might be due to the canvas not being properly notified of size changes
-                // consider adding a resize observer or force re-render on window resize
                 <AppStepviz
                     step_model={(*workspace.step_model).clone()}
                     is_processing={*workspace.is_processing}
                     metadata={(*workspace.metadata).clone()}
-                    on_render_error={render_error_callback}
+                    on_render_error={workspace.actions.on_error.clone()}
+                    msaa_samples={main_panel::msaa_samples_from_url()}
+                    selected_parts={(*workspace.selected_parts).clone()}
+                    on_select_part={workspace.actions.on_select_part.clone()}
+                    on_measurements_change={workspace.actions.on_measurements_change.clone()}
+                    clip_planes={(*workspace.clip_planes).clone()}
+                    on_clip_planes_change={workspace.actions.on_clip_planes_change.clone()}
+                    mesh_checks={workspace.mesh_checks.clone()}
                 />
                 <div class="result-message">
                     { workspace.result.as_ref().map(|msg| msg.as_str()).unwrap_or("") }
@@ -93,6 +109,11 @@ fn app() -> Html {
                 metadata={(*workspace.metadata).clone()}
                 on_calculate_volume={workspace.actions.on_calculate_volume.clone()}
                 on_calculate_surface={workspace.actions.on_calculate_surface.clone()}
+                model={(*workspace.step_model).clone()}
+                selected_parts={(*workspace.selected_parts).clone()}
+                clip_planes={(*workspace.clip_planes).clone()}
+                mesh_checks={workspace.mesh_checks.clone()}
+                diagnostics={workspace.diagnostics.entries.clone()}
             />
             </aside>
         </div>
@@ -102,6 +123,11 @@ fn app() -> Html {
 #[wasm_bindgen(start)]
 pub fn run_app() {
     AppTracer::init();
+    // the worker build (src/bin/step_worker.rs) links this crate too
+    if web_sys::window().is_none() {
+        StepWorker::registrar().register();
+        return;
+    }
     trace_span!("run_app");
     yew::Renderer::<App>::new().render();
 }
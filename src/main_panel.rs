@@ -1,14 +1,32 @@
 use crate::{
-    common::{Metadata, StepModel},
+    common::{
+        CAMERA_FOV_Y, ClipPlane, MSAA_SAMPLE_COUNT, Measurement, Metadata, RenderablePart,
+        StepModel, StepVizError, add3,
+        explode::ExplodePlan,
+        measure::{edge_near, plane_at, radius_at, snap_point},
+        mesh_check::MeshCheck,
+        scale3, sub3, transform_point,
+    },
+    components::{
+        explode_toolbar::ExplodeToolbar,
+        measure_toolbar::{MeasureTool, MeasureToolbar},
+        mesh_check_toolbar::MeshCheckToolbar,
+        section_toolbar::SectionToolbar,
+    },
     rendering::{
-        camera::CameraState,
+        camera::{Bounds, CameraState, visible_bounds},
+        picking::{Bvh, Hit},
         renderer::render_wgpu_on_canvas,
-        wgpu_state::{WgpuState, init_wgpu},
+        wgpu_state::{WgpuState, canvas_pixel_size, init_wgpu},
     },
     trace_span,
 };
+use gloo::events::EventListener;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::{JsCast, closure::Closure};
 use wasm_bindgen_futures::spawn_local;
-use web_sys::HtmlCanvasElement;
+use web_sys::{Element, HtmlCanvasElement, ResizeObserver};
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -17,35 +35,367 @@ pub struct MainPanelProps {
     #[prop_or(false)]
     pub is_processing: bool,
     pub metadata: Option<Metadata>,
-    pub on_render_error: Callback<String>,
+    pub on_render_error: Callback<StepVizError>,
+    #[prop_or(MSAA_SAMPLE_COUNT)]
+    pub msaa_samples: u32,
+    // indices into the model's render parts
+    #[prop_or_default]
+    pub selected_parts: Vec<usize>,
+    #[prop_or_default]
+    pub on_select_part: Callback<(Option<usize>, bool)>,
+    #[prop_or_default]
+    pub on_measurements_change: Callback<Vec<Measurement>>,
+    // section planes, what they cut away is neither drawn nor picked
+    #[prop_or_default]
+    pub clip_planes: Vec<ClipPlane>,
+    #[prop_or_default]
+    pub on_clip_planes_change: Callback<Vec<ClipPlane>>,
+    // per render part, drawn over the model on demand
+    #[prop_or_default]
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
 }
 
 use std::rc::Rc;
 
+// `?msaa=1` in the page url turns multisampling off, WebGPU only knows 1 and 4
+pub fn msaa_samples_from_url() -> u32 {
+    web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .and_then(|search| {
+            search.trim_start_matches('?').split('&').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.eq_ignore_ascii_case("msaa")
+                    .then(|| value.parse::<u32>().ok())
+                    .flatten()
+            })
+        })
+        .filter(|samples| matches!(samples, 1 | 4))
+        .unwrap_or(MSAA_SAMPLE_COUNT)
+}
+
+pub enum CameraAction {
+    // pointer deltas in CSS pixels
+    Orbit {
+        dx: f32,
+        dy: f32,
+    },
+    Pan {
+        dx: f32,
+        dy: f32,
+        viewport_height: f32,
+    },
+    Zoom {
+        factor: f32,
+        ndc: [f32; 2],
+        aspect: f32,
+    },
+    LookAt([f32; 3]),
+    // frames `bounds`, optionally from new (azimuth, elevation) angles
+    Frame {
+        bounds: Bounds,
+        aspect: f32,
+        angles: Option<(f32, f32)>,
+    },
+}
+
+// Reduced rather than set so several pointer events between two renders all
+// apply to the latest camera
+impl Reducible for CameraState {
+    type Action = CameraAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut camera = (*self).clone();
+        match action {
+            CameraAction::Orbit { dx, dy } => camera.orbit(dx, dy),
+            CameraAction::Pan {
+                dx,
+                dy,
+                viewport_height,
+            } => camera.pan(dx, dy, viewport_height),
+            CameraAction::Zoom {
+                factor,
+                ndc,
+                aspect,
+            } => camera.zoom(factor, ndc, aspect),
+            CameraAction::LookAt(point) => camera.look_at_point(point),
+            CameraAction::Frame {
+                bounds,
+                aspect,
+                angles,
+            } => {
+                if let Some((azimuth, elevation)) = angles {
+                    camera.azimuth = azimuth;
+                    camera.elevation = elevation;
+                }
+                camera.frame(bounds, aspect);
+            }
+        }
+        Rc::new(camera)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DragMode {
+    Orbit,
+    Pan,
+}
+
+// CSS size of the canvas, (1, 1) when it isn't mounted
+fn viewport(canvas_ref: &NodeRef) -> (f32, f32) {
+    canvas_ref
+        .cast::<HtmlCanvasElement>()
+        .map(|canvas| {
+            (
+                canvas.client_width().max(1) as f32,
+                canvas.client_height().max(1) as f32,
+            )
+        })
+        .unwrap_or((1.0, 1.0))
+}
+
+// pointer position relative to the canvas in normalized device coordinates
+fn pointer_ndc(canvas_ref: &NodeRef, x: f32, y: f32) -> [f32; 2] {
+    let (width, height) = viewport(canvas_ref);
+    [2.0 * x / width - 1.0, 1.0 - 2.0 * y / height]
+}
+
+// pointer travel (CSS pixels) above which a press counts as a drag, not a click
+const CLICK_TOLERANCE: f32 = 4.0;
+
+// visible part under the pointer, with the world point that was hit
+fn pick(
+    canvas_ref: &NodeRef,
+    camera: &CameraState,
+    model: &StepModel,
+    bvh: &Bvh,
+    clip_planes: &[ClipPlane],
+    event: &MouseEvent,
+) -> Option<(Hit, [f32; 3])> {
+    trace_span!("pick");
+    let (width, height) = viewport(canvas_ref);
+    let ndc = pointer_ndc(canvas_ref, event.offset_x() as f32, event.offset_y() as f32);
+    let (origin, direction) = camera.ray(ndc, width / height);
+    let hit = bvh.raycast(origin, direction, |part, point| {
+        model
+            .render_parts
+            .get(part)
+            .is_some_and(|part| part.visible)
+            && !clip_planes.iter().any(|plane| plane.cuts(point))
+    })?;
+    Some((hit, add3(origin, scale3(direction, hit.distance))))
+}
+
+// picking structure of a model with the exploded view offsets it was built with
+type BvhCache = Option<(String, usize, Rc<Vec<[f32; 3]>>, Rc<Bvh>)>;
+
+// Picking structure over all parts, hidden ones are skipped per ray. It is
+// built on the first pick after the model or the explode amount changed,
+// rebuilding it on every step of the explode slider would stall the drag.
+fn cached_bvh(
+    cache: &RefCell<BvhCache>,
+    model: &StepModel,
+    offsets: &Rc<Vec<[f32; 3]>>,
+) -> Rc<Bvh> {
+    let mut cache = cache.borrow_mut();
+    if let Some((id, len, built_offsets, bvh)) = cache.as_ref()
+        && *id == model.id
+        && *len == model.render_parts.len()
+        && Rc::ptr_eq(built_offsets, offsets)
+    {
+        return bvh.clone();
+    }
+    let bvh = Rc::new(Bvh::build(&model.render_parts, offsets));
+    *cache = Some((
+        model.id.clone(),
+        model.render_parts.len(),
+        offsets.clone(),
+        bvh.clone(),
+    ));
+    bvh
+}
+
+// how close (CSS pixels) a click has to be to a vertex or edge to snap to it
+const SNAP_RADIUS: f32 = 8.0;
+
+// first pick of a two pick measurement
+#[derive(Clone, PartialEq)]
+enum PendingPick {
+    Point([f64; 3]),
+    // anchor and outward normal of a planar face
+    Plane([f64; 3], [f64; 3]),
+}
+
+// Applies a click on `part` with `tool`: returns the finished measurement, or
+// the pick to wait on. Clicks that don't fit the tool (a curved face for an
+// angle ...) keep the pending pick.
+fn measure_click(
+    tool: MeasureTool,
+    pending: Option<&PendingPick>,
+    part: &RenderablePart,
+    hit: &Hit,
+    point: [f32; 3],
+    tolerance: f32,
+) -> (Option<Measurement>, Option<PendingPick>) {
+    trace_span!("measure_click");
+    let keep = pending.cloned();
+    match tool {
+        MeasureTool::Distance => {
+            let snapped = snap_point(part, hit.triangle, point, tolerance);
+            match pending {
+                Some(PendingPick::Point(from)) => (
+                    Some(Measurement::Distance {
+                        from: *from,
+                        to: snapped,
+                    }),
+                    None,
+                ),
+                _ => (None, Some(PendingPick::Point(snapped))),
+            }
+        }
+        MeasureTool::Edge => match edge_near(part, point, tolerance) {
+            Some(points) => (Some(Measurement::EdgeLength { points }), None),
+            None => (None, keep),
+        },
+        MeasureTool::Angle => match (plane_at(part, hit.triangle, point), pending) {
+            (Some((anchor, normal)), Some(PendingPick::Plane(first, first_normal))) => (
+                Some(Measurement::Angle {
+                    anchors: [*first, anchor],
+                    normals: [*first_normal, normal],
+                }),
+                None,
+            ),
+            (Some((anchor, normal)), _) => (None, Some(PendingPick::Plane(anchor, normal))),
+            (None, _) => (None, keep),
+        },
+        MeasureTool::Radius => match radius_at(part, hit.triangle, point) {
+            Some(measurement) => (Some(measurement), None),
+            None => (None, keep),
+        },
+    }
+}
+
+// Measurements and mesh problem edges (world space) drawn over the canvas, at
+// their projected positions
+fn annotation_overlay(
+    measurements: &[Measurement],
+    pending: Option<&PendingPick>,
+    problem_edges: &[[[f32; 3]; 2]],
+    camera: &CameraState,
+    (width, height): (f32, f32),
+    units: Option<&str>,
+) -> Html {
+    let to_screen = |point: [f64; 3]| {
+        let ndc = camera.project(point.map(|x| x as f32), width / height)?;
+        Some(((ndc[0] + 1.0) * 0.5 * width, (1.0 - ndc[1]) * 0.5 * height))
+    };
+    let marker =
+        |(x, y): (f32, f32)| html! { <circle cx={x.to_string()} cy={y.to_string()} r="3" /> };
+
+    let annotations = measurements.iter().filter_map(|measurement| {
+        let outline = measurement
+            .outline()
+            .into_iter()
+            .map(to_screen)
+            .collect::<Option<Vec<_>>>()?;
+        let (label_x, label_y) = to_screen(measurement.label_anchor())?;
+        let points = outline
+            .iter()
+            .map(|(x, y)| format!("{x},{y}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ends = [outline[0], outline[outline.len() - 1]];
+        Some(html! {
+            <g class="measurement">
+                <polyline points={points} />
+                { for ends.into_iter().map(marker) }
+                <text x={label_x.to_string()} y={(label_y - 8.0).to_string()}>
+                    { measurement.label(units) }
+                </text>
+            </g>
+        })
+    });
+    let problem_edges = problem_edges.iter().filter_map(|[a, b]| {
+        let a = to_screen(a.map(|x| x as f64))?;
+        let b = to_screen(b.map(|x| x as f64))?;
+        Some(html! {
+            <line
+                x1={a.0.to_string()}
+                y1={a.1.to_string()}
+                x2={b.0.to_string()}
+                y2={b.1.to_string()}
+            />
+        })
+    });
+    let pending = pending
+        .and_then(|pick| match pick {
+            PendingPick::Point(point) | PendingPick::Plane(point, _) => to_screen(*point),
+        })
+        .map(marker);
+
+    html! {
+        <svg class="measure-overlay" width={width.to_string()} height={height.to_string()}>
+            <g class="mesh-problems">{ for problem_edges }</g>
+            { for annotations }
+            <g class="measurement measurement-pending">{ pending }</g>
+        </svg>
+    }
+}
+
 #[function_component(AppStepviz)]
 pub fn stepviz_viewer(props: &MainPanelProps) -> Html {
     trace_span!("stepviz_viewer");
     let canvas_ref = use_node_ref();
     let wgpu_state = use_state(|| None::<Rc<WgpuState>>);
-    let camera_state = use_state(CameraState::default);
-    let is_dragging = use_state(|| false);
-    let last_mouse_pos = use_state(|| (0, 0));
-    let render_parts = use_state(Vec::new);
+    let camera_state = use_reducer(CameraState::default);
+    // active pointers (mouse, fingers, pens) by id, positions relative to the canvas
+    let pointers = use_mut_ref(HashMap::<i32, (f32, f32)>::new);
+    let drag_mode = use_mut_ref(|| DragMode::Orbit);
+    // how far the pointers moved since the first one went down
+    let pointer_travel = use_mut_ref(|| 0.0f32);
+    // drawing buffer size in device pixels, follows layout and zoom changes
+    let canvas_size = use_state(|| (0u32, 0u32));
+    // while a tool is active clicks measure instead of selecting
+    let measure_tool = use_state(|| None::<MeasureTool>);
+    let pending_pick = use_state(|| None::<PendingPick>);
+    // 0 assembled, 1 fully exploded
+    let explode_amount = use_state(|| 0.0f32);
+    // open, non-manifold and flipped edges drawn over the model
+    let show_mesh_problems = use_state(|| false);
+
+    let explode_plan = {
+        let step_model = props.step_model.clone();
+        let key = step_model
+            .as_ref()
+            .map(|model| (model.id.clone(), model.render_parts.len()));
+        use_memo(key, move |_| {
+            step_model
+                .map(|model| ExplodePlan::new(&model.render_parts, &model.product_tree))
+                .unwrap_or_default()
+        })
+    };
+    // exploded view moves per part, only ever applied to what is drawn and picked
+    let offsets = {
+        use_memo((explode_plan.clone(), *explode_amount), |(plan, amount)| {
+            plan.offsets(*amount)
+        })
+    };
+    let bvh = use_mut_ref(BvhCache::default);
 
     {
         let canvas_ref = canvas_ref.clone();
         let wgpu_state = wgpu_state.clone();
         let render_error_cb = props.on_render_error.clone();
 
-        use_effect_with((), move |_| {
+        use_effect_with(props.msaa_samples, move |msaa_samples| {
+            let msaa_samples = *msaa_samples;
             if let Some(canvas) = canvas_ref.cast::<HtmlCanvasElement>() {
                 spawn_local(async move {
-                    match init_wgpu(canvas).await {
+                    match init_wgpu(canvas, msaa_samples).await {
                         Ok(state) => {
                             wgpu_state.set(Some(Rc::new(state)));
                         }
                         Err(e) => {
-                            render_error_cb.emit(format!("WebGPU init failed: {e}"));
+                            render_error_cb.emit(e);
                         }
                     }
                 });
@@ -55,37 +405,83 @@ pub fn stepviz_viewer(props: &MainPanelProps) -> Html {
     }
 
     {
-        let render_parts = render_parts.clone();
-        use_effect_with(props.step_model.clone(), move |maybe_model| {
-            if let Some(model) = maybe_model {
-                render_parts.set((**model).render_parts.clone());
-            } else {
-                render_parts.set(Vec::new());
+        let canvas_ref = canvas_ref.clone();
+        let canvas_size = canvas_size.clone();
+
+        use_effect_with((), move |_| {
+            let mut observer = None;
+            let mut listener = None;
+            let mut on_resize = None;
+            if let Some(canvas) = canvas_ref.cast::<HtmlCanvasElement>() {
+                let update = {
+                    let canvas = canvas.clone();
+                    move || canvas_size.set(canvas_pixel_size(&canvas))
+                };
+                // the observer catches layout changes (window, sidebars), the
+                // window listener devicePixelRatio changes (zoom, moving the
+                // window to another screen) which leave the CSS size untouched
+                let callback = Closure::<dyn FnMut()>::new(update.clone());
+                observer = ResizeObserver::new(callback.as_ref().unchecked_ref()).ok();
+                if let Some(observer) = &observer {
+                    observer.observe(&canvas);
+                }
+                listener = web_sys::window()
+                    .map(|window| EventListener::new(&window, "resize", move |_| update()));
+                on_resize = Some(callback);
+            }
+            move || {
+                if let Some(observer) = observer {
+                    observer.disconnect();
+                }
+                drop((listener, on_resize));
             }
-            || ()
         });
     }
 
     {
         let wgpu_state_handle = wgpu_state.clone();
         let camera_state = camera_state.clone();
-        let render_parts = render_parts.clone();
+        let step_model = props.step_model.clone();
+        // models are compared by pointer, comparing them by value walks every vertex
+        let model_ptr = step_model.as_ref().map(|model| Rc::as_ptr(model) as usize);
         let render_error_cb = props.on_render_error.clone();
+        let canvas_ref = canvas_ref.clone();
 
         use_effect_with(
-            (wgpu_state_handle, camera_state, render_parts),
-            move |(wgpu_handle, camera, parts)| {
+            (
+                wgpu_state_handle,
+                camera_state,
+                model_ptr,
+                *canvas_size,
+                props.selected_parts.clone(),
+                props.clip_planes.clone(),
+                offsets.clone(),
+            ),
+            move |(wgpu_handle, camera, _, _, selected, clip_planes, offsets)| {
                 if let Some(wgpu_state) = &**wgpu_handle {
-                    if !parts.is_empty() {
-                        let parts_vec = (**parts).clone();
+                    if let Some(model) = step_model.filter(|model| !model.render_parts.is_empty()) {
+                        // the projection aspect is taken from the surface config
+                        if let Some(canvas) = canvas_ref.cast::<HtmlCanvasElement>() {
+                            wgpu_state.fit_canvas(&canvas);
+                        }
                         let camera_value = (**camera).clone();
                         let state = wgpu_state.clone();
                         let error_cb = render_error_cb.clone();
+                        let selected = selected.clone();
+                        let clip_planes = clip_planes.clone();
+                        let offsets = offsets.clone();
                         spawn_local(async move {
-                            if let Err(e) =
-                                render_wgpu_on_canvas(state, parts_vec, &camera_value).await
+                            if let Err(e) = render_wgpu_on_canvas(
+                                state,
+                                &model,
+                                &selected,
+                                &clip_planes,
+                                &offsets,
+                                &camera_value,
+                            )
+                            .await
                             {
-                                error_cb.emit(format!("Render error: {e}"));
+                                error_cb.emit(e);
                             }
                         });
                     }
@@ -100,35 +496,109 @@ pub fn stepviz_viewer(props: &MainPanelProps) -> Html {
             html! { <div class="canvas-processing-overlay">{ "Preparing 3D view..." }</div> }
         } else if props.step_model.is_none() {
             html! { <div class="empty-canvas-message">{ "Upload a STEP file to visualize it." }</div> }
-        } else if render_parts.is_empty() {
+        } else if props
+            .step_model
+            .as_ref()
+            .is_some_and(|model| model.render_parts.is_empty())
+        {
             html! { <div class="empty-canvas-message">{ "Parsing geometry..." }</div> }
         } else {
             Html::default()
         }
     };
 
-    let preset_button = |label: &str, azimuth: f32, elevation: f32, distance: f32| {
-        let camera_state = camera_state.clone();
+    // world bounds per part, computed once per model
+    let part_bounds = {
+        let step_model = props.step_model.clone();
+        let key = step_model
+            .as_ref()
+            .map(|model| (model.id.clone(), model.render_parts.len()));
+        use_memo(key, move |_| {
+            step_model
+                .map(|model| {
+                    model
+                        .render_parts
+                        .iter()
+                        .map(|part| part.world_bounds())
+                        .collect::<Vec<Option<Bounds>>>()
+                })
+                .unwrap_or_default()
+        })
+    };
+
+    // a half done measurement or an exploded view doesn't carry over to
+    // another model
+    {
+        let pending_pick = pending_pick.clone();
+        let explode_amount = explode_amount.clone();
+        let model_id = props.step_model.as_ref().map(|model| model.id.clone());
+        use_effect_with(model_id, move |_| {
+            pending_pick.set(None);
+            explode_amount.set(0.0);
+            || ()
+        });
+    }
+
+    // frames the visible parts, from new view angles when given
+    let fit_visible = {
+        let camera_state = camera_state.dispatcher();
+        let canvas_ref = canvas_ref.clone();
+        let step_model = props.step_model.clone();
+        let part_bounds = part_bounds.clone();
+        let offsets = offsets.clone();
+        Callback::from(move |angles: Option<(f32, f32)>| {
+            let Some(model) = step_model.as_ref() else {
+                return;
+            };
+            let visible = model.render_parts.iter().map(|part| part.visible);
+            let exploded: Vec<Option<Bounds>> = part_bounds
+                .iter()
+                .enumerate()
+                .map(|(index, bounds)| {
+                    let offset = offsets.get(index).copied().unwrap_or_default();
+                    bounds.map(|(min, max)| (add3(min, offset), add3(max, offset)))
+                })
+                .collect();
+            if let Some(bounds) = visible_bounds(&exploded, visible) {
+                let (width, height) = viewport(&canvas_ref);
+                camera_state.dispatch(CameraAction::Frame {
+                    bounds,
+                    aspect: width / height,
+                    angles,
+                });
+            }
+        })
+    };
+
+    // a newly shown model is framed as a whole
+    {
+        let fit_visible = fit_visible.clone();
+        use_effect_with((*part_bounds).clone(), move |bounds| {
+            if !bounds.is_empty() {
+                fit_visible.emit(None);
+            }
+            || ()
+        });
+    }
+
+    let preset_button = |label: &str, angles: Option<(f32, f32)>| {
+        let fit_visible = fit_visible.clone();
         html! {
             <button
                 class="camera-button"
-                onclick={Callback::from(move |_| {
-                    let mut new_camera = (*camera_state).clone();
-                    new_camera.azimuth = azimuth;
-                    new_camera.elevation = elevation;
-                    new_camera.distance = distance;
-                    camera_state.set(new_camera);
-                })}
+                onclick={Callback::from(move |_| fit_visible.emit(angles))}
             >{ label }</button>
         }
     };
 
+    let default_camera = CameraState::default();
     let camera_toolbar = html! {
         <div class="camera-toolbar">
-            { preset_button("Reset", CameraState::default().azimuth, CameraState::default().elevation, CameraState::default().distance) }
-            { preset_button("Iso", 0.8, 0.9, 3.0) }
-            { preset_button("Top", 0.0, 1.3, 2.5) }
-            { preset_button("Front", 0.0, 0.0, 3.0) }
+            { preset_button("Reset", Some((default_camera.azimuth, default_camera.elevation))) }
+            { preset_button("Fit", None) }
+            { preset_button("Iso", Some((0.8, 0.9))) }
+            { preset_button("Top", Some((0.0, 1.3))) }
+            { preset_button("Front", Some((0.0, 0.0))) }
         </div>
     };
 
@@ -137,66 +607,312 @@ pub fn stepviz_viewer(props: &MainPanelProps) -> Html {
             <div class="canvas-stats">
                 <div>{ format!("{} triangles", meta.triangle_count) }</div>
                 <div>{ format!("{} vertices", meta.vertex_count) }</div>
-                { meta.units.as_ref().map(|u| html!{ <div>{ format!("Units: {}", u) }</div> }).unwrap_or(Html::default()) }
+                { meta.length_unit().map(|u| html!{ <div>{ format!("Units: {}", u) }</div> }).unwrap_or(Html::default()) }
             </div>
         }
     } else {
         Html::default()
     };
-    let on_mouse_down = {
-        let is_dragging = is_dragging.clone();
-        let last_mouse_pos = last_mouse_pos.clone();
-        Callback::from(move |e: MouseEvent| {
-            is_dragging.set(true);
-            last_mouse_pos.set((e.client_x(), e.client_y()));
+    let on_pointer_down = {
+        let pointers = pointers.clone();
+        let drag_mode = drag_mode.clone();
+        let pointer_travel = pointer_travel.clone();
+        Callback::from(move |e: PointerEvent| {
+            if let Some(target) = e.target_dyn_into::<Element>() {
+                let _ = target.set_pointer_capture(e.pointer_id());
+            }
+            let mut pointers = pointers.borrow_mut();
+            if pointers.is_empty() {
+                *pointer_travel.borrow_mut() = 0.0;
+                // right or middle button (or shift) pans, everything else orbits
+                let pan = e.pointer_type() == "mouse" && (e.button() != 0 || e.shift_key());
+                *drag_mode.borrow_mut() = if pan { DragMode::Pan } else { DragMode::Orbit };
+            }
+            pointers.insert(e.pointer_id(), (e.offset_x() as f32, e.offset_y() as f32));
         })
     };
 
-    let on_mouse_up = {
-        let is_dragging = is_dragging.clone();
-        Callback::from(move |_| {
-            is_dragging.set(false);
+    let on_pointer_up = {
+        let pointers = pointers.clone();
+        Callback::from(move |e: PointerEvent| {
+            pointers.borrow_mut().remove(&e.pointer_id());
         })
     };
 
-    let on_mouse_move = {
-        let is_dragging = is_dragging.clone();
-        let last_mouse_pos = last_mouse_pos.clone();
+    let on_pointer_move = {
+        let pointers = pointers.clone();
+        let drag_mode = drag_mode.clone();
+        let pointer_travel = pointer_travel.clone();
+        let camera_state = camera_state.dispatcher();
+        let canvas_ref = canvas_ref.clone();
+        Callback::from(move |e: PointerEvent| {
+            let mut pointers = pointers.borrow_mut();
+            let position = (e.offset_x() as f32, e.offset_y() as f32);
+            let Some(last) = pointers.insert(e.pointer_id(), position) else {
+                // hovering, no button pressed
+                pointers.remove(&e.pointer_id());
+                return;
+            };
+            *pointer_travel.borrow_mut() += (position.0 - last.0).hypot(position.1 - last.1);
+            let (width, height) = viewport(&canvas_ref);
+            match pointers.len() {
+                1 => {
+                    let (dx, dy) = (position.0 - last.0, position.1 - last.1);
+                    camera_state.dispatch(match *drag_mode.borrow() {
+                        DragMode::Orbit => CameraAction::Orbit { dx, dy },
+                        DragMode::Pan => CameraAction::Pan {
+                            dx,
+                            dy,
+                            viewport_height: height,
+                        },
+                    });
+                }
+                2 => {
+                    // pinch zooms around the fingers, moving both fingers pans
+                    let Some(&other) = pointers
+                        .iter()
+                        .find(|(id, _)| **id != e.pointer_id())
+                        .map(|(_, position)| position)
+                    else {
+                        return;
+                    };
+                    let spread = |a: (f32, f32)| (a.0 - other.0).hypot(a.1 - other.1);
+                    let middle = |a: (f32, f32)| ((a.0 + other.0) * 0.5, (a.1 + other.1) * 0.5);
+                    let (before, after) = (middle(last), middle(position));
+                    if spread(position) > 1.0 {
+                        camera_state.dispatch(CameraAction::Zoom {
+                            factor: spread(last) / spread(position),
+                            ndc: pointer_ndc(&canvas_ref, after.0, after.1),
+                            aspect: width / height,
+                        });
+                    }
+                    camera_state.dispatch(CameraAction::Pan {
+                        dx: after.0 - before.0,
+                        dy: after.1 - before.1,
+                        viewport_height: height,
+                    });
+                }
+                _ => {}
+            }
+        })
+    };
+
+    let on_wheel = {
+        let camera_state = camera_state.dispatcher();
+        let canvas_ref = canvas_ref.clone();
+        Callback::from(move |e: WheelEvent| {
+            let (width, height) = viewport(&canvas_ref);
+            camera_state.dispatch(CameraAction::Zoom {
+                factor: if e.delta_y() > 0.0 { 1.1 } else { 1.0 / 1.1 },
+                ndc: pointer_ndc(&canvas_ref, e.offset_x() as f32, e.offset_y() as f32),
+                aspect: width / height,
+            });
+        })
+    };
+
+    // selects the part under the pointer, ctrl (cmd on macOS) adds to or
+    // removes from the selection, clicking empty space clears it
+    let on_click = {
         let camera_state = camera_state.clone();
+        let canvas_ref = canvas_ref.clone();
+        let step_model = props.step_model.clone();
+        let bvh = bvh.clone();
+        let pointer_travel = pointer_travel.clone();
+        let on_select_part = props.on_select_part.clone();
+        let measure_tool = measure_tool.clone();
+        let pending_pick = pending_pick.clone();
+        let on_measurements_change = props.on_measurements_change.clone();
+        let clip_planes = props.clip_planes.clone();
+        let offsets = offsets.clone();
         Callback::from(move |e: MouseEvent| {
-            if *is_dragging {
-                let (last_x, last_y) = *last_mouse_pos;
-                let dx = e.client_x() - last_x;
-                let dy = e.client_y() - last_y;
-                last_mouse_pos.set((e.client_x(), e.client_y()));
-
-                let mut new_camera_state = (*camera_state).clone();
-                new_camera_state.azimuth -= dx as f32 * 0.01;
-                new_camera_state.elevation =
-                    (new_camera_state.elevation - dy as f32 * 0.01).clamp(-1.57, 1.57);
-                camera_state.set(new_camera_state);
+            if *pointer_travel.borrow() > CLICK_TOLERANCE {
+                // end of an orbit or pan drag
+                return;
+            }
+            let Some(model) = step_model.as_ref() else {
+                return;
+            };
+            let bvh = cached_bvh(&bvh, model, &offsets);
+            let hit = pick(&canvas_ref, &camera_state, model, &bvh, &clip_planes, &e);
+            let Some(tool) = *measure_tool else {
+                on_select_part.emit((hit.map(|(hit, _)| hit.part), e.ctrl_key() || e.meta_key()));
+                return;
+            };
+            let Some((hit, point)) = hit else {
+                return;
+            };
+            let (_, height) = viewport(&canvas_ref);
+            // world size of SNAP_RADIUS pixels at the picked depth
+            let tolerance = SNAP_RADIUS * 2.0 * hit.distance * (CAMERA_FOV_Y * 0.5).tan() / height;
+            let part = &model.render_parts[hit.part];
+            // measured on the assembled model
+            let offset = offsets.get(hit.part).copied().unwrap_or_default();
+            let point = sub3(point, offset);
+            let (measurement, pending) =
+                measure_click(tool, pending_pick.as_ref(), part, &hit, point, tolerance);
+            pending_pick.set(pending);
+            if let Some(measurement) = measurement {
+                let mut measurements = model.measurements.clone();
+                measurements.push(measurement);
+                on_measurements_change.emit(measurements);
             }
         })
     };
 
+    // re-centers the orbit on the surface point under the pointer
+    let on_double_click = {
+        let camera_state = camera_state.clone();
+        let canvas_ref = canvas_ref.clone();
+        let step_model = props.step_model.clone();
+        let bvh = bvh.clone();
+        let clip_planes = props.clip_planes.clone();
+        let offsets = offsets.clone();
+        Callback::from(move |e: MouseEvent| {
+            let Some(model) = step_model.as_ref() else {
+                return;
+            };
+            let bvh = cached_bvh(&bvh, model, &offsets);
+            if let Some((_, point)) =
+                pick(&canvas_ref, &camera_state, model, &bvh, &clip_planes, &e)
+            {
+                camera_state.dispatch(CameraAction::LookAt(point));
+            }
+        })
+    };
+
+    let units = props
+        .step_model
+        .as_ref()
+        .and_then(|model| model.metadata.length_unit().map(str::to_string));
+    let measurements = props
+        .step_model
+        .as_ref()
+        .map(|model| model.measurements.clone())
+        .unwrap_or_default();
+
+    let on_tool_change = {
+        let measure_tool = measure_tool.clone();
+        let pending_pick = pending_pick.clone();
+        Callback::from(move |tool: Option<MeasureTool>| {
+            measure_tool.set(tool);
+            pending_pick.set(None);
+        })
+    };
+    let on_clear_measurements = {
+        let on_change = props.on_measurements_change.clone();
+        let pending_pick = pending_pick.clone();
+        Callback::from(move |_| {
+            pending_pick.set(None);
+            on_change.emit(Vec::new());
+        })
+    };
+
+    let section_bounds: Rc<Vec<Bounds>> = Rc::new(
+        props
+            .step_model
+            .as_ref()
+            .map(|model| {
+                part_bounds
+                    .iter()
+                    .zip(&model.render_parts)
+                    .filter_map(|(bounds, part)| bounds.filter(|_| part.visible))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    );
+    // the section toolbar's view plane faces the viewer
+    let view_normal = scale3(camera_state.basis().0, -1.0);
+
+    let on_explode = {
+        let explode_amount = explode_amount.clone();
+        Callback::from(move |amount: f32| explode_amount.set(amount))
+    };
+    let on_toggle_mesh_problems = {
+        let show_mesh_problems = show_mesh_problems.clone();
+        Callback::from(move |show: bool| show_mesh_problems.set(show))
+    };
+
+    // problem edges of the visible parts where they are drawn, exploded and
+    // not cut away
+    let problem_edges: Vec<[[f32; 3]; 2]> = match props.step_model.as_ref() {
+        Some(model) if *show_mesh_problems => model
+            .render_parts
+            .iter()
+            .zip(props.mesh_checks.iter())
+            .enumerate()
+            .filter(|(_, (part, _))| part.visible)
+            .flat_map(|(index, (part, check))| {
+                let offset = offsets.get(index).copied().unwrap_or([0.0; 3]);
+                check.problem_edges().map(move |edge| {
+                    edge.map(|point| add3(transform_point(&part.model_matrix, point), offset))
+                })
+            })
+            .filter(|edge| {
+                !edge
+                    .iter()
+                    .all(|point| props.clip_planes.iter().any(|plane| plane.cuts(*point)))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let overlay = if props.step_model.is_some() {
+        annotation_overlay(
+            &measurements,
+            pending_pick.as_ref(),
+            &problem_edges,
+            &camera_state,
+            viewport(&canvas_ref),
+            units.as_deref(),
+        )
+    } else {
+        Html::default()
+    };
+
     html! {
         <div class="canvas-wrapper">
             <canvas
                 id="step3D"
                 ref={canvas_ref}
                 class="main-panel-canvas"
-                onmousedown={on_mouse_down}
-                onmouseup={on_mouse_up}
-                onmousemove={on_mouse_move}
-                onwheel={Callback::from(move |e: WheelEvent| {
-                    let mut new_camera_state = (*camera_state).clone();
-                    new_camera_state.distance *= if e.delta_y() > 0.0 { 1.1 } else { 0.9 };
-                    camera_state.set(new_camera_state);
-                })}
+                onpointerdown={on_pointer_down}
+                onpointermove={on_pointer_move}
+                onpointerup={on_pointer_up.clone()}
+                onpointercancel={on_pointer_up}
+                onwheel={on_wheel}
+                onclick={on_click}
+                ondblclick={on_double_click}
+                oncontextmenu={Callback::from(|e: MouseEvent| e.prevent_default())}
             />
+            { overlay }
             <div class="canvas-ui">
                 { stats_overlay }
                 { camera_toolbar }
+                if props.step_model.as_ref().is_some_and(|model| model.render_parts.len() > 1) {
+                    <ExplodeToolbar amount={*explode_amount} on_change={on_explode} />
+                }
+                if props.step_model.is_some() && !props.mesh_checks.is_empty() {
+                    <MeshCheckToolbar
+                        mesh_checks={props.mesh_checks.clone()}
+                        active={*show_mesh_problems}
+                        on_toggle={on_toggle_mesh_problems}
+                    />
+                }
+                <MeasureToolbar
+                    tool={*measure_tool}
+                    pending={pending_pick.is_some()}
+                    measurements={measurements.clone()}
+                    units={units.clone()}
+                    on_tool_change={on_tool_change}
+                    on_measurements_change={props.on_measurements_change.clone()}
+                    on_clear={on_clear_measurements}
+                />
+                <SectionToolbar
+                    clip_planes={props.clip_planes.clone()}
+                    visible_bounds={section_bounds}
+                    view_normal={view_normal}
+                    on_clip_planes_change={props.on_clip_planes_change.clone()}
+                />
             </div>
             { canvas_overlay }
         </div>
@@ -1,9 +1,19 @@
+use crate::common::{CAMERA_FOV_Y, add3, cross3, dot3, length3, normalize3, scale3, sub3};
 use crate::trace_span;
+
+// (min, max) corners of an axis aligned box, in world space
+pub type Bounds = ([f32; 3], [f32; 3]);
+
+const UP: [f32; 3] = [0.0, 1.0, 0.0];
+// keeps the eye off the poles where the view basis degenerates
+const MAX_ELEVATION: f32 = 1.55;
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct CameraState {
     pub azimuth: f32,
     pub elevation: f32,
     pub distance: f32,
+    // orbit center, in world (model) coordinates
     pub target: [f32; 3],
 }
 
@@ -12,11 +22,8 @@ impl Default for CameraState {
         Self {
             azimuth: 0.5,
             elevation: 0.5,
-            //wtf
-            //FIXME: distance is way too big
-            // should be around 10-20
-            // or maybe it should be computed based on the model size?
-            distance: 500.0,
+            // replaced by `frame` once a model is shown
+            distance: 10.0,
             target: [0.0, 0.0, 0.0],
         }
     }
@@ -34,3 +41,135 @@ pub fn compute_eye_position(camera: &CameraState) -> [f32; 3] {
 
     [eye_x, eye_y, eye_z]
 }
+
+impl CameraState {
+    // forward, right and up unit vectors of the view, matching create_look_at_matrix
+    pub fn basis(&self) -> ([f32; 3], [f32; 3], [f32; 3]) {
+        let forward = normalize3(sub3(self.target, compute_eye_position(self)));
+        let right = normalize3(cross3(forward, UP));
+        let up = cross3(right, forward);
+        (forward, right, up)
+    }
+
+    // half height of the view at the target's depth
+    fn half_height(&self) -> f32 {
+        self.distance * (CAMERA_FOV_Y * 0.5).tan()
+    }
+
+    // drag deltas in CSS pixels, the model follows the pointer
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        trace_span!("CameraState::orbit");
+        self.azimuth += dx * 0.01;
+        self.elevation = (self.elevation + dy * 0.01).clamp(-MAX_ELEVATION, MAX_ELEVATION);
+    }
+
+    // moves the target in the view plane so the point under the pointer
+    // stays under it; `viewport_height` in the same unit as the deltas
+    pub fn pan(&mut self, dx: f32, dy: f32, viewport_height: f32) {
+        trace_span!("CameraState::pan");
+        let (_, right, up) = self.basis();
+        let units_per_pixel = 2.0 * self.half_height() / viewport_height.max(1.0);
+        let offset = add3(scale3(right, -dx), scale3(up, dy));
+        self.target = add3(self.target, scale3(offset, units_per_pixel));
+    }
+
+    // Scales the distance by `factor` keeping the point under `ndc` (x right,
+    // y up, both in [-1, 1]) fixed on screen
+    pub fn zoom(&mut self, factor: f32, ndc: [f32; 2], aspect: f32) {
+        trace_span!("CameraState::zoom");
+        let (_, right, up) = self.basis();
+        let half_height = self.half_height();
+        let under_pointer = add3(
+            scale3(right, ndc[0] * half_height * aspect),
+            scale3(up, ndc[1] * half_height),
+        );
+        self.target = add3(self.target, scale3(under_pointer, 1.0 - factor));
+        self.distance *= factor;
+    }
+
+    // Turns the camera in place to orbit around `point`
+    pub fn look_at_point(&mut self, point: [f32; 3]) {
+        trace_span!("CameraState::look_at_point");
+        let offset = sub3(compute_eye_position(self), point);
+        let distance = length3(offset);
+        if distance <= f32::EPSILON {
+            return;
+        }
+        self.target = point;
+        self.distance = distance;
+        self.elevation = (offset[1] / distance)
+            .asin()
+            .clamp(-MAX_ELEVATION, MAX_ELEVATION);
+        self.azimuth = offset[2].atan2(offset[0]);
+    }
+
+    // Centers `bounds` and backs off until its bounding sphere fits the view
+    pub fn frame(&mut self, bounds: Bounds, aspect: f32) {
+        trace_span!("CameraState::frame");
+        let (min, max) = bounds;
+        self.target = scale3(add3(min, max), 0.5);
+        let radius = (length3(sub3(max, min)) * 0.5).max(1e-3);
+        let half_fov_y = CAMERA_FOV_Y * 0.5;
+        let half_fov_x = (half_fov_y.tan() * aspect).atan();
+        self.distance = radius * 1.05 / half_fov_y.min(half_fov_x).sin();
+    }
+
+    // ray through `ndc`, origin at the eye, unit direction
+    pub fn ray(&self, ndc: [f32; 2], aspect: f32) -> ([f32; 3], [f32; 3]) {
+        let (forward, right, up) = self.basis();
+        let tan = (CAMERA_FOV_Y * 0.5).tan();
+        let direction = add3(
+            forward,
+            add3(
+                scale3(right, ndc[0] * tan * aspect),
+                scale3(up, ndc[1] * tan),
+            ),
+        );
+        (compute_eye_position(self), normalize3(direction))
+    }
+
+    // inverse of `ray`: normalized device coordinates of a world point,
+    // None when it is behind the eye
+    pub fn project(&self, point: [f32; 3], aspect: f32) -> Option<[f32; 2]> {
+        let (forward, right, up) = self.basis();
+        let offset = sub3(point, compute_eye_position(self));
+        let depth = dot3(offset, forward);
+        if depth <= f32::EPSILON {
+            return None;
+        }
+        let tan = (CAMERA_FOV_Y * 0.5).tan();
+        Some([
+            dot3(offset, right) / (depth * tan * aspect),
+            dot3(offset, up) / (depth * tan),
+        ])
+    }
+}
+
+// union of the bounds of visible parts, None when nothing is visible
+pub fn visible_bounds(
+    bounds: &[Option<Bounds>],
+    visible: impl Iterator<Item = bool>,
+) -> Option<Bounds> {
+    bounds
+        .iter()
+        .zip(visible)
+        .filter_map(|(bounds, visible)| bounds.filter(|_| visible))
+        .reduce(|(min_a, max_a), (min_b, max_b)| {
+            (
+                std::array::from_fn(|axis| min_a[axis].min(min_b[axis])),
+                std::array::from_fn(|axis| max_a[axis].max(max_b[axis])),
+            )
+        })
+}
+
+// Near/far planes hugging the model's bounding sphere (`radius` around its
+// center) as seen from `eye_distance`, with a small margin so the sphere is
+// never clipped
+pub fn clip_planes(eye_distance: f32, radius: f32) -> (f32, f32) {
+    trace_span!("clip_planes");
+    let radius = radius.max(f32::EPSILON) * 1.05;
+    let min_near = radius * crate::common::constants::NEAR_PLANE_MIN_RATIO;
+    let near = (eye_distance - radius).max(min_near);
+    let far = (eye_distance + radius).max(near * 2.0);
+    (near, far)
+}
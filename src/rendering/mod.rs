@@ -1,3 +1,5 @@
 pub mod camera;
+pub mod picking;
 pub mod renderer;
+pub mod scene;
 pub mod wgpu_state;
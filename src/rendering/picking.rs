@@ -0,0 +1,371 @@
+use crate::common::{RenderablePart, add3, cross3, dot3, scale3, sub3, transform_point};
+use crate::trace_span;
+
+// triangles per leaf, below this splitting costs more than testing
+const LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    // index into the parts the BVH was built from
+    pub part: usize,
+    // triangle within that part, `indices[3 * triangle..]`
+    pub triangle: usize,
+    // distance along the ray direction
+    pub distance: f32,
+}
+
+struct Triangle {
+    corners: [[f32; 3]; 3],
+    part: usize,
+    index: usize,
+}
+
+impl Triangle {
+    fn centroid(&self, axis: usize) -> f32 {
+        (self.corners[0][axis] + self.corners[1][axis] + self.corners[2][axis]) / 3.0
+    }
+}
+
+// Leaves hold `count` triangles from `start`. Inner nodes have count 0, their
+// first child right after them and the second one at `start`.
+struct Node {
+    min: [f32; 3],
+    max: [f32; 3],
+    start: usize,
+    count: usize,
+}
+
+// Bounding volume hierarchy over the world space triangles of all parts
+// (hidden ones included, visibility is checked while casting)
+pub struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    // `offsets` are the exploded view moves of the parts, missing ones
+    // don't move
+    pub fn build(parts: &[RenderablePart], offsets: &[[f32; 3]]) -> Self {
+        trace_span!("Bvh::build");
+        let mut triangles = Vec::new();
+        for (index, part) in parts.iter().enumerate() {
+            let offset = offsets.get(index).copied().unwrap_or_default();
+            for (triangle_index, triangle) in part.mesh.indices.chunks_exact(3).enumerate() {
+                if let Some(corners) = triangle_corners(part, triangle) {
+                    let corners = corners.map(|corner| add3(corner, offset));
+                    triangles.push(Triangle {
+                        corners,
+                        part: index,
+                        index: triangle_index,
+                    });
+                }
+            }
+        }
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * triangles.len() / LEAF_SIZE + 1),
+            triangles,
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.build_node(0, bvh.triangles.len());
+        }
+        bvh
+    }
+
+    // splits triangles[start..end] at the centroid median of the longest axis
+    fn build_node(&mut self, start: usize, end: usize) {
+        let (min, max) = bounds(&self.triangles[start..end]);
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            start,
+            count: end - start,
+        });
+        if end - start <= LEAF_SIZE {
+            return;
+        }
+        let extent = sub3(max, min);
+        let axis = (0..3)
+            .max_by(|a, b| extent[*a].total_cmp(&extent[*b]))
+            .unwrap_or(0);
+        let middle = start + (end - start) / 2;
+        self.triangles[start..end].select_nth_unstable_by(middle - start, |a, b| {
+            a.centroid(axis).total_cmp(&b.centroid(axis))
+        });
+        self.build_node(start, middle);
+        let second = self.nodes.len();
+        self.build_node(middle, end);
+        self.nodes[node].start = second;
+        self.nodes[node].count = 0;
+    }
+
+    // Nearest triangle hit by the ray that `accept` takes, given the part and
+    // the world point hit (hidden parts, points cut away by section planes)
+    pub fn raycast(
+        &self,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        accept: impl Fn(usize, [f32; 3]) -> bool,
+    ) -> Option<Hit> {
+        trace_span!("Bvh::raycast");
+        let inv_direction = direction.map(|d| 1.0 / d);
+        let mut nearest: Option<Hit> = None;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = nearest.map_or(f32::INFINITY, |hit| hit.distance);
+            if !hits_box(origin, inv_direction, node.min, node.max, limit) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start);
+                stack.push(index + 1);
+                continue;
+            }
+            for triangle in &self.triangles[node.start..node.start + node.count] {
+                if let Some(distance) = intersect_triangle(origin, direction, triangle.corners)
+                    && distance < limit.min(nearest.map_or(f32::INFINITY, |hit| hit.distance))
+                    && accept(triangle.part, add3(origin, scale3(direction, distance)))
+                {
+                    nearest = Some(Hit {
+                        part: triangle.part,
+                        triangle: triangle.index,
+                        distance,
+                    });
+                }
+            }
+        }
+        nearest
+    }
+}
+
+fn triangle_corners(part: &RenderablePart, triangle: &[u32]) -> Option<[[f32; 3]; 3]> {
+    let corner = |i: usize| {
+        let vertex = part.mesh.vertices.get(triangle[i] as usize)?;
+        Some(transform_point(&part.model_matrix, vertex.position))
+    };
+    Some([corner(0)?, corner(1)?, corner(2)?])
+}
+
+fn bounds(triangles: &[Triangle]) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for corner in triangles.iter().flat_map(|triangle| &triangle.corners) {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(corner[axis]);
+            max[axis] = max[axis].max(corner[axis]);
+        }
+    }
+    (min, max)
+}
+
+// slab test, true when the ray enters the box before `limit`
+fn hits_box(
+    origin: [f32; 3],
+    inv_direction: [f32; 3],
+    min: [f32; 3],
+    max: [f32; 3],
+    limit: f32,
+) -> bool {
+    let mut near = 0.0f32;
+    let mut far = limit;
+    for axis in 0..3 {
+        let t1 = (min[axis] - origin[axis]) * inv_direction[axis];
+        let t2 = (max[axis] - origin[axis]) * inv_direction[axis];
+        // NaN (ray parallel to and on a slab plane) keeps the current range
+        near = near.max(t1.min(t2));
+        far = far.min(t1.max(t2));
+    }
+    near <= far
+}
+
+// Möller–Trumbore, both faces count as hits
+fn intersect_triangle(
+    origin: [f32; 3],
+    direction: [f32; 3],
+    corners: [[f32; 3]; 3],
+) -> Option<f32> {
+    let edge1 = sub3(corners[1], corners[0]);
+    let edge2 = sub3(corners[2], corners[0]);
+    let p = cross3(direction, edge2);
+    let det = dot3(edge1, p);
+    if det.abs() <= f32::EPSILON * dot3(edge1, edge1).max(dot3(edge2, edge2)) {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = sub3(origin, corners[0]);
+    let u = dot3(s, p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = cross3(s, edge1);
+    let v = dot3(direction, q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = dot3(edge2, q) * inv_det;
+    (t > 0.0).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::fixtures::cuboid;
+    use crate::common::normalize3;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn brute_force(parts: &[RenderablePart], origin: [f32; 3], direction: [f32; 3]) -> Option<Hit> {
+        let mut nearest: Option<Hit> = None;
+        for (index, part) in parts.iter().enumerate().filter(|(_, p)| p.visible) {
+            for (triangle_index, triangle) in part.mesh.indices.chunks_exact(3).enumerate() {
+                let corners = triangle_corners(part, triangle).unwrap();
+                if let Some(distance) = intersect_triangle(origin, direction, corners)
+                    && nearest.is_none_or(|hit| distance < hit.distance)
+                {
+                    nearest = Some(Hit {
+                        part: index,
+                        triangle: triangle_index,
+                        distance,
+                    });
+                }
+            }
+        }
+        nearest
+    }
+
+    #[test]
+    fn picks_the_nearest_part() {
+        let parts = vec![
+            cuboid([1.0; 3], [0.0, 0.0, 0.0]),
+            cuboid([1.0; 3], [0.0, 0.0, -3.0]),
+        ];
+        let bvh = Bvh::build(&parts, &[]);
+        let hit = bvh
+            .raycast([0.5, 0.5, 10.0], [0.0, 0.0, -1.0], |_, _| true)
+            .unwrap();
+        assert_eq!(hit.part, 0);
+        // the top face, z = 1
+        assert!([10, 11].contains(&hit.triangle), "{hit:?}");
+        assert!((hit.distance - 9.0).abs() < 1e-5, "{hit:?}");
+
+        // hidden parts are looked through
+        let hit = bvh
+            .raycast([0.5, 0.5, 10.0], [0.0, 0.0, -1.0], |part, _| part != 0)
+            .unwrap();
+        assert_eq!(hit.part, 1);
+        assert!((hit.distance - 12.0).abs() < 1e-5, "{hit:?}");
+
+        assert_eq!(
+            bvh.raycast([5.0, 5.0, 10.0], [0.0, 0.0, -1.0], |_, _| true),
+            None
+        );
+        // nothing behind the origin
+        assert_eq!(
+            bvh.raycast([0.5, 0.5, 10.0], [0.0, 0.0, 1.0], |_, _| true),
+            None
+        );
+    }
+
+    #[test]
+    fn placements_are_applied() {
+        let mut part = cuboid([1.0; 3], [0.0, 0.0, 0.0]);
+        // scale by 2 then move along x
+        part.model_matrix[0] = 2.0;
+        part.model_matrix[5] = 2.0;
+        part.model_matrix[10] = 2.0;
+        part.model_matrix[12] = 10.0;
+        let bvh = Bvh::build(&[part], &[]);
+        let hit = bvh
+            .raycast([11.5, 1.5, 10.0], [0.0, 0.0, -1.0], |_, _| true)
+            .unwrap();
+        assert!((hit.distance - 8.0).abs() < 1e-5, "{hit:?}");
+        assert_eq!(
+            bvh.raycast([0.5, 0.5, 10.0], [0.0, 0.0, -1.0], |_, _| true),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut parts: Vec<RenderablePart> = (0..40)
+            .map(|_| {
+                cuboid(
+                    [1.0; 3],
+                    [
+                        rng.gen_range(-10.0..10.0),
+                        rng.gen_range(-10.0..10.0),
+                        rng.gen_range(-10.0..10.0),
+                    ],
+                )
+            })
+            .collect();
+        for part in parts.iter_mut().step_by(3) {
+            part.visible = false;
+        }
+        let bvh = Bvh::build(&parts, &[]);
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = [
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-20.0..20.0),
+                rng.gen_range(-20.0..20.0),
+            ];
+            // aimed around a random cube so a fair share of rays hit
+            let aim = parts[rng.gen_range(0..parts.len())].model_matrix;
+            let target = [
+                aim[12] + rng.gen_range(-0.5..1.5),
+                aim[13] + rng.gen_range(-0.5..1.5),
+                aim[14] + rng.gen_range(-0.5..1.5),
+            ];
+            let direction = normalize3(sub3(target, origin));
+            let expected = brute_force(&parts, origin, direction);
+            let actual = bvh.raycast(origin, direction, |part, _| parts[part].visible);
+            assert_eq!(
+                actual.map(|hit| (hit.part, hit.triangle)),
+                expected.map(|hit| (hit.part, hit.triangle))
+            );
+            if let (Some(actual), Some(expected)) = (actual, expected) {
+                assert!((actual.distance - expected.distance).abs() < 1e-4);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100, "only {hits} rays hit something");
+    }
+
+    #[test]
+    fn offsets_move_parts() {
+        let parts = vec![
+            cuboid([1.0; 3], [0.0, 0.0, 0.0]),
+            cuboid([1.0; 3], [0.0, 0.0, 0.0]),
+        ];
+        let bvh = Bvh::build(&parts, &[[0.0; 3], [5.0, 0.0, 0.0]]);
+        let hit = bvh
+            .raycast([5.5, 0.5, 10.0], [0.0, 0.0, -1.0], |_, _| true)
+            .unwrap();
+        assert_eq!(hit.part, 1);
+        assert!((hit.distance - 9.0).abs() < 1e-5, "{hit:?}");
+    }
+
+    #[test]
+    fn rejected_points_are_looked_through() {
+        let parts = vec![cuboid([1.0; 3], [0.0, 0.0, 0.0])];
+        let bvh = Bvh::build(&parts, &[]);
+        // everything above z = 0.5 is cut away, the ray goes on to the bottom face
+        let hit = bvh
+            .raycast([0.5, 0.5, 10.0], [0.0, 0.0, -1.0], |_, point| {
+                point[2] <= 0.5
+            })
+            .unwrap();
+        assert!((hit.distance - 10.0).abs() < 1e-5, "{hit:?}");
+    }
+
+    #[test]
+    fn empty_input() {
+        let bvh = Bvh::build(&[RenderablePart::default()], &[]);
+        assert_eq!(bvh.raycast([0.0; 3], [0.0, 0.0, -1.0], |_, _| true), None);
+    }
+}
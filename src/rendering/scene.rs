@@ -0,0 +1,409 @@
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::{
+    apptracing::{AppTracer, AppTracerTrait},
+    common::{
+        CACHE_SIZE, ClipPlane, HIGHLIGHT_COLOR, MAX_CLIP_PLANES, PartMesh, RenderablePart,
+        StepModel, add3, dot3, explode::translated, sub3,
+    },
+    rendering::wgpu_state::WgpuState,
+    trace_span,
+};
+use bytemuck::{Pod, Zeroable, cast_slice};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+// Per part slot of the dynamic uniform buffer, see WGSL_SHADER
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable, PartialEq)]
+struct PartUniform {
+    model_matrix: [f32; 16],
+    // rgb = part color, w = 1.0 when per-vertex (face style) colors should be used
+    color: [f32; 4],
+    // rgb = selection color, w = mix amount, zero for unselected parts
+    highlight: [f32; 4],
+}
+
+// Camera slot, see WGSL_SHADER
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CameraUniform {
+    view_projection: [f32; 16],
+    // xyz = eye, w = number of clip planes in use
+    eye: [f32; 4],
+    // xyz = normal, w = offset
+    clip_planes: [[f32; 4]; MAX_CLIP_PLANES],
+}
+
+// Buffers of one tessellated shell, drawn once per placed instance
+#[derive(PartialEq)]
+struct GpuMesh {
+    vertex_buffer: wgpu::Buffer,
+    color_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    vertex_count: usize,
+    index_count: u32,
+    has_vertex_colors: bool,
+}
+
+impl GpuMesh {
+    fn upload(device: &wgpu::Device, mesh: &PartMesh) -> Self {
+        trace_span!("GpuMesh::upload");
+        if mesh.indices.is_empty() {
+            AppTracer::warn("Part with empty indices will not be rendered");
+        }
+        let has_vertex_colors = has_vertex_colors(mesh);
+        // without face colors the shader takes the part color from its slot
+        let vertex_colors = if has_vertex_colors {
+            mesh.vertex_colors.clone()
+        } else {
+            vec![[1.0; 3]; mesh.vertices.len()]
+        };
+        Self {
+            vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Vertex Buffer"),
+                contents: cast_slice(&mesh.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+            color_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Vertex Color Buffer"),
+                contents: cast_slice(&vertex_colors),
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+            index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Index Buffer"),
+                contents: cast_slice(&mesh.indices),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+            vertex_count: mesh.vertices.len(),
+            index_count: mesh.indices.len() as u32,
+            has_vertex_colors,
+        }
+    }
+
+    // geometry is immutable for a given model id, the counts only guard
+    // against a model being replaced under the same id
+    fn holds(&self, mesh: &PartMesh) -> bool {
+        self.vertex_count == mesh.vertices.len()
+            && self.index_count as usize == mesh.indices.len()
+            && self.has_vertex_colors == has_vertex_colors(mesh)
+    }
+}
+
+fn has_vertex_colors(mesh: &PartMesh) -> bool {
+    !mesh.vertex_colors.is_empty() && mesh.vertex_colors.len() == mesh.vertices.len()
+}
+
+#[derive(PartialEq)]
+struct GpuPart {
+    mesh: Rc<GpuMesh>,
+    // placement the world bounds below were computed with
+    model_matrix: [f32; 16],
+    bounds: Option<([f32; 3], [f32; 3])>,
+    // exploded view move on top of `model_matrix`
+    offset: [f32; 3],
+    visible: bool,
+    // last uniform written to the part slot, None when the slot is stale
+    uniform: Option<PartUniform>,
+}
+
+impl GpuPart {
+    fn new(mesh: Rc<GpuMesh>, part: &RenderablePart) -> Self {
+        Self {
+            mesh,
+            model_matrix: part.model_matrix,
+            bounds: part.world_bounds(),
+            offset: [0.0; 3],
+            visible: part.visible,
+            uniform: None,
+        }
+    }
+}
+
+// Geometry of one model kept on the GPU. Per frame only the camera uniform
+// and the slots of parts whose placement or color changed are written.
+#[derive(PartialEq)]
+pub struct GpuScene {
+    model_id: String,
+    parts: Vec<GpuPart>,
+    // uploaded meshes by the mesh they came from, which is kept alive so its
+    // address can't be reused
+    meshes: HashMap<*const PartMesh, (Arc<PartMesh>, Rc<GpuMesh>)>,
+    camera_buffer: wgpu::Buffer,
+    part_buffer: wgpu::Buffer,
+    part_stride: u64,
+    part_capacity: usize,
+    bind_group: wgpu::BindGroup,
+    center: [f32; 3],
+    radius: f32,
+}
+
+impl GpuScene {
+    fn new(state: &WgpuState, model_id: &str) -> Self {
+        trace_span!("GpuScene::new");
+        let alignment = state.device.limits().min_uniform_buffer_offset_alignment as u64;
+        let part_stride =
+            (std::mem::size_of::<PartUniform>() as u64).div_ceil(alignment) * alignment;
+        let camera_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Camera Uniform Buffer"),
+            size: std::mem::size_of::<CameraUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (part_buffer, bind_group) = create_part_slots(state, &camera_buffer, part_stride, 1);
+        Self {
+            model_id: model_id.to_string(),
+            parts: Vec::new(),
+            meshes: HashMap::new(),
+            camera_buffer,
+            part_buffer,
+            part_stride,
+            part_capacity: 1,
+            bind_group,
+            center: [0.0; 3],
+            radius: 1.0,
+        }
+    }
+
+    // world point drawn at the origin, parts are recentered for precision
+    pub fn center(&self) -> [f32; 3] {
+        self.center
+    }
+
+    // radius of the bounding sphere around `center`
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    // Uploads parts that are new since the last call (parts stream in while
+    // a file is tessellated) and refreshes uniforms of changed parts.
+    // `selected` holds indices into `parts`, `offsets` the exploded view
+    // moves of the parts (missing ones don't move).
+    fn sync(
+        &mut self,
+        state: &WgpuState,
+        parts: &[RenderablePart],
+        selected: &[usize],
+        offsets: &[[f32; 3]],
+    ) {
+        trace_span!("GpuScene::sync");
+        let mut bounds_changed = self.parts.len() != parts.len();
+        self.parts.truncate(parts.len());
+        for (index, part) in parts.iter().enumerate() {
+            match self.parts.get_mut(index) {
+                Some(gpu) if gpu.mesh.holds(&part.mesh) => {
+                    if gpu.model_matrix != part.model_matrix {
+                        gpu.model_matrix = part.model_matrix;
+                        gpu.bounds = part.world_bounds();
+                        bounds_changed = true;
+                    }
+                    gpu.visible = part.visible;
+                }
+                Some(_) => {
+                    let mesh = self.mesh_for(state, &part.mesh);
+                    self.parts[index] = GpuPart::new(mesh, part);
+                    bounds_changed = true;
+                }
+                None => {
+                    let mesh = self.mesh_for(state, &part.mesh);
+                    self.parts.push(GpuPart::new(mesh, part));
+                }
+            }
+        }
+        // drop meshes no part draws anymore
+        self.meshes
+            .retain(|_, (_, mesh)| Rc::strong_count(mesh) > 1);
+
+        if self.parts.len() > self.part_capacity {
+            self.part_capacity = self.parts.len().next_power_of_two();
+            (self.part_buffer, self.bind_group) = create_part_slots(
+                state,
+                &self.camera_buffer,
+                self.part_stride,
+                self.part_capacity,
+            );
+            for gpu in &mut self.parts {
+                gpu.uniform = None;
+            }
+        }
+        for (index, gpu) in self.parts.iter_mut().enumerate() {
+            let offset = offsets.get(index).copied().unwrap_or_default();
+            if gpu.offset != offset {
+                gpu.offset = offset;
+                bounds_changed = true;
+            }
+        }
+        if bounds_changed {
+            self.update_bounds();
+        }
+
+        for (index, (gpu, part)) in self.parts.iter_mut().zip(parts).enumerate() {
+            let model_matrix = translated(&part.model_matrix, sub3(gpu.offset, self.center));
+            let uniform = PartUniform {
+                model_matrix,
+                color: [
+                    part.color[0],
+                    part.color[1],
+                    part.color[2],
+                    if gpu.mesh.has_vertex_colors { 1.0 } else { 0.0 },
+                ],
+                highlight: if selected.contains(&index) {
+                    HIGHLIGHT_COLOR
+                } else {
+                    [0.0; 4]
+                },
+            };
+            if gpu.uniform != Some(uniform) {
+                state.queue.write_buffer(
+                    &self.part_buffer,
+                    index as u64 * self.part_stride,
+                    bytemuck::bytes_of(&uniform),
+                );
+                gpu.uniform = Some(uniform);
+            }
+        }
+    }
+
+    // instances of a shell share their mesh, it is uploaded once
+    fn mesh_for(&mut self, state: &WgpuState, mesh: &Arc<PartMesh>) -> Rc<GpuMesh> {
+        let (_, gpu) = self.meshes.entry(Arc::as_ptr(mesh)).or_insert_with(|| {
+            (
+                Arc::clone(mesh),
+                Rc::new(GpuMesh::upload(&state.device, mesh)),
+            )
+        });
+        Rc::clone(gpu)
+    }
+
+    fn update_bounds(&mut self) {
+        trace_span!("GpuScene::update_bounds");
+        let (min, max) = self
+            .parts
+            .iter()
+            .filter_map(|gpu| {
+                let (min, max) = gpu.bounds?;
+                Some((add3(min, gpu.offset), add3(max, gpu.offset)))
+            })
+            .reduce(|(min_a, max_a), (min_b, max_b)| {
+                (
+                    std::array::from_fn(|axis| min_a[axis].min(min_b[axis])),
+                    std::array::from_fn(|axis| max_a[axis].max(max_b[axis])),
+                )
+            })
+            .unwrap_or(([-1.0; 3], [1.0; 3]));
+        self.center = std::array::from_fn(|axis| (min[axis] + max[axis]) * 0.5);
+        let size: [f32; 3] = std::array::from_fn(|axis| (max[axis] - min[axis]).max(0.1));
+        // bounding sphere around the (recentered) bounds
+        self.radius = 0.5 * (size[0] * size[0] + size[1] * size[1] + size[2] * size[2]).sqrt();
+    }
+
+    // `eye` is in scene (recentered) space, `clip_planes` in world space, the
+    // ones past MAX_CLIP_PLANES are ignored
+    pub fn set_camera(
+        &self,
+        queue: &wgpu::Queue,
+        view_projection: &[f32; 16],
+        eye: [f32; 3],
+        clip_planes: &[ClipPlane],
+    ) {
+        let mut uniform = CameraUniform {
+            view_projection: *view_projection,
+            eye: [eye[0], eye[1], eye[2], 0.0],
+            clip_planes: [[0.0; 4]; MAX_CLIP_PLANES],
+        };
+        for (slot, plane) in uniform.clip_planes.iter_mut().zip(clip_planes) {
+            let normal = plane.normal;
+            *slot = [
+                normal[0],
+                normal[1],
+                normal[2],
+                plane.offset - dot3(normal, self.center),
+            ];
+        }
+        uniform.eye[3] = clip_planes.len().min(MAX_CLIP_PLANES) as f32;
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        trace_span!("GpuScene::draw");
+        for (index, gpu) in self.parts.iter().enumerate() {
+            let mesh = &gpu.mesh;
+            if !gpu.visible || mesh.index_count == 0 {
+                continue;
+            }
+            let offset = (index as u64 * self.part_stride) as u32;
+            render_pass.set_bind_group(0, &self.bind_group, &[offset]);
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, mesh.color_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+        }
+    }
+}
+
+fn create_part_slots(
+    state: &WgpuState,
+    camera_buffer: &wgpu::Buffer,
+    part_stride: u64,
+    capacity: usize,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    trace_span!("create_part_slots");
+    let part_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Part Uniform Buffer"),
+        size: part_stride * capacity as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &state.bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &part_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<PartUniform>() as u64),
+                }),
+            },
+        ],
+        label: Some("bind_group"),
+    });
+    (part_buffer, bind_group)
+}
+
+// Scenes of the most recently drawn models, most recent first. Switching
+// back to one of them doesn't upload anything.
+#[derive(Default, PartialEq)]
+pub struct SceneCache {
+    scenes: VecDeque<GpuScene>,
+}
+
+impl SceneCache {
+    pub fn scene_for(
+        &mut self,
+        state: &WgpuState,
+        model: &StepModel,
+        selected: &[usize],
+        offsets: &[[f32; 3]],
+    ) -> &GpuScene {
+        trace_span!("SceneCache::scene_for");
+        let scene = self
+            .scenes
+            .iter()
+            .position(|scene| scene.model_id == model.id)
+            .and_then(|pos| self.scenes.remove(pos))
+            .unwrap_or_else(|| {
+                self.scenes.truncate(CACHE_SIZE.saturating_sub(1));
+                GpuScene::new(state, &model.id)
+            });
+        self.scenes.push_front(scene);
+        let scene = &mut self.scenes[0];
+        scene.sync(state, &model.render_parts, selected, offsets);
+        scene
+    }
+}
@@ -1,20 +1,118 @@
-use crate::{apptracing::AppTracer, apptracing::AppTracerTrait, trace_span};
+use crate::{
+    apptracing::AppTracer, apptracing::AppTracerTrait, common::StepVizError,
+    rendering::scene::SceneCache, trace_span,
+};
+use std::cell::RefCell;
 use web_sys::HtmlCanvasElement;
 use wgpu::{self, SurfaceTarget};
 
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 #[derive(PartialEq)]
 pub struct WgpuState {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface: wgpu::Surface<'static>,
-    pub config: wgpu::SurfaceConfiguration,
+    pub config: RefCell<wgpu::SurfaceConfiguration>,
     pub render_pipeline: wgpu::RenderPipeline,
     pub bind_group_layout: wgpu::BindGroupLayout,
+    pub sample_count: u32,
+    pub attachments: RefCell<FrameAttachments>,
+    pub scenes: RefCell<SceneCache>,
+}
+
+// Size dependent render targets, rebuilt whenever the surface is resized
+#[derive(PartialEq)]
+pub struct FrameAttachments {
+    pub depth: wgpu::TextureView,
+    // multisampled color target resolved into the surface texture, None without MSAA
+    pub msaa: Option<wgpu::TextureView>,
+}
+
+impl FrameAttachments {
+    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32) -> Self {
+        trace_span!("FrameAttachments::new");
+        let create_view = |label: &str, format: wgpu::TextureFormat| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: config.width,
+                        height: config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        Self {
+            depth: create_view("Depth Texture", DEPTH_FORMAT),
+            msaa: (sample_count > 1).then(|| create_view("MSAA Color Texture", config.format)),
+        }
+    }
+}
+
+impl WgpuState {
+    // Matches the drawing buffer to the size the canvas is displayed at, in
+    // device pixels, and reconfigures the surface and the depth/MSAA targets.
+    // Returns whether the size changed.
+    pub fn fit_canvas(&self, canvas: &HtmlCanvasElement) -> bool {
+        let max = self.device.limits().max_texture_dimension_2d;
+        let (width, height) = canvas_pixel_size(canvas);
+        let (width, height) = (width.min(max), height.min(max));
+        if canvas.width() != width || canvas.height() != height {
+            canvas.set_width(width);
+            canvas.set_height(height);
+        }
+        let mut config = self.config.borrow_mut();
+        if config.width == width && config.height == height {
+            return false;
+        }
+        trace_span!("WgpuState::fit_canvas");
+        config.width = width;
+        config.height = height;
+        self.surface.configure(&self.device, &config);
+        *self.attachments.borrow_mut() =
+            FrameAttachments::new(&self.device, &config, self.sample_count);
+        true
+    }
+}
+
+// CSS size of the canvas scaled by devicePixelRatio, so HiDPI screens get a
+// full resolution drawing buffer
+pub fn canvas_pixel_size(canvas: &HtmlCanvasElement) -> (u32, u32) {
+    let ratio = web_sys::window()
+        .map(|window| window.device_pixel_ratio())
+        .filter(|ratio| *ratio > 0.0)
+        .unwrap_or(1.0);
+    let scale = |css: i32| (css as f64 * ratio).round().max(1.0) as u32;
+    (scale(canvas.client_width()), scale(canvas.client_height()))
 }
 
 use crate::common::constants::WGSL_SHADER;
 
-pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn std::error::Error>> {
+#[cfg(target_arch = "wasm32")]
+fn canvas_target(canvas: HtmlCanvasElement) -> Result<SurfaceTarget<'static>, String> {
+    Ok(SurfaceTarget::Canvas(canvas))
+}
+
+// canvas surfaces only exist on the web, native builds (tests, tools) can't render
+#[cfg(not(target_arch = "wasm32"))]
+fn canvas_target(_canvas: HtmlCanvasElement) -> Result<SurfaceTarget<'static>, String> {
+    Err("WebGPU canvas surfaces are only available on wasm32".to_string())
+}
+
+// `msaa_samples` falls back to 1 when the surface format can't be multisampled
+// with that count
+pub async fn init_wgpu(
+    canvas: HtmlCanvasElement,
+    msaa_samples: u32,
+) -> Result<WgpuState, StepVizError> {
     trace_span!("init_wgpu");
 
     let instance_descriptor = wgpu::InstanceDescriptor {
@@ -24,13 +122,13 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
 
     let instance = wgpu::Instance::new(&instance_descriptor);
 
-    let target = SurfaceTarget::Canvas(canvas.clone());
+    let target = canvas_target(canvas.clone()).map_err(StepVizError::gpu)?;
     let surface = match instance.create_surface(target) {
         Ok(surface) => surface,
         Err(err) => {
             let msg = format!("Failed to create WebGPU surface: {}", err);
             AppTracer::error(&msg);
-            return Err(msg.into());
+            return Err(StepVizError::gpu(msg));
         }
     };
 
@@ -48,7 +146,7 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
         Err(err) => {
             let msg = format!("Failed to request WebGPU adapter: {}", err);
             AppTracer::error(&msg);
-            return Err(msg.into());
+            return Err(StepVizError::gpu(msg));
         }
     };
     //FIXME: we should request an adapter with the surface
@@ -67,12 +165,16 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
         Err(err) => {
             let msg = format!("Failed to request adapter device: {}", err);
             AppTracer::error(&msg);
-            return Err(msg.into());
+            return Err(StepVizError::gpu(msg));
         }
     };
 
-    let canvas_width = canvas.client_width().max(1) as u32;
-    let canvas_height = canvas.client_height().max(1) as u32;
+    let max_dimension = device.limits().max_texture_dimension_2d;
+    let (canvas_width, canvas_height) = canvas_pixel_size(&canvas);
+    let (canvas_width, canvas_height) = (
+        canvas_width.min(max_dimension),
+        canvas_height.min(max_dimension),
+    );
     canvas.set_width(canvas_width);
     canvas.set_height(canvas_height);
 
@@ -89,6 +191,21 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
     };
     surface.configure(&device, &config);
 
+    let sample_count = if adapter
+        .get_texture_format_features(config.format)
+        .flags
+        .sample_count_supported(msaa_samples)
+    {
+        msaa_samples
+    } else {
+        AppTracer::warn(&format!(
+            "{}x MSAA is not supported for {:?}, rendering without it",
+            msaa_samples, config.format
+        ));
+        1
+    };
+    let attachments = FrameAttachments::new(&device, &config, sample_count);
+
     let shader_module_descriptor = wgpu::ShaderModuleDescriptor {
         label: Some("shader"),
         source: wgpu::ShaderSource::Wgsl(WGSL_SHADER.into()),
@@ -96,34 +213,25 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
     let shader = device.create_shader_module(shader_module_descriptor);
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         entries: &[
+            // camera and clip planes (see scene.rs)
             wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
-                    min_binding_size: std::num::NonZeroU64::new(64),
+                    min_binding_size: std::num::NonZeroU64::new(144),
                 },
                 count: None,
             },
+            // per part model matrix and color, one slot per part (see scene.rs)
             wgpu::BindGroupLayoutEntry {
                 binding: 1,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: std::num::NonZeroU64::new(64),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::FRAGMENT,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    //FIXME: this should be 64 but it crashes
-                    min_binding_size: std::num::NonZeroU64::new(12),
+                    has_dynamic_offset: true,
+                    min_binding_size: std::num::NonZeroU64::new(96),
                 },
                 count: None,
             },
@@ -145,12 +253,20 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
             compilation_options: wgpu::PipelineCompilationOptions::default(),
             module: &shader,
             entry_point: Some("vs_main"),
-            buffers: &[wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<crate::common::GpuVertex>()
-                    as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
-            }],
+            buffers: &[
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<crate::common::GpuVertex>()
+                        as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                },
+                // per-vertex colors (face styles)
+                wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![2 => Float32x3],
+                },
+            ],
         },
         fragment: Some(wgpu::FragmentState {
             compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -163,8 +279,17 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
             })],
         }),
         primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
         multiview: None,
     });
 
@@ -172,8 +297,11 @@ pub async fn init_wgpu(canvas: HtmlCanvasElement) -> Result<WgpuState, Box<dyn s
         device,
         queue,
         surface,
-        config,
+        config: RefCell::new(config),
         render_pipeline,
         bind_group_layout,
+        sample_count,
+        attachments: RefCell::new(attachments),
+        scenes: RefCell::default(),
     })
 }
@@ -1,5 +1,14 @@
+use crate::common::mesh_check::MeshCheck;
 use crate::trace_span;
-use crate::{common::Metadata, components::details_panel::DetailsPanel};
+use crate::{
+    common::{ClipPlane, Diagnostic, Metadata, StepModel},
+    components::{
+        details_panel::DetailsPanel, diagnostics_panel::DiagnosticsPanel,
+        export_panel::ExportPanel, selection_panel::SelectionPanel,
+        validation_panel::ValidationPanel,
+    },
+};
+use std::rc::Rc;
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -7,6 +16,14 @@ pub struct RightPanelProps {
     pub metadata: Option<Metadata>,
     pub on_calculate_volume: Callback<()>,
     pub on_calculate_surface: Callback<()>,
+    pub model: Option<Rc<StepModel>>,
+    pub selected_parts: Vec<usize>,
+    #[prop_or_default]
+    pub clip_planes: Vec<ClipPlane>,
+    #[prop_or_default]
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
+    #[prop_or_default]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 #[function_component(RightPanel)]
@@ -14,11 +31,22 @@ pub fn right_panel(props: &RightPanelProps) -> Html {
     trace_span!("right_panel");
     html! {
         <div class="right-panel">
+            <SelectionPanel
+                model={props.model.clone()}
+                selected_parts={props.selected_parts.clone()}
+                mesh_checks={props.mesh_checks.clone()}
+            />
             <DetailsPanel
                 metadata={props.metadata.clone()}
                 on_calculate_volume={props.on_calculate_volume.clone()}
                 on_calculate_surface={props.on_calculate_surface.clone()}
+                model={props.model.clone()}
+                clip_planes={props.clip_planes.clone()}
+                mesh_checks={props.mesh_checks.clone()}
             />
+            <DiagnosticsPanel diagnostics={props.diagnostics.clone()} />
+            <ValidationPanel model={props.model.clone()} />
+            <ExportPanel model={props.model.clone()} />
         </div>
     }
 }
@@ -0,0 +1,169 @@
+use crate::common::{
+    Metadata, PipelineProgress, ProductNode, RenderablePart, StepVizError, TessellationJob,
+    parse_step,
+};
+use crate::trace_span;
+use gloo::timers::callback::Timeout;
+use gloo::worker::{HandlerId, Worker, WorkerScope};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub enum WorkerInput {
+    // starts a new job, dropping whatever job is still running
+    Process {
+        job: u32,
+        file_name: String,
+        bytes: Vec<u8>,
+    },
+    Cancel {
+        job: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum WorkerOutput {
+    Progress {
+        job: u32,
+        progress: PipelineProgress,
+    },
+    Parsed {
+        job: u32,
+        id: String,
+        metadata: Box<Metadata>,
+        product_tree: Vec<ProductNode>,
+    },
+    // parts of one tessellated shell, its instances share one mesh
+    Parts {
+        job: u32,
+        #[serde(with = "crate::common::render::shared_meshes")]
+        parts: Vec<RenderablePart>,
+    },
+    // a shell that was skipped, the load goes on
+    Warning {
+        job: u32,
+        warning: StepVizError,
+    },
+    Finished {
+        job: u32,
+    },
+    Failed {
+        job: u32,
+        error: StepVizError,
+    },
+}
+
+impl WorkerOutput {
+    pub fn job(&self) -> u32 {
+        match self {
+            WorkerOutput::Progress { job, .. }
+            | WorkerOutput::Parsed { job, .. }
+            | WorkerOutput::Parts { job, .. }
+            | WorkerOutput::Warning { job, .. }
+            | WorkerOutput::Finished { job }
+            | WorkerOutput::Failed { job, .. } => *job,
+        }
+    }
+}
+
+pub enum WorkerMessage {
+    Step(u32),
+}
+
+struct ActiveJob {
+    job: u32,
+    handler: HandlerId,
+    tessellation: TessellationJob,
+}
+
+// Parses and tessellates STEP files off the UI thread, one shell per event
+// loop turn so cancel requests are picked up between shells.
+pub struct StepWorker {
+    active: Option<ActiveJob>,
+}
+
+impl Worker for StepWorker {
+    type Message = WorkerMessage;
+    type Input = WorkerInput;
+    type Output = WorkerOutput;
+
+    fn create(_scope: &WorkerScope<Self>) -> Self {
+        Self { active: None }
+    }
+
+    fn update(&mut self, scope: &WorkerScope<Self>, msg: Self::Message) {
+        let WorkerMessage::Step(job) = msg;
+        // steps of cancelled or replaced jobs are dropped here
+        let Some(active) = self.active.as_mut().filter(|active| active.job == job) else {
+            return;
+        };
+        if let Some(shell) = active.tessellation.next() {
+            match shell {
+                Ok(parts) if parts.is_empty() => {}
+                Ok(parts) => scope.respond(active.handler, WorkerOutput::Parts { job, parts }),
+                Err(warning) => {
+                    scope.respond(active.handler, WorkerOutput::Warning { job, warning })
+                }
+            }
+            let progress = active.tessellation.progress();
+            scope.respond(active.handler, WorkerOutput::Progress { job, progress });
+        }
+        if active.tessellation.is_finished() {
+            scope.respond(active.handler, WorkerOutput::Finished { job });
+            self.active = None;
+        } else {
+            schedule_step(scope, job);
+        }
+    }
+
+    fn received(&mut self, scope: &WorkerScope<Self>, msg: Self::Input, id: HandlerId) {
+        trace_span!("StepWorker::received");
+        match msg {
+            WorkerInput::Process {
+                job,
+                file_name,
+                bytes,
+            } => {
+                self.active = None;
+                let progress = PipelineProgress::Parsing;
+                scope.respond(id, WorkerOutput::Progress { job, progress });
+                let text = String::from_utf8_lossy(&bytes);
+                let parsed = match parse_step(&text, &file_name) {
+                    Ok(parsed) => parsed,
+                    Err(error) => {
+                        scope.respond(id, WorkerOutput::Failed { job, error });
+                        return;
+                    }
+                };
+                scope.respond(
+                    id,
+                    WorkerOutput::Parsed {
+                        job,
+                        id: parsed.id,
+                        metadata: Box::new(parsed.metadata),
+                        product_tree: parsed.prepared.product_tree,
+                    },
+                );
+                let tessellation = TessellationJob::new(parsed.prepared.sections);
+                let progress = tessellation.progress();
+                scope.respond(id, WorkerOutput::Progress { job, progress });
+                self.active = Some(ActiveJob {
+                    job,
+                    handler: id,
+                    tessellation,
+                });
+                schedule_step(scope, job);
+            }
+            WorkerInput::Cancel { job } => {
+                if self.active.as_ref().is_some_and(|active| active.job == job) {
+                    self.active = None;
+                }
+            }
+        }
+    }
+}
+
+// a zero timeout (not a microtask) lets queued input messages run first
+fn schedule_step(scope: &WorkerScope<StepWorker>, job: u32) {
+    let scope = scope.clone();
+    Timeout::new(0, move || scope.send_message(WorkerMessage::Step(job))).forget();
+}
@@ -1,45 +1,147 @@
+use crate::common::mesh_check::MeshCheck;
 use crate::common::{
-    FileIndexItem, LruCache, Metadata, StepModel, compute_bounding_box, convert_header,
-    delete_model, hash_text_to_id, load_index, load_model, parse_units, save_index, save_model,
-    step_extract_wsgl_reqs,
+    BoundingBox, ClipPlane, Diagnostic, FileIndexItem, IdbStore, LruCache, Measurement, Metadata,
+    ModelStore, PipelineProgress, StepModel, StepVizError, StorageError, find_product_node,
+    migrate_local_storage,
 };
 use crate::trace_span;
+use crate::worker::{StepWorker, WorkerInput, WorkerOutput};
 use gloo::file::File;
 use gloo::file::callbacks::FileReader;
+use gloo::worker::{Spawnable, WorkerBridge};
+use std::cell::RefCell;
+use std::future::Future;
 use std::rc::Rc;
 use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
 use web_sys::{Event, HtmlInputElement};
 use yew::prelude::*;
 
-use crate::common::constants::{CACHE_SIZE, MAX_FILE_BYTES};
+use crate::common::constants::{CACHE_SIZE, MAX_FILE_BYTES, STEP_WORKER_PATH};
 
 pub struct WorkspaceActions {
     pub on_file_change: Callback<Event>,
+    pub on_cancel_processing: Callback<()>,
     pub on_item_click: Callback<String>,
     pub on_delete: Callback<String>,
     pub on_deselect: Callback<()>,
     pub on_clear_history: Callback<()>,
     pub on_visibility_change: Callback<(usize, bool)>,
+    pub on_node_visibility_change: Callback<(usize, bool)>,
     pub on_show_all: Callback<()>,
     pub on_hide_all: Callback<()>,
     pub on_calculate_volume: Callback<()>,
     pub on_calculate_surface: Callback<()>,
+    // (picked part, extend the selection), None clears unless extending
+    pub on_select_part: Callback<(Option<usize>, bool)>,
+    // replaces the measurements of the shown model, they are saved with it
+    pub on_measurements_change: Callback<Vec<Measurement>>,
+    pub on_clip_planes_change: Callback<Vec<ClipPlane>>,
+    // shows the error in the result line and lists it in the diagnostics
+    pub on_error: Callback<StepVizError>,
+}
+
+// Warnings and errors of the last load, later storage and GPU failures are
+// added as they happen
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub entries: Vec<Diagnostic>,
+}
+
+pub enum DiagnosticsAction {
+    // a load starts over with the warnings it already has
+    Reset(Vec<Diagnostic>),
+    Push(Diagnostic),
+}
+
+impl Reducible for Diagnostics {
+    type Action = DiagnosticsAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        match action {
+            DiagnosticsAction::Reset(entries) => Rc::new(Diagnostics { entries }),
+            // a failing render repeats every frame, it is listed once
+            DiagnosticsAction::Push(entry) if self.entries.contains(&entry) => self,
+            DiagnosticsAction::Push(entry) => {
+                let mut entries = self.entries.clone();
+                entries.push(entry);
+                Rc::new(Diagnostics { entries })
+            }
+        }
+    }
 }
 
 pub struct StepWorkspace {
     pub result: UseStateHandle<Option<String>>,
+    pub diagnostics: UseReducerHandle<Diagnostics>,
     pub metadata: UseStateHandle<Option<Metadata>>,
     pub files_index: UseStateHandle<Vec<FileIndexItem>>,
     pub selected_file: UseStateHandle<Option<String>>,
     pub step_model: UseStateHandle<Option<Rc<StepModel>>>,
     pub is_processing: UseStateHandle<bool>,
+    pub progress: UseStateHandle<Option<PipelineProgress>>,
+    // indices into the render parts of `step_model`
+    pub selected_parts: UseStateHandle<Vec<usize>>,
+    // section planes of the view, in world space
+    pub clip_planes: UseStateHandle<Vec<ClipPlane>>,
+    // welded topology check of every render part of `step_model`
+    pub mesh_checks: Rc<Vec<MeshCheck>>,
     pub actions: WorkspaceActions,
 }
 
+type BrowserStore = ModelStore<IdbStore>;
+
+// runs a storage write in the background, failures go to `on_error`
+fn persist(
+    on_error: &Callback<StepVizError>,
+    task: impl Future<Output = Result<(), StorageError>> + 'static,
+) {
+    let on_error = on_error.clone();
+    spawn_local(async move {
+        if let Err(err) = task.await {
+            on_error.emit(err.into());
+        }
+    });
+}
+
+fn spawn_step_worker(output: &Rc<RefCell<Callback<WorkerOutput>>>) -> WorkerBridge<StepWorker> {
+    let output = output.clone();
+    StepWorker::spawner()
+        .callback(move |msg| output.borrow().emit(msg))
+        .spawn(&step_worker_url())
+}
+
+// the app may be served below a path (trunk --public-url), gloo would resolve
+// a relative path against the page url instead of the document base
+fn step_worker_url() -> String {
+    gloo::utils::document()
+        .base_uri()
+        .ok()
+        .flatten()
+        .and_then(|base| web_sys::Url::new_with_base(STEP_WORKER_PATH, &base).ok())
+        .map(|url| url.href())
+        .unwrap_or_else(|| STEP_WORKER_PATH.to_string())
+}
+
+// Drops the outputs of the running job and tells the worker to stop it,
+// returns the id for the next job.
+fn cancel_job(
+    worker: &RefCell<Option<WorkerBridge<StepWorker>>>,
+    current_job: &RefCell<u32>,
+) -> u32 {
+    let mut job = current_job.borrow_mut();
+    if let Some(bridge) = worker.borrow().as_ref() {
+        bridge.send(WorkerInput::Cancel { job: *job });
+    }
+    *job += 1;
+    *job
+}
+
 #[hook]
 pub fn use_step_workspace() -> StepWorkspace {
     trace_span!("use_step_workspace");
     let result = use_state(|| None::<String>);
+    let diagnostics = use_reducer(Diagnostics::default);
     let metadata = use_state(|| None::<Metadata>);
     let file_reader = use_state(|| None::<FileReader>);
     let files_index = use_state(|| Vec::<FileIndexItem>::new());
@@ -47,25 +149,200 @@ pub fn use_step_workspace() -> StepWorkspace {
     let step_model = use_state(|| None::<Rc<StepModel>>);
     let selected_file = use_state(|| None::<String>);
     let is_processing = use_state(|| false);
+    let progress = use_state(|| None::<PipelineProgress>);
+    let selected_parts = use_state(Vec::<usize>::new);
+    let clip_planes = use_state(Vec::<ClipPlane>::new);
+    let worker = use_mut_ref(|| None::<WorkerBridge<StepWorker>>);
+    let worker_output = use_mut_ref(Callback::<WorkerOutput>::noop);
+    // id of the job whose outputs are still wanted
+    let current_job = use_mut_ref(|| 0u32);
+    let pending_model = use_mut_ref(|| None::<StepModel>);
+    let store = use_memo((), |_| BrowserStore::new(IdbStore::default()));
+    // geometry only changes with the model, not with visibility
+    let mesh_checks = {
+        let model = (*step_model).clone();
+        let key = model
+            .as_ref()
+            .map(|model| (model.id.clone(), model.render_parts.len()));
+        use_memo(key, move |_| {
+            model
+                .map(|model| model.render_parts.iter().map(MeshCheck::of_part).collect())
+                .unwrap_or_default()
+        })
+    };
+
+    let on_error = {
+        let result = result.clone();
+        let diagnostics = diagnostics.dispatcher();
+        Callback::from(move |error: StepVizError| {
+            result.set(Some(error.to_string()));
+            diagnostics.dispatch(DiagnosticsAction::Push(Diagnostic::error(error)));
+        })
+    };
 
     {
         let files_index_handle = files_index.clone();
+        let on_error = on_error.clone();
+        let store = store.clone();
         use_effect_with((), move |_| {
-            let idx = load_index();
-            files_index_handle.set(idx);
+            spawn_local(async move {
+                if let Err(err) = migrate_local_storage(&store).await {
+                    on_error.emit(err.into());
+                }
+                match store.load_index().await {
+                    Ok(idx) => files_index_handle.set(idx),
+                    Err(err) => on_error.emit(err.into()),
+                }
+            });
             || ()
         });
     }
 
+    // part indices only make sense within the model they were picked in,
+    // plane offsets within its bounds
+    {
+        let selected_parts = selected_parts.clone();
+        let clip_planes = clip_planes.clone();
+        let model_id = step_model.as_ref().map(|model| model.id.clone());
+        use_effect_with(model_id, move |_| {
+            selected_parts.set(Vec::new());
+            clip_planes.set(Vec::new());
+            || ()
+        });
+    }
+
+    // outputs are routed through a ref so the worker callback, created once,
+    // always sees the state handles of the latest render
+    let on_worker_output = {
+        let result_state = result.clone();
+        let diagnostics = diagnostics.dispatcher();
+        let on_error = on_error.clone();
+        let metadata_state = metadata.clone();
+        let list_state = files_index.clone();
+        let cache_state = cache.clone();
+        let step_model_state = step_model.clone();
+        let selected_file_state = selected_file.clone();
+        let processing_state = is_processing.clone();
+        let progress_state = progress.clone();
+        let current_job = current_job.clone();
+        let pending_model = pending_model.clone();
+        let store = store.clone();
+        Callback::from(move |output: WorkerOutput| {
+            if output.job() != *current_job.borrow() {
+                // late output of a cancelled job
+                return;
+            }
+            match output {
+                WorkerOutput::Progress { progress, .. } => {
+                    progress_state.set(Some(progress));
+                }
+                WorkerOutput::Parsed {
+                    id,
+                    metadata,
+                    product_tree,
+                    ..
+                } => {
+                    for warning in &metadata.warnings {
+                        let warning = Diagnostic::warning(warning.clone());
+                        diagnostics.dispatch(DiagnosticsAction::Push(warning));
+                    }
+                    metadata_state.set(Some((*metadata).clone()));
+                    selected_file_state.set(Some(id.clone()));
+                    result_state.set(Some("Tessellating geometry for 3D view...".to_string()));
+                    *pending_model.borrow_mut() = Some(StepModel {
+                        id,
+                        metadata: *metadata,
+                        render_parts: Vec::new(),
+                        product_tree,
+                        measurements: Vec::new(),
+                    });
+                }
+                WorkerOutput::Parts { parts, .. } => {
+                    if let Some(model) = pending_model.borrow_mut().as_mut() {
+                        model.render_parts.extend(parts);
+                    }
+                }
+                WorkerOutput::Warning { warning, .. } => {
+                    if let Some(model) = pending_model.borrow_mut().as_mut() {
+                        model.metadata.warnings.push(warning.clone());
+                    }
+                    diagnostics.dispatch(DiagnosticsAction::Push(Diagnostic::warning(warning)));
+                }
+                WorkerOutput::Finished { .. } => {
+                    let Some(mut model) = pending_model.borrow_mut().take() else {
+                        return;
+                    };
+                    model.metadata.vertex_count = model
+                        .render_parts
+                        .iter()
+                        .map(|p| p.mesh.vertices.len())
+                        .sum();
+                    model.metadata.triangle_count = model
+                        .render_parts
+                        .iter()
+                        .map(|p| p.mesh.indices.len() / 3)
+                        .sum();
+                    model.metadata.bounding_box = BoundingBox::of_parts(&model.render_parts);
+
+                    {
+                        let mut cache_ref = cache_state.borrow_mut();
+                        cache_ref.insert(model.id.clone(), model.clone());
+                    }
+
+                    let mut list = (*list_state).clone();
+                    list.retain(|i| i.id != model.id);
+                    list.insert(
+                        0,
+                        FileIndexItem {
+                            id: model.id.clone(),
+                            name: model.metadata.header.file_name.clone(),
+                            entity_count: model.metadata.entity_count,
+                            time_stamp: model.metadata.header.time_stamp.clone(),
+                        },
+                    );
+                    list_state.set(list.clone());
+                    {
+                        let store = store.clone();
+                        let model = model.clone();
+                        persist(&on_error, async move {
+                            store.save_model(&model).await?;
+                            store.save_index(&list).await
+                        });
+                    }
+
+                    let model_warnings = model.metadata.warnings.len();
+                    metadata_state.set(Some(model.metadata.clone()));
+                    step_model_state.set(Some(Rc::new(model)));
+                    result_state.set(Some(match model_warnings {
+                        0 => "Parsed STEP file successfully.".to_string(),
+                        n => format!("Parsed STEP file with {n} warning(s), see Diagnostics."),
+                    }));
+                    progress_state.set(None);
+                    processing_state.set(false);
+                }
+                WorkerOutput::Failed { error, .. } => {
+                    pending_model.borrow_mut().take();
+                    on_error.emit(error);
+                    metadata_state.set(None);
+                    progress_state.set(None);
+                    processing_state.set(false);
+                }
+            }
+        })
+    };
+    *worker_output.borrow_mut() = on_worker_output;
+
     let on_file_change = {
-        let result_handle = result.clone();
+        let diagnostics = diagnostics.dispatcher();
+        let on_error = on_error.clone();
         let metadata_handle = metadata.clone();
         let file_reader_handle = file_reader.clone();
-        let files_index_handle = files_index.clone();
-        let cache_handle = cache.clone();
-        let step_model_handle = step_model.clone();
-        let selected_file_handle = selected_file.clone();
         let is_processing_handle = is_processing.clone();
+        let progress_handle = progress.clone();
+        let worker = worker.clone();
+        let worker_output = worker_output.clone();
+        let current_job = current_job.clone();
+        let pending_model = pending_model.clone();
         Callback::from(move |event: Event| {
             trace_span!("on_file_change callback");
             let input: HtmlInputElement = event
@@ -74,149 +351,49 @@ pub fn use_step_workspace() -> StepWorkspace {
                 .expect("file input event");
             if let Some(files) = input.files() {
                 if let Some(web_file) = files.get(0) {
+                    // a new file supersedes whatever is still being processed
+                    let job = cancel_job(&worker, &current_job);
+                    pending_model.borrow_mut().take();
+                    diagnostics.dispatch(DiagnosticsAction::Reset(Vec::new()));
                     is_processing_handle.set(true);
                     if web_file.size() > MAX_FILE_BYTES {
-                        result_handle.set(Some(
-                            "File too large. Maximum allowed is 20 MB.".to_string(),
+                        on_error.emit(StepVizError::io(
+                            "File too large. Maximum allowed is 20 MB.",
                         ));
                         metadata_handle.set(None);
+                        progress_handle.set(None);
                         is_processing_handle.set(false);
                         return;
                     }
+                    progress_handle.set(Some(PipelineProgress::Reading));
                     let name = web_file.name();
                     let file = File::from(web_sys::File::from(web_file));
-                    let result_state = result_handle.clone();
+                    let on_error = on_error.clone();
                     let metadata_state = metadata_handle.clone();
-                    let list_state = files_index_handle.clone();
-                    let cache_state = cache_handle.clone();
-                    let step_model_state = step_model_handle.clone();
-                    let selected_file_state = selected_file_handle.clone();
                     let processing_state = is_processing_handle.clone();
-                    let reader = gloo::file::callbacks::read_as_text(&file, move |res| {
-                        match res {
-                            Ok(text) => match ruststep::parser::parse(&text) {
-                                Ok(parsed) => {
-                                    if parsed.data.is_empty() {
-                                        result_state.set(Some(
-                                            "No data sections found in the STEP file.".to_string(),
-                                        ));
-                                        processing_state.set(false);
-                                        return;
-                                    }
-                                    let section = match parsed.data.get(0) {
-                                        Some(section)
-                                            if !section.entities.is_empty()
-                                                || !section.meta.is_empty() =>
-                                        {
-                                            section
-                                        }
-                                        _ => {
-                                            result_state.set(Some(
-                                                "STEP file has no usable data sections (empty meta/entities).".to_string(),
-                                            ));
-                                            metadata_state.set(None);
-                                            processing_state.set(false);
-                                            return;
-                                        }
-                                    };
-                                    let step_table =
-                                        truck_stepio::r#in::Table::from_data_section(&section);
-                                    let entity_count: usize = parsed
-                                        .data
-                                        .iter()
-                                        .map(|section| section.entities.len())
-                                        .sum();
-                                    let mut step_header = convert_header(&parsed.header);
-                                    if step_header.file_name.is_empty() {
-                                        step_header.file_name = name.clone();
-                                    }
-                                    let id = hash_text_to_id(&text);
-                                    let bbox = compute_bounding_box(&step_table);
-                                    let units = parse_units(&parsed);
-                                    let meta = Metadata {
-                                        header: step_header.clone(),
-                                        entity_count,
-                                        bounding_box: bbox,
-                                        units,
-                                        vertex_count: 0,
-                                        triangle_count: 0,
-                                        volume: None,
-                                        surface_area: None,
-                                    };
-
-                                    metadata_state.set(Some(meta.clone()));
-                                    selected_file_state.set(Some(id.clone()));
-                                    result_state.set(Some(
-                                        "Tessellating geometry for 3D view...".to_string(),
-                                    ));
-
-                                    let metadata_future = metadata_state.clone();
-                                    let step_model_future = step_model_state.clone();
-                                    let cache_future = cache_state.clone();
-                                    let result_future = result_state.clone();
-                                    let model_meta = meta.clone();
-                                    let tess_id = id.clone();
-                                    wasm_bindgen_futures::spawn_local(async move {
-                                        let renderable_parts =
-                                            step_extract_wsgl_reqs(&tess_id, &step_table);
-                                        let vertex_count =
-                                            renderable_parts.iter().map(|p| p.vertices.len()).sum();
-                                        let triangle_count = renderable_parts
-                                            .iter()
-                                            .map(|p| p.indices.len() / 3)
-                                            .sum();
-
-                                        let mut updated_meta = model_meta;
-                                        updated_meta.vertex_count = vertex_count;
-                                        updated_meta.triangle_count = triangle_count;
-
-                                        let model = StepModel {
-                                            id: tess_id.clone(),
-                                            metadata: updated_meta.clone(),
-                                            render_parts: renderable_parts,
-                                        };
-
-                                        {
-                                            let mut cache_ref = cache_future.borrow_mut();
-                                            cache_ref.insert(tess_id.clone(), model.clone());
-                                        }
-                                        save_model(&model);
-
-                                        metadata_future.set(Some(updated_meta));
-                                        step_model_future.set(Some(Rc::new(model)));
-                                        result_future.set(Some(
-                                            "Parsed STEP file successfully.".to_string(),
-                                        ));
-                                        processing_state.set(false);
-                                    });
-
-                                    let mut list = (*list_state).clone();
-                                    list.retain(|i| i.id != id);
-                                    list.insert(
-                                        0,
-                                        FileIndexItem {
-                                            id: id.clone(),
-                                            name: step_header.file_name.clone(),
-                                            entity_count,
-                                            time_stamp: step_header.time_stamp.clone(),
-                                        },
-                                    );
-                                    list_state.set(list.clone());
-                                    save_index(&list);
-                                    return;
-                                }
-                                Err(e) => {
-                                    result_state.set(Some(format!("Failed to parse STEP: {e}")));
-                                    metadata_state.set(None);
-                                }
-                            },
+                    let progress_state = progress_handle.clone();
+                    let worker = worker.clone();
+                    let worker_output = worker_output.clone();
+                    let reader =
+                        gloo::file::callbacks::read_as_bytes(&file, move |res| match res {
+                            Ok(bytes) => {
+                                let mut worker = worker.borrow_mut();
+                                let bridge =
+                                    worker.get_or_insert_with(|| spawn_step_worker(&worker_output));
+                                bridge.send(WorkerInput::Process {
+                                    job,
+                                    file_name: name,
+                                    bytes,
+                                });
+                            }
                             Err(e) => {
-                                result_state.set(Some(format!("Failed to read file: {e}")));
+                                on_error
+                                    .emit(StepVizError::io(format!("Failed to read file: {e}")));
                                 metadata_state.set(None);
+                                progress_state.set(None);
+                                processing_state.set(false);
                             }
-                        }
-                        processing_state.set(false);
-                    });
+                        });
                     file_reader_handle.set(Some(reader));
                     return;
                 }
@@ -225,26 +402,61 @@ pub fn use_step_workspace() -> StepWorkspace {
         })
     };
 
+    let on_cancel_processing = {
+        let result_state = result.clone();
+        let file_reader_state = file_reader.clone();
+        let processing_state = is_processing.clone();
+        let progress_state = progress.clone();
+        let worker = worker.clone();
+        let current_job = current_job.clone();
+        let pending_model = pending_model.clone();
+        Callback::from(move |_| {
+            cancel_job(&worker, &current_job);
+            pending_model.borrow_mut().take();
+            // dropping the reader aborts a read still in flight
+            file_reader_state.set(None);
+            progress_state.set(None);
+            processing_state.set(false);
+            result_state.set(Some("Processing cancelled.".to_string()));
+        })
+    };
+
     let on_item_click = {
         let files_index_state = files_index.clone();
         let metadata_state = metadata.clone();
         let result_state = result.clone();
+        let diagnostics = diagnostics.dispatcher();
+        let on_error = on_error.clone();
         let cache_state = cache.clone();
         let step_model_state = step_model.clone();
         let selected_file_state = selected_file.clone();
+        let processing_state = is_processing.clone();
+        let on_cancel_processing = on_cancel_processing.clone();
+        let store = store.clone();
         Callback::from(move |id: String| {
-            let maybe_model = {
-                let mut c = cache_state.borrow_mut();
-                c.get(&id)
+            if *processing_state {
+                on_cancel_processing.emit(());
             }
-            .or_else(|| load_model(&id));
-
-            match maybe_model {
-                Some(model) => {
+            let show_model = {
+                let files_index_state = files_index_state.clone();
+                let metadata_state = metadata_state.clone();
+                let result_state = result_state.clone();
+                let diagnostics = diagnostics.clone();
+                let on_error = on_error.clone();
+                let cache_state = cache_state.clone();
+                let step_model_state = step_model_state.clone();
+                let selected_file_state = selected_file_state.clone();
+                let store = store.clone();
+                move |model: StepModel| {
+                    let id = model.id.clone();
                     {
                         let mut c = cache_state.borrow_mut();
                         c.insert(id.clone(), model.clone());
                     }
+                    let warnings = model.metadata.warnings.iter().cloned();
+                    diagnostics.dispatch(DiagnosticsAction::Reset(
+                        warnings.map(Diagnostic::warning).collect(),
+                    ));
                     metadata_state.set(Some(model.metadata.clone()));
                     step_model_state.set(Some(Rc::new(model)));
                     selected_file_state.set(Some(id.clone()));
@@ -254,11 +466,27 @@ pub fn use_step_workspace() -> StepWorkspace {
                         let item = list.remove(pos);
                         list.insert(0, item);
                         files_index_state.set(list.clone());
-                        save_index(&list);
+                        persist(&on_error, async move { store.save_index(&list).await });
                     }
                 }
+            };
+
+            let cached = {
+                let mut c = cache_state.borrow_mut();
+                c.get(&id)
+            };
+            match cached {
+                Some(model) => show_model(model),
                 None => {
-                    result_state.set(Some("Cached data missing.".to_string()));
+                    let on_error = on_error.clone();
+                    let store = store.clone();
+                    spawn_local(async move {
+                        match store.load_model(&id).await {
+                            Ok(Some(model)) => show_model(model),
+                            Ok(None) => on_error.emit(StepVizError::storage("cached data missing")),
+                            Err(err) => on_error.emit(err.into()),
+                        }
+                    });
                 }
             }
         })
@@ -267,10 +495,12 @@ pub fn use_step_workspace() -> StepWorkspace {
     let on_delete = {
         let files_index = files_index.clone();
         let result_state = result.clone();
+        let on_error = on_error.clone();
         let cache_handle = cache.clone();
         let selected_file_state = selected_file.clone();
         let metadata_state = metadata.clone();
         let step_model_state = step_model.clone();
+        let store = store.clone();
         Callback::from(move |delete_id: String| {
             if let Some(window) = web_sys::window() {
                 if let Ok(false) = window.confirm_with_message(
@@ -285,11 +515,17 @@ pub fn use_step_workspace() -> StepWorkspace {
                 c.remove(&delete_id);
             }
 
-            delete_model(&delete_id);
             let mut list = (*files_index).clone();
             list.retain(|i| i.id != delete_id);
             files_index.set(list.clone());
-            save_index(&list);
+            {
+                let store = store.clone();
+                let delete_id = delete_id.clone();
+                persist(&on_error, async move {
+                    store.delete_model(&delete_id).await?;
+                    store.save_index(&list).await
+                });
+            }
             if selected_file_state.as_ref() == Some(&delete_id) {
                 selected_file_state.set(None);
                 metadata_state.set(None);
@@ -313,10 +549,12 @@ pub fn use_step_workspace() -> StepWorkspace {
     let on_clear_history = {
         let files_index_state = files_index.clone();
         let result_state = result.clone();
+        let on_error = on_error.clone();
         let cache_handle = cache.clone();
         let metadata_state = metadata.clone();
         let step_model_state = step_model.clone();
         let selected_file_state = selected_file.clone();
+        let store = store.clone();
         Callback::from(move |_| {
             if let Some(window) = web_sys::window() {
                 if let Ok(false) = window.confirm_with_message(
@@ -327,9 +565,9 @@ pub fn use_step_workspace() -> StepWorkspace {
                 }
             }
 
-            let existing = (*files_index_state).clone();
-            for item in &existing {
-                delete_model(&item.id);
+            {
+                let store = store.clone();
+                persist(&on_error, async move { store.clear().await });
             }
 
             {
@@ -338,7 +576,6 @@ pub fn use_step_workspace() -> StepWorkspace {
             }
 
             files_index_state.set(Vec::new());
-            save_index(&[]);
             metadata_state.set(None);
             step_model_state.set(None);
             selected_file_state.set(None);
@@ -359,6 +596,26 @@ pub fn use_step_workspace() -> StepWorkspace {
         })
     };
 
+    let on_node_visibility_change = {
+        let step_model = step_model.clone();
+        Callback::from(move |(node_id, visible): (usize, bool)| {
+            if let Some(model) = step_model.as_ref() {
+                let Some(node) = find_product_node(&model.product_tree, node_id) else {
+                    return;
+                };
+                // hiding a subassembly hides every instance below it
+                let node_ids = node.subtree_ids();
+                let mut new_model = (**model).clone();
+                for part in &mut new_model.render_parts {
+                    if part.node_id.is_some_and(|id| node_ids.contains(&id)) {
+                        part.visible = visible;
+                    }
+                }
+                step_model.set(Some(Rc::new(new_model)));
+            }
+        })
+    };
+
     let on_show_all = {
         let step_model = step_model.clone();
         Callback::from(move |_| {
@@ -389,6 +646,8 @@ pub fn use_step_workspace() -> StepWorkspace {
         let step_model = step_model.clone();
         let metadata = metadata.clone();
         let cache = cache.clone();
+        let on_error = on_error.clone();
+        let store = store.clone();
         Callback::from(move |_| {
             if let Some(model) = step_model.as_ref() {
                 let mut total_volume = 0.0;
@@ -407,7 +666,11 @@ pub fn use_step_workspace() -> StepWorkspace {
                     let mut c = cache.borrow_mut();
                     c.insert(new_model.id.clone(), new_model.clone());
                 }
-                save_model(&new_model);
+                {
+                    let store = store.clone();
+                    let new_model = new_model.clone();
+                    persist(&on_error, async move { store.save_model(&new_model).await });
+                }
 
                 step_model.set(Some(Rc::new(new_model)));
             }
@@ -418,6 +681,8 @@ pub fn use_step_workspace() -> StepWorkspace {
         let step_model = step_model.clone();
         let metadata = metadata.clone();
         let cache = cache.clone();
+        let on_error = on_error.clone();
+        let store = store.clone();
         Callback::from(move |_| {
             if let Some(model) = step_model.as_ref() {
                 let mut total_area = 0.0;
@@ -436,31 +701,98 @@ pub fn use_step_workspace() -> StepWorkspace {
                     let mut c = cache.borrow_mut();
                     c.insert(new_model.id.clone(), new_model.clone());
                 }
-                save_model(&new_model);
+                {
+                    let store = store.clone();
+                    let new_model = new_model.clone();
+                    persist(&on_error, async move { store.save_model(&new_model).await });
+                }
+
+                step_model.set(Some(Rc::new(new_model)));
+            }
+        })
+    };
+
+    let on_measurements_change = {
+        let step_model = step_model.clone();
+        let cache = cache.clone();
+        let on_error = on_error.clone();
+        let store = store.clone();
+        Callback::from(move |measurements: Vec<Measurement>| {
+            if let Some(model) = step_model.as_ref() {
+                let mut new_model = (**model).clone();
+                new_model.measurements = measurements;
+
+                {
+                    let mut c = cache.borrow_mut();
+                    c.insert(new_model.id.clone(), new_model.clone());
+                }
+                {
+                    let store = store.clone();
+                    let new_model = new_model.clone();
+                    persist(&on_error, async move { store.save_model(&new_model).await });
+                }
 
                 step_model.set(Some(Rc::new(new_model)));
             }
         })
     };
 
+    let on_select_part = {
+        let selected_parts = selected_parts.clone();
+        Callback::from(move |(part, extend): (Option<usize>, bool)| {
+            let mut selection = if extend {
+                (*selected_parts).clone()
+            } else {
+                Vec::new()
+            };
+            if let Some(part) = part {
+                // extending with an already selected part drops it again
+                match selection.iter().position(|selected| *selected == part) {
+                    Some(pos) if extend => {
+                        selection.remove(pos);
+                    }
+                    Some(_) => {}
+                    None => selection.push(part),
+                }
+            }
+            selected_parts.set(selection);
+        })
+    };
+
+    let on_clip_planes_change = {
+        let clip_planes = clip_planes.clone();
+        Callback::from(move |planes: Vec<ClipPlane>| clip_planes.set(planes))
+    };
+
     StepWorkspace {
         result,
+        diagnostics,
         metadata,
         files_index,
         selected_file,
         step_model,
         is_processing,
+        progress,
+        selected_parts,
+        clip_planes,
+        mesh_checks,
         actions: WorkspaceActions {
             on_file_change,
+            on_cancel_processing,
             on_item_click,
             on_delete,
             on_deselect,
             on_clear_history,
             on_visibility_change,
+            on_node_visibility_change,
             on_show_all,
             on_hide_all,
             on_calculate_volume,
             on_calculate_surface,
+            on_select_part,
+            on_measurements_change,
+            on_clip_planes_change,
+            on_error,
         },
     }
 }
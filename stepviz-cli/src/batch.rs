@@ -0,0 +1,382 @@
+use serde::Serialize;
+use std::fmt::Write;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use stepviz_core::mass::MassProperties;
+use stepviz_core::{BoundingBox, StepVizError, parse_step, tessellate_all};
+
+// One report line per file. Whatever could not be read before a failure
+// stays empty and `error` says why.
+#[derive(Debug, Default, Serialize)]
+pub struct BatchRow {
+    // relative to the processed directory
+    pub path: String,
+    pub bytes: u64,
+    pub error: Option<String>,
+    // io, syntax, header or geometry, panics have none
+    pub error_kind: Option<String>,
+    pub file_name: String,
+    pub file_description: String,
+    pub time_stamp: String,
+    pub author: String,
+    pub organization: String,
+    pub originating_system: String,
+    pub preprocessor_version: String,
+    pub schema: String,
+    pub units: Option<String>,
+    pub entity_count: Option<usize>,
+    pub part_count: Option<usize>,
+    pub triangle_count: Option<usize>,
+    pub volume: Option<f64>,
+    pub surface_area: Option<f64>,
+    pub bbox_min: Option<[f64; 3]>,
+    pub bbox_max: Option<[f64; 3]>,
+    // shells that were skipped
+    pub warnings: Option<usize>,
+    pub parse_ms: Option<f64>,
+    pub tessellation_ms: Option<f64>,
+}
+
+// .stp and .step files below `directory`, sorted. Symlinked directories are
+// not followed, they may loop back up the tree.
+pub fn step_files(directory: &Path) -> Result<Vec<PathBuf>, StepVizError> {
+    let mut files = Vec::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let io_error = |e| StepVizError::io(format!("{}: {e}", directory.display()));
+        for entry in std::fs::read_dir(&directory).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
+            let file_type = entry.file_type().map_err(io_error)?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|extension| {
+                extension.eq_ignore_ascii_case("stp") || extension.eq_ignore_ascii_case("step")
+            }) && (file_type.is_file() || path.is_file())
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Processes `files` on `jobs` threads, rows come back in the order of
+// `files`. `progress` sees every row as soon as it is done. Files a worker
+// thread died on (or never got to) are reported as failed rows.
+pub fn process_files(
+    root: &Path,
+    files: &[PathBuf],
+    jobs: usize,
+    progress: impl Fn(usize, &BatchRow) + Sync,
+) -> Vec<BatchRow> {
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let rows: Vec<OnceLock<BatchRow>> = files.iter().map(|_| OnceLock::new()).collect();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = files.get(index) else {
+                            break;
+                        };
+                        let row = rows[index].get_or_init(|| process_file(root, path));
+                        progress(done.fetch_add(1, Ordering::Relaxed) + 1, row);
+                    }
+                })
+            })
+            .collect();
+        // joined here so a panicked worker doesn't take the others down
+        for worker in workers {
+            let _ = worker.join();
+        }
+    });
+    rows.into_iter()
+        .zip(files)
+        .map(|(row, path)| {
+            row.into_inner().unwrap_or_else(|| {
+                let mut row = BatchRow::new(root, path);
+                row.error = Some("worker thread panicked before finishing this file".to_string());
+                row
+            })
+        })
+        .collect()
+}
+
+pub fn process_file(root: &Path, path: &Path) -> BatchRow {
+    let mut row = BatchRow::new(root, path);
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            row.fail(&StepVizError::io(e));
+            return row;
+        }
+    };
+    row.bytes = bytes.len() as u64;
+    let text = String::from_utf8_lossy(&bytes);
+    let file_name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+
+    // a legacy file that trips the geometry code only fails its own row
+    let start = Instant::now();
+    let parsed = catch_unwind(|| parse_step(&text, &file_name));
+    row.parse_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+    let parsed = match parsed {
+        Ok(Ok(parsed)) => parsed,
+        Ok(Err(error)) => {
+            row.fail(&error);
+            return row;
+        }
+        Err(panic) => {
+            row.error = Some(format!("parser panicked: {}", panic_message(&panic)));
+            return row;
+        }
+    };
+    let metadata = &parsed.metadata;
+    let header = &metadata.header;
+    row.file_name = header.file_name.clone();
+    row.file_description = header.file_description.clone();
+    row.time_stamp = header.time_stamp.clone();
+    row.author = header.author.join("; ");
+    row.organization = header.organization.join("; ");
+    row.originating_system = header.originating_system.clone();
+    row.preprocessor_version = header.preprocessor_version.clone();
+    row.schema = header.file_schema.clone();
+    row.units = metadata.length_unit().map(str::to_string);
+    row.entity_count = Some(metadata.entity_count);
+
+    let assembly_warnings = metadata.warnings.len();
+
+    let start = Instant::now();
+    let sections = parsed.prepared.sections;
+    let tessellated = catch_unwind(AssertUnwindSafe(|| tessellate_all(sections)));
+    row.tessellation_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+    let parts = match tessellated {
+        Ok((parts, warnings)) => {
+            row.warnings = Some(assembly_warnings + warnings.len());
+            parts
+        }
+        Err(panic) => {
+            row.error = Some(format!("tessellation panicked: {}", panic_message(&panic)));
+            return row;
+        }
+    };
+    let bodies: Vec<MassProperties> = parts.iter().map(MassProperties::of_part).collect();
+    let mass = MassProperties::combine(&bodies);
+    let bounds = BoundingBox::of_parts(&parts);
+    row.bbox_min = bounds.as_ref().map(|bounds| bounds.min);
+    row.bbox_max = bounds.as_ref().map(|bounds| bounds.max);
+    row.part_count = Some(parts.len());
+    row.triangle_count = Some(parts.iter().map(|part| part.mesh.indices.len() / 3).sum());
+    row.volume = Some(mass.volume);
+    row.surface_area = Some(mass.area);
+    row
+}
+
+impl BatchRow {
+    fn new(root: &Path, path: &Path) -> Self {
+        BatchRow {
+            path: path
+                .strip_prefix(root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn fail(&mut self, error: &StepVizError) {
+        self.error = Some(error.to_string());
+        self.error_kind = Some(error.kind().to_string());
+    }
+}
+
+pub(crate) fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+const CSV_COLUMNS: [&str; 27] = [
+    "path",
+    "bytes",
+    "error",
+    "error_kind",
+    "file_name",
+    "file_description",
+    "time_stamp",
+    "author",
+    "organization",
+    "originating_system",
+    "preprocessor_version",
+    "schema",
+    "units",
+    "entity_count",
+    "part_count",
+    "triangle_count",
+    "volume",
+    "surface_area",
+    "bbox_min_x",
+    "bbox_min_y",
+    "bbox_min_z",
+    "bbox_max_x",
+    "bbox_max_y",
+    "bbox_max_z",
+    "warnings",
+    "parse_ms",
+    "tessellation_ms",
+];
+
+// RFC 4180 CSV with a header line, empty cells for missing values
+pub fn to_csv(rows: &[BatchRow]) -> String {
+    let mut out = CSV_COLUMNS.join(",") + "\r\n";
+    for row in rows {
+        let text = |value: &str| value.to_string();
+        let number = |value: Option<f64>| value.map_or(String::new(), |v| v.to_string());
+        let count = |value: Option<usize>| value.map_or(String::new(), |v| v.to_string());
+        let axis = |point: Option<[f64; 3]>, axis: usize| number(point.map(|p| p[axis]));
+        let cells = [
+            text(&row.path),
+            row.bytes.to_string(),
+            text(row.error.as_deref().unwrap_or("")),
+            text(row.error_kind.as_deref().unwrap_or("")),
+            text(&row.file_name),
+            text(&row.file_description),
+            text(&row.time_stamp),
+            text(&row.author),
+            text(&row.organization),
+            text(&row.originating_system),
+            text(&row.preprocessor_version),
+            text(&row.schema),
+            text(row.units.as_deref().unwrap_or("")),
+            count(row.entity_count),
+            count(row.part_count),
+            count(row.triangle_count),
+            number(row.volume),
+            number(row.surface_area),
+            axis(row.bbox_min, 0),
+            axis(row.bbox_min, 1),
+            axis(row.bbox_min, 2),
+            axis(row.bbox_max, 0),
+            axis(row.bbox_max, 1),
+            axis(row.bbox_max, 2),
+            count(row.warnings),
+            number(row.parse_ms),
+            number(row.tessellation_ms),
+        ];
+        let line: Vec<String> = cells.iter().map(|cell| csv_cell(cell)).collect();
+        let _ = write!(out, "{}\r\n", line.join(","));
+    }
+    out
+}
+
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_quoted_when_needed() {
+        assert_eq!(csv_cell("plain"), "plain");
+        assert_eq!(csv_cell("a, b"), "\"a, b\"");
+        assert_eq!(csv_cell("say \"hi\"\n"), "\"say \"\"hi\"\"\n\"");
+    }
+
+    #[test]
+    fn directory_report_keeps_failures() {
+        let root = std::env::temp_dir().join(format!("stepviz-batch-{}", std::process::id()));
+        let nested = root.join("legacy");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(
+            root.join("part.STEP"),
+            include_str!("../../examples/Part1.stp"),
+        )
+        .unwrap();
+        std::fs::write(nested.join("broken.stp"), "ISO-10303-21;\nnot a step file").unwrap();
+        std::fs::write(root.join("notes.txt"), "ignored").unwrap();
+
+        let files = step_files(&root).unwrap();
+        assert_eq!(files, [nested.join("broken.stp"), root.join("part.STEP")]);
+        let seen = AtomicUsize::new(0);
+        let rows = process_files(&root, &files, 4, |_, _| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        });
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(seen.into_inner(), 2);
+
+        let [broken, part] = &rows[..] else {
+            panic!("{rows:?}");
+        };
+        assert_eq!(
+            broken.path,
+            Path::new("legacy").join("broken.stp").to_string_lossy()
+        );
+        assert_eq!(broken.error_kind.as_deref(), Some("syntax"));
+        assert!(broken.entity_count.is_none() && broken.parse_ms.is_some());
+
+        assert_eq!(part.path, "part.STEP");
+        assert!(part.error.is_none(), "{part:?}");
+        assert!(part.part_count.unwrap() > 0 && part.triangle_count.unwrap() > 0);
+        assert!(part.volume.unwrap() > 0.0 && part.tessellation_ms.is_some());
+        assert_eq!(part.warnings, Some(0));
+        // Part1 is a 100 x 50 x 50 block at the origin
+        let max = part.bbox_max.unwrap();
+        assert!((0..3).all(|i| (max[i] - [100.0, 50.0, 50.0][i]).abs() < 1e-6));
+        assert!(part.bbox_min.unwrap().iter().all(|v| v.abs() < 1e-6));
+
+        let csv = to_csv(&rows);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("path,bytes,error,error_kind,file_name,"));
+        assert!(lines[2].starts_with("part.STEP,"));
+        let json = serde_json::to_value(&rows).unwrap();
+        assert_eq!(json[1]["part_count"], part.part_count.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_not_followed() {
+        let root = std::env::temp_dir().join(format!("stepviz-links-{}", std::process::id()));
+        let nested = root.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("a.stp"), "").unwrap();
+        std::os::unix::fs::symlink(&root, nested.join("loop")).unwrap();
+        std::os::unix::fs::symlink(nested.join("a.stp"), root.join("b.stp")).unwrap();
+
+        let files = step_files(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        // linked files still count
+        assert_eq!(files.unwrap(), [root.join("b.stp"), nested.join("a.stp")]);
+    }
+
+    #[test]
+    fn panicked_workers_leave_failed_rows() {
+        let root = Path::new("missing");
+        let files = [root.join("a.stp"), root.join("b.stp"), root.join("c.stp")];
+        // the only worker dies after its first file
+        let rows = process_files(root, &files, 1, |done, _| {
+            assert!(done < 1, "progress callback failed");
+        });
+        let errors: Vec<Option<&str>> = rows.iter().map(|row| row.error_kind.as_deref()).collect();
+        assert_eq!(errors, [Some("io"), None, None]);
+        assert_eq!(rows[1].path, "b.stp");
+        assert!(rows[1].error.as_deref().unwrap().contains("panicked"));
+        assert!(rows[2].error.is_some());
+    }
+}
@@ -0,0 +1,368 @@
+// stepviz: inspect, check and convert STEP files without a browser
+mod batch;
+mod report;
+mod serve;
+
+use batch::{process_files, step_files, to_csv};
+use report::{Info, Stats};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use stepviz_core::export::{ExportFormat, ExportOptions, exported_parts};
+use stepviz_core::{StepVizError, load_step, parse_step};
+
+const USAGE: &str = "\
+usage: stepviz <command> <file.stp> [options]
+       stepviz batch <directory> [options]
+       stepviz serve [--port <n>]
+
+commands:
+  info      header, units, entity count and product structure
+  stats     geometry, mass, mesh checks and validation properties
+  convert   write the tessellated parts as another format
+  batch     one report row per STEP file found below a directory
+  serve     info, stats and convert as a local HTTP service on 127.0.0.1
+
+options:
+  --json            print JSON instead of text
+  --strict          stats: parts that are not closed fail the check too
+  --to <format>     convert: stl, stl-ascii, glb, obj or ply
+  -o, --output <f>  convert: output file, default is the input renamed
+                    batch: report file, default is stdout
+  --local           convert: OBJ and PLY parts in their own coordinates
+  --jobs <n>        batch: files processed at once, default is one per core
+  --port <n>        serve: port on 127.0.0.1, default 8707, 0 picks a free one
+
+batch writes CSV unless --json is given.
+stats exits with 2 when a check fails, batch when a file fails,
+every command with 1 on errors.";
+
+// checks failed, errors use ExitCode::FAILURE
+const EXIT_CHECK_FAILED: u8 = 2;
+
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    command: String,
+    file: PathBuf,
+    json: bool,
+    strict: bool,
+    to: Option<ExportFormat>,
+    output: Option<PathBuf>,
+    local: bool,
+    jobs: Option<usize>,
+    port: Option<u16>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        command: args.next().ok_or("missing command")?,
+        ..Default::default()
+    };
+    if !["info", "stats", "convert", "batch", "serve"].contains(&parsed.command.as_str()) {
+        return Err(format!("unknown command '{}'", parsed.command));
+    }
+    let mut file = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+        match arg.as_str() {
+            "--json" => parsed.json = true,
+            "--strict" => parsed.strict = true,
+            "--local" => parsed.local = true,
+            "--to" => {
+                let name = value("--to")?;
+                let format =
+                    ExportFormat::from_name(&name).ok_or(format!("unknown format '{name}'"))?;
+                parsed.to = Some(format);
+            }
+            "--jobs" => {
+                let jobs = value("--jobs")?;
+                match jobs.parse() {
+                    Ok(jobs) if jobs > 0 => parsed.jobs = Some(jobs),
+                    _ => return Err(format!("invalid job count '{jobs}'")),
+                }
+            }
+            "--port" => {
+                let port = value("--port")?;
+                parsed.port = Some(port.parse().map_err(|_| format!("invalid port '{port}'"))?);
+            }
+            "-o" | "--output" => parsed.output = Some(PathBuf::from(value("--output")?)),
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{flag}'")),
+            path if file.is_none() => file = Some(PathBuf::from(path)),
+            extra => return Err(format!("unexpected argument '{extra}'")),
+        }
+    }
+    if parsed.command == "serve" {
+        return match file {
+            Some(extra) => Err(format!("unexpected argument '{}'", extra.display())),
+            None => Ok(parsed),
+        };
+    }
+    parsed.file = file.ok_or(if parsed.command == "batch" {
+        "missing directory"
+    } else {
+        "missing STEP file"
+    })?;
+    if parsed.command == "convert" && parsed.to.is_none() {
+        return Err("convert needs --to <format>".to_string());
+    }
+    Ok(parsed)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let args = match parse_args(args.into_iter()) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("stepviz: {message}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(&args) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("stepviz: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<ExitCode, StepVizError> {
+    match args.command.as_str() {
+        "batch" => return run_batch(args),
+        "serve" => {
+            let port = args.port.unwrap_or(serve::DEFAULT_PORT);
+            serve::serve(port, |address| {
+                // the first line tells scripts and tests which port was picked
+                println!("listening on http://{address}");
+                eprintln!("\n{}", serve::ROUTES);
+            })?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => {}
+    }
+    let bytes = std::fs::read(&args.file)
+        .map_err(|e| StepVizError::io(format!("{}: {e}", args.file.display())))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let file_name = args
+        .file
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+
+    match (args.command.as_str(), args.to) {
+        ("info", _) => {
+            let parsed = parse_step(&text, &file_name)?;
+            print(&Info::new(&file_name, &parsed), args.json)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        ("stats", _) => {
+            let stats = Stats::new(&file_name, &load_step(&text, &file_name)?);
+            print(&stats, args.json)?;
+            let passed = stats.validation_passed && (!args.strict || stats.mesh_clean);
+            Ok(if passed {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(EXIT_CHECK_FAILED)
+            })
+        }
+        ("convert", Some(format)) => {
+            let model = load_step(&text, &file_name)?;
+            let output = args
+                .output
+                .clone()
+                .unwrap_or_else(|| args.file.with_extension(format.extension()));
+            let written = convert(&model, format, !args.local, &output)?;
+            print(&written, args.json)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => unreachable!("parse_args checks the command and --to"),
+    }
+}
+
+fn run_batch(args: &Args) -> Result<ExitCode, StepVizError> {
+    let files = step_files(&args.file)?;
+    let jobs = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    // panics become failed rows, the default hook would only add noise
+    std::panic::set_hook(Box::new(|_| {}));
+    let total = files.len();
+    let rows = process_files(&args.file, &files, jobs, |done, row| {
+        let status = row.error.as_deref().unwrap_or("ok");
+        eprintln!("[{done}/{total}] {}: {status}", row.path);
+    });
+    let _ = std::panic::take_hook();
+
+    let report = if args.json {
+        serde_json::to_string_pretty(&rows).map_err(StepVizError::io)? + "\n"
+    } else {
+        to_csv(&rows)
+    };
+    match &args.output {
+        Some(path) => std::fs::write(path, report)
+            .map_err(|e| StepVizError::io(format!("{}: {e}", path.display())))?,
+        None => write_stdout(&report)?,
+    }
+    let failed = rows.iter().filter(|row| row.error.is_some()).count();
+    eprintln!("{} file(s), {failed} failed", rows.len());
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_CHECK_FAILED)
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct Written {
+    files: Vec<WrittenFile>,
+    parts: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct WrittenFile {
+    path: PathBuf,
+    bytes: usize,
+}
+
+impl std::fmt::Display for Written {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in &self.files {
+            writeln!(f, "wrote {} ({} bytes)", file.path.display(), file.bytes)?;
+        }
+        writeln!(f, "{} part(s)", self.parts)
+    }
+}
+
+// writes every part of `model` next to `output`, which names the main file
+fn convert(
+    model: &stepviz_core::StepModel,
+    format: ExportFormat,
+    apply_transforms: bool,
+    output: &Path,
+) -> Result<Written, StepVizError> {
+    let options = ExportOptions {
+        visible_only: false,
+        apply_transforms,
+    };
+    let parts = exported_parts(model, options);
+    let stem = output
+        .file_stem()
+        .ok_or_else(|| StepVizError::io(format!("{}: not a file name", output.display())))?
+        .to_string_lossy();
+    let directory = output.parent().unwrap_or(Path::new(""));
+    let mut files = Vec::new();
+    for (index, (name, content)) in format
+        .write(model, options, &stem, &parts)
+        .into_iter()
+        .enumerate()
+    {
+        // the main file keeps the extension it was given
+        let path = if index == 0 {
+            output.to_path_buf()
+        } else {
+            directory.join(name)
+        };
+        std::fs::write(&path, &content)
+            .map_err(|e| StepVizError::io(format!("{}: {e}", path.display())))?;
+        files.push(WrittenFile {
+            path,
+            bytes: content.len(),
+        });
+    }
+    Ok(Written {
+        files,
+        parts: parts.len(),
+    })
+}
+
+fn print<T: Serialize + std::fmt::Display>(value: &T, json: bool) -> Result<(), StepVizError> {
+    let text = if json {
+        serde_json::to_string_pretty(value).map_err(StepVizError::io)? + "\n"
+    } else {
+        value.to_string()
+    };
+    write_stdout(&text)
+}
+
+fn write_stdout(text: &str) -> Result<(), StepVizError> {
+    match std::io::stdout().lock().write_all(text.as_bytes()) {
+        // piped into head or the like, which stopped reading
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(StepVizError::io),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stepviz_core::stl::StlFormat;
+
+    fn parse(args: &str) -> Result<Args, String> {
+        parse_args(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn arguments() {
+        let args = parse("convert part.stp --to stl-ascii -o out/x.stl --local --json").unwrap();
+        assert_eq!(args.command, "convert");
+        assert_eq!(args.file, PathBuf::from("part.stp"));
+        assert_eq!(args.to, Some(ExportFormat::Stl(StlFormat::Ascii)));
+        assert_eq!(args.output, Some(PathBuf::from("out/x.stl")));
+        assert!(args.local && args.json && !args.strict);
+
+        assert!(parse("stats a.stp --strict").unwrap().strict);
+        assert_eq!(parse("batch legacy --jobs 8").unwrap().jobs, Some(8));
+        assert_eq!(
+            parse("batch legacy --jobs 0").unwrap_err(),
+            "invalid job count '0'"
+        );
+        assert_eq!(parse("batch").unwrap_err(), "missing directory");
+        assert_eq!(parse("serve --port 0").unwrap().port, Some(0));
+        assert_eq!(
+            parse("serve a.stp").unwrap_err(),
+            "unexpected argument 'a.stp'"
+        );
+        assert_eq!(parse("info").unwrap_err(), "missing STEP file");
+        assert_eq!(parse("open a.stp").unwrap_err(), "unknown command 'open'");
+        assert_eq!(
+            parse("convert a.stp").unwrap_err(),
+            "convert needs --to <format>"
+        );
+        assert_eq!(
+            parse("convert a.stp --to step").unwrap_err(),
+            "unknown format 'step'"
+        );
+        assert_eq!(
+            parse("info a.stp b.stp").unwrap_err(),
+            "unexpected argument 'b.stp'"
+        );
+    }
+
+    #[test]
+    fn convert_writes_obj_with_its_material_file() {
+        let text = include_str!("../../examples/Part1.stp");
+        let model = load_step(text, "Part1.stp").unwrap();
+        let directory = std::env::temp_dir().join(format!("stepviz-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let output = directory.join("part.obj");
+
+        let written = convert(&model, ExportFormat::Obj, true, &output).unwrap();
+        let paths: Vec<&Path> = written
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [output.as_path(), directory.join("part.mtl").as_path()]
+        );
+        let obj = std::fs::read_to_string(&output).unwrap();
+        assert!(obj.starts_with("mtllib part.mtl\n"));
+        assert_eq!(written.parts, model.render_parts.len());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
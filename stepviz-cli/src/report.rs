@@ -0,0 +1,305 @@
+use serde::Serialize;
+use std::fmt;
+use stepviz_core::mass::MassProperties;
+use stepviz_core::mesh_check::MeshCheck;
+use stepviz_core::pipeline::ParsedStep;
+use stepviz_core::types::{BoundingBox, StepHeader};
+use stepviz_core::validation::{ValidationValue, validate};
+use stepviz_core::{ProductNode, StepModel};
+
+// What `stepviz info` prints: the header and product structure, no geometry
+#[derive(Debug, Serialize)]
+pub struct Info {
+    pub file: String,
+    pub id: String,
+    pub header: StepHeader,
+    pub units: Option<String>,
+    pub entity_count: usize,
+    pub section_entity_counts: Vec<usize>,
+    pub products: Vec<ProductNode>,
+}
+
+impl Info {
+    pub fn new(file: &str, parsed: &ParsedStep) -> Self {
+        Self {
+            file: file.to_string(),
+            id: parsed.id.clone(),
+            header: parsed.metadata.header.clone(),
+            units: parsed.metadata.length_unit().map(str::to_string),
+            entity_count: parsed.metadata.entity_count,
+            section_entity_counts: parsed.metadata.section_entity_counts.clone(),
+            products: parsed.prepared.product_tree.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = &self.header;
+        row(f, "File", &self.file)?;
+        row(f, "Name", &header.file_name)?;
+        row(f, "Schema", &header.file_schema)?;
+        row(f, "Originating", &header.originating_system)?;
+        row(f, "Preprocessor", &header.preprocessor_version)?;
+        row(f, "Author", &header.author.join(", "))?;
+        row(f, "Organization", &header.organization.join(", "))?;
+        row(f, "Time stamp", &header.time_stamp)?;
+        row(f, "Units", self.units.as_deref().unwrap_or("-"))?;
+        let sections = self.section_entity_counts.len();
+        row(
+            f,
+            "Entities",
+            &format!("{} in {sections} section(s)", self.entity_count),
+        )?;
+        writeln!(f, "Products")?;
+        for product in &self.products {
+            product_lines(f, product, 1)?;
+        }
+        Ok(())
+    }
+}
+
+// What `stepviz stats` prints: geometry, mass, mesh and validation checks
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub file: String,
+    pub units: Option<String>,
+    pub parts: usize,
+    pub vertices: usize,
+    pub triangles: usize,
+    // of the placed parts
+    pub bounding_box: Option<BoundingBox>,
+    pub volume: f64,
+    pub surface_area: f64,
+    pub centroid: [f64; 3],
+    pub mesh: MeshStats,
+    pub validation: Vec<CheckReport>,
+    // every validation property of the file matches the geometry
+    pub validation_passed: bool,
+    // every part is a closed, consistently oriented shell
+    pub mesh_clean: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MeshStats {
+    pub closed_parts: usize,
+    pub open_edges: usize,
+    pub non_manifold_edges: usize,
+    pub flipped_edges: usize,
+    pub degenerate_triangles: usize,
+    pub problem_parts: Vec<PartProblem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartProblem {
+    pub part: String,
+    pub issues: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub product: String,
+    pub property: String,
+    pub expected: ValidationValue,
+    pub computed: ValidationValue,
+    pub relative_error: f64,
+    pub passed: bool,
+}
+
+impl Stats {
+    pub fn new(file: &str, model: &StepModel) -> Self {
+        let bodies: Vec<MassProperties> = model
+            .render_parts
+            .iter()
+            .map(MassProperties::of_part)
+            .collect();
+        let mass = MassProperties::combine(&bodies);
+
+        let mut mesh = MeshStats::default();
+        for (index, part) in model.render_parts.iter().enumerate() {
+            let check = MeshCheck::of_part(part);
+            mesh.closed_parts += usize::from(check.is_closed());
+            mesh.open_edges += check.open_edges.len();
+            mesh.non_manifold_edges += check.non_manifold_edges.len();
+            mesh.flipped_edges += check.flipped_edges.len();
+            mesh.degenerate_triangles += check.degenerate_triangles;
+            if !check.volume_reliable() {
+                mesh.problem_parts.push(PartProblem {
+                    part: model.part_name(index),
+                    issues: check.summary(),
+                });
+            }
+        }
+
+        let validation: Vec<CheckReport> = validate(model)
+            .into_iter()
+            .map(|check| CheckReport {
+                product: check.name,
+                property: check.expected.label().to_string(),
+                expected: check.expected,
+                computed: check.computed,
+                relative_error: check.relative_error,
+                passed: check.passed,
+            })
+            .collect();
+
+        Self {
+            file: file.to_string(),
+            units: model.metadata.length_unit().map(str::to_string),
+            parts: model.render_parts.len(),
+            vertices: model
+                .render_parts
+                .iter()
+                .map(|p| p.mesh.vertices.len())
+                .sum(),
+            triangles: model
+                .render_parts
+                .iter()
+                .map(|p| p.mesh.indices.len() / 3)
+                .sum(),
+            bounding_box: model.metadata.bounding_box.clone(),
+            volume: mass.volume,
+            surface_area: mass.area,
+            centroid: mass.centroid,
+            validation_passed: validation.iter().all(|check| check.passed),
+            mesh_clean: mesh.problem_parts.is_empty(),
+            mesh,
+            validation,
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = self.units.as_deref().unwrap_or("");
+        row(f, "File", &self.file)?;
+        row(f, "Parts", &self.parts.to_string())?;
+        row(f, "Vertices", &self.vertices.to_string())?;
+        row(f, "Triangles", &self.triangles.to_string())?;
+        if let Some(bounds) = &self.bounding_box {
+            row(
+                f,
+                "Bounding box",
+                &format!("{} to {}", point(bounds.min), point(bounds.max)),
+            )?;
+        }
+        row(f, "Volume", &format!("{:.4} {unit}³", self.volume))?;
+        row(
+            f,
+            "Surface area",
+            &format!("{:.4} {unit}²", self.surface_area),
+        )?;
+        row(f, "Centroid", &point(self.centroid))?;
+
+        let mesh = &self.mesh;
+        row(
+            f,
+            "Mesh",
+            &format!(
+                "{} of {} parts closed, {} open, {} non-manifold, {} flipped edges, {} degenerate triangles",
+                mesh.closed_parts,
+                self.parts,
+                mesh.open_edges,
+                mesh.non_manifold_edges,
+                mesh.flipped_edges,
+                mesh.degenerate_triangles
+            ),
+        )?;
+        for problem in &mesh.problem_parts {
+            writeln!(f, "  {}: {}", problem.part, problem.issues)?;
+        }
+
+        let failed = self.validation.iter().filter(|check| !check.passed).count();
+        let summary = if self.validation.is_empty() {
+            "no validation properties in the file".to_string()
+        } else {
+            let total = self.validation.len();
+            format!("{} of {total} checks passed", total - failed)
+        };
+        row(f, "Validation", &summary)?;
+        for check in &self.validation {
+            writeln!(
+                f,
+                "  {} {} {}: file {}, computed {}, error {:.3}%",
+                if check.passed { "pass" } else { "FAIL" },
+                check.product,
+                check.property,
+                check.expected,
+                check.computed,
+                check.relative_error * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn row(f: &mut fmt::Formatter<'_>, label: &str, value: &str) -> fmt::Result {
+    writeln!(f, "{label:<14}{value}")
+}
+
+fn point(p: [f64; 3]) -> String {
+    format!("({:.4}, {:.4}, {:.4})", p[0], p[1], p[2])
+}
+
+fn product_lines(f: &mut fmt::Formatter<'_>, node: &ProductNode, depth: usize) -> fmt::Result {
+    let name = node.instance_name.as_ref().unwrap_or(&node.name);
+    write!(f, "{:indent$}{name}", "", indent = depth * 2)?;
+    if node.instance_count > 1 {
+        write!(f, " (x{})", node.instance_count)?;
+    }
+    writeln!(f)?;
+    for child in &node.children {
+        product_lines(f, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stepviz_core::{load_step, parse_step};
+
+    const ASSEMBLY: &str = include_str!("../../examples/as1-tc-214.stp");
+
+    #[test]
+    fn info_lists_the_product_structure() {
+        let parsed = parse_step(ASSEMBLY, "as1-tc-214.stp").unwrap();
+        let info = Info::new("as1-tc-214.stp", &parsed);
+        let text = info.to_string();
+        assert!(text.starts_with("File          as1-tc-214.stp\n"), "{text}");
+        assert!(text.contains("\nProducts\n  "), "{text}");
+        assert!(text.contains("\nUnits         mm\n"), "{text}");
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(
+            json["products"][0]["name"],
+            parsed.prepared.product_tree[0].name
+        );
+        assert_eq!(json["entity_count"], parsed.metadata.entity_count);
+    }
+
+    #[test]
+    fn stats_gate_on_validation_and_mesh() {
+        let model = load_step(ASSEMBLY, "as1-tc-214.stp").unwrap();
+        let stats = Stats::new("as1-tc-214.stp", &model);
+        assert_eq!(stats.parts, model.render_parts.len());
+        assert_eq!(stats.validation.len(), 27);
+        assert!(stats.validation_passed);
+        assert!(stats.mesh_clean);
+        assert!(stats.volume > 0.0);
+        let text = stats.to_string();
+        assert!(text.contains("27 of 27 checks passed"));
+        assert!(text.contains(" mm³\n") && text.contains(" mm²\n"), "{text}");
+
+        let open = include_str!("../../examples/boxy_with_cylindricity.stp");
+        let model = load_step(open, "boxy_with_cylindricity.stp").unwrap();
+        let stats = Stats::new("boxy_with_cylindricity.stp", &model);
+        assert!(!stats.mesh_clean);
+        assert_eq!(stats.mesh.open_edges, 360);
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(
+            json["mesh"]["problem_parts"][0]["issues"],
+            "360 open edges, 6 degenerate triangles"
+        );
+    }
+}
@@ -0,0 +1,570 @@
+// stepviz serve: info, stats and convert over HTTP, bound to loopback only
+use crate::batch::panic_message;
+use crate::report::{Info, Stats};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use stepviz_core::export::{ExportFormat, ExportOptions, exported_parts};
+use stepviz_core::zip::write_zip;
+use stepviz_core::{StepVizError, load_step, parse_step};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const DEFAULT_PORT: u16 = 8707;
+
+// uploads above this are refused without reading them
+const MAX_UPLOAD: usize = 512 * 1024 * 1024;
+// threads answering requests, further connections wait for one of them
+const REQUEST_THREADS: usize = 8;
+// background jobs processing at once, more are refused with 503
+const MAX_RUNNING_JOBS: usize = 4;
+// finished jobs are forgotten after this long, or oldest first beyond the count
+const JOB_TTL: Duration = Duration::from_secs(60 * 60);
+const MAX_FINISHED_JOBS: usize = 64;
+
+pub const ROUTES: &str = "\
+GET    /health              liveness check
+POST   /info                header, units and product structure as JSON
+POST   /stats               geometry, mass properties, mesh and validation checks as JSON
+POST   /convert?format=<f>  the tessellated parts as stl, stl-ascii, glb, obj or ply
+POST   /jobs/<task>         runs info, stats or convert in the background, returns a job id
+GET    /jobs/<id>           job status
+GET    /jobs/<id>/result    job result once the status is done
+DELETE /jobs/<id>           forgets a finished job and its result
+
+The request body is the STEP file. ?name=<file.stp> names the output files,
+?local=true keeps OBJ and PLY parts in their own coordinates. Finished jobs
+are kept for an hour, at most 64 of them.";
+
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+    // sent as an attachment name for converted files
+    pub file_name: Option<String>,
+}
+
+impl Reply {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec_pretty(value).unwrap_or_default(),
+            file_name: None,
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(
+            status,
+            &serde_json::json!({ "error": message.into(), "status": status }),
+        )
+    }
+
+    fn failure(status: u16, error: &StepVizError) -> Self {
+        Self::json(
+            status,
+            &serde_json::json!({
+                "error": error.to_string(),
+                "kind": error.kind(),
+                "status": status,
+            }),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Task {
+    Info,
+    Stats,
+    Convert(ExportFormat),
+}
+
+impl Task {
+    fn parse(name: &str, query: &HashMap<String, String>) -> Result<Self, Reply> {
+        match name {
+            "info" => Ok(Task::Info),
+            "stats" => Ok(Task::Stats),
+            "convert" => {
+                let format = query
+                    .get("format")
+                    .ok_or(Reply::error(400, "convert needs ?format=<format>"))?;
+                ExportFormat::from_name(format)
+                    .map(Task::Convert)
+                    .ok_or(Reply::error(400, format!("unknown format '{format}'")))
+            }
+            _ => Err(Reply::error(404, format!("unknown task '{name}'"))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Task::Info => "info",
+            Task::Stats => "stats",
+            Task::Convert(_) => "convert",
+        }
+    }
+
+    // a file that crashes the geometry code becomes a 500, the server goes on
+    fn run(self, upload: &Upload) -> Reply {
+        let result = catch_unwind(AssertUnwindSafe(|| self.run_unchecked(upload)));
+        match result {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(error)) => Reply::failure(422, &error),
+            Err(panic) => Reply::error(
+                500,
+                format!("processing panicked: {}", panic_message(&panic)),
+            ),
+        }
+    }
+
+    fn run_unchecked(self, upload: &Upload) -> Result<Reply, StepVizError> {
+        let text = String::from_utf8_lossy(&upload.bytes);
+        let name = &upload.file_name;
+        match self {
+            Task::Info => Ok(Reply::json(
+                200,
+                &Info::new(name, &parse_step(&text, name)?),
+            )),
+            Task::Stats => Ok(Reply::json(
+                200,
+                &Stats::new(name, &load_step(&text, name)?),
+            )),
+            Task::Convert(format) => {
+                let model = load_step(&text, name)?;
+                let options = ExportOptions {
+                    visible_only: false,
+                    apply_transforms: !upload.local,
+                };
+                let stem = name
+                    .rsplit_once('.')
+                    .map_or(name.as_str(), |(stem, _)| stem);
+                let files = format.write(&model, options, stem, &exported_parts(&model, options));
+                // OBJ comes with its material file, several files go out as one zip
+                Ok(match &files[..] {
+                    [(file_name, content)] => Reply {
+                        status: 200,
+                        content_type: format.mime(),
+                        body: content.clone(),
+                        file_name: Some(file_name.clone()),
+                    },
+                    _ => Reply {
+                        status: 200,
+                        content_type: "application/zip",
+                        body: write_zip(
+                            files
+                                .iter()
+                                .map(|(name, content)| (name.as_str(), content.as_slice())),
+                        ),
+                        file_name: Some(format!("{stem}.zip")),
+                    },
+                })
+            }
+        }
+    }
+}
+
+struct Upload {
+    file_name: String,
+    bytes: Vec<u8>,
+    local: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct JobStatus {
+    id: String,
+    task: &'static str,
+    // running, done or failed
+    status: &'static str,
+    error: Option<String>,
+}
+
+struct Job {
+    task: Task,
+    result: Option<Reply>,
+    finished: Option<Instant>,
+}
+
+impl Job {
+    fn status(&self, id: &str) -> JobStatus {
+        let (status, error) = match &self.result {
+            None => ("running", None),
+            Some(reply) if reply.status == 200 => ("done", None),
+            Some(reply) => (
+                "failed",
+                serde_json::from_slice::<serde_json::Value>(&reply.body)
+                    .ok()
+                    .and_then(|body| body["error"].as_str().map(str::to_string)),
+            ),
+        };
+        JobStatus {
+            id: id.to_string(),
+            task: self.task.name(),
+            status,
+            error,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl Jobs {
+    fn start(self: &Arc<Self>, task: Task, upload: Upload) -> Reply {
+        let id = match job_id() {
+            Ok(id) => id,
+            Err(message) => return Reply::error(500, message),
+        };
+        let job = Job {
+            task,
+            result: None,
+            finished: None,
+        };
+        let reply = Reply::json(202, &job.status(&id));
+        {
+            let mut jobs = self.lock();
+            let running = jobs.values().filter(|job| job.result.is_none()).count();
+            if running >= MAX_RUNNING_JOBS {
+                return Reply::error(
+                    503,
+                    format!("{running} jobs are running already, try again later"),
+                );
+            }
+            jobs.insert(id.clone(), job);
+        }
+        let jobs = Arc::clone(self);
+        std::thread::spawn(move || {
+            let result = task.run(&upload);
+            let mut map = jobs.lock();
+            // running jobs are neither deleted nor expired
+            if let Some(job) = map.get_mut(&id) {
+                job.result = Some(result);
+                job.finished = Some(Instant::now());
+            }
+            expire(&mut map, Instant::now());
+        });
+        reply
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Job>> {
+        // a panic never happens while the map is held, so poison is harmless
+        let mut jobs = self
+            .jobs
+            .lock()
+            .unwrap_or_else(|poison| poison.into_inner());
+        expire(&mut jobs, Instant::now());
+        jobs
+    }
+}
+
+// drops finished jobs past JOB_TTL, then the oldest beyond MAX_FINISHED_JOBS
+fn expire(jobs: &mut HashMap<String, Job>, now: Instant) {
+    jobs.retain(|_, job| {
+        job.finished
+            .is_none_or(|at| now.duration_since(at) < JOB_TTL)
+    });
+    let mut finished: Vec<(Instant, String)> = jobs
+        .iter()
+        .filter_map(|(id, job)| Some((job.finished?, id.clone())))
+        .collect();
+    if finished.len() > MAX_FINISHED_JOBS {
+        finished.sort();
+        for (_, id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(id);
+        }
+    }
+}
+
+// 128 random bits, the id is all it takes to read or delete a job
+fn job_id() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| format!("no random job id: {e}"))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+// Routes one request, `body` is only read for POST
+pub fn handle(jobs: &Arc<Jobs>, method: &Method, url: &str, body: Vec<u8>) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let query = query_pairs(query);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let upload = || Upload {
+        file_name: query
+            .get("name")
+            .cloned()
+            .unwrap_or_else(|| "upload.stp".to_string()),
+        bytes: body,
+        local: query.get("local").is_some_and(|value| value == "true"),
+    };
+
+    match (method, &segments[..]) {
+        (Method::Get, ["health"]) => Reply::json(200, &serde_json::json!({ "status": "ok" })),
+        (Method::Post, [task @ ("info" | "stats" | "convert")]) => {
+            match Task::parse(task, &query) {
+                Ok(task) => task.run(&upload()),
+                Err(reply) => reply,
+            }
+        }
+        (Method::Post, ["jobs", task]) => match Task::parse(task, &query) {
+            Ok(task) => jobs.start(task, upload()),
+            Err(reply) => reply,
+        },
+        (Method::Get, ["jobs", id]) => match jobs.lock().get(*id) {
+            Some(job) => Reply::json(200, &job.status(id)),
+            None => Reply::error(404, format!("no job '{id}'")),
+        },
+        (Method::Get, ["jobs", id, "result"]) => match jobs.lock().get(*id) {
+            Some(Job {
+                result: Some(reply),
+                ..
+            }) => reply.clone(),
+            Some(_) => Reply::error(409, format!("job '{id}' is still running")),
+            None => Reply::error(404, format!("no job '{id}'")),
+        },
+        (Method::Delete, ["jobs", id]) => {
+            let mut jobs = jobs.lock();
+            match jobs.get(*id) {
+                // its thread would go on unaccounted for, see MAX_RUNNING_JOBS
+                Some(Job { result: None, .. }) => {
+                    Reply::error(409, format!("job '{id}' is still running"))
+                }
+                Some(_) => {
+                    jobs.remove(*id);
+                    Reply::json(200, &serde_json::json!({ "id": id, "deleted": true }))
+                }
+                None => Reply::error(404, format!("no job '{id}'")),
+            }
+        }
+        _ => Reply::error(404, format!("no route for {method} {path}")),
+    }
+}
+
+// Serves until the process is stopped on REQUEST_THREADS threads
+pub fn serve(port: u16, on_ready: impl FnOnce(std::net::SocketAddr)) -> Result<(), StepVizError> {
+    let server = Server::http(("127.0.0.1", port)).map_err(StepVizError::io)?;
+    let address = server
+        .server_addr()
+        .to_ip()
+        .ok_or_else(|| StepVizError::io("server is not listening on TCP"))?;
+    on_ready(address);
+    let server = Arc::new(server);
+    let jobs = Arc::new(Jobs::default());
+    let threads: Vec<_> = (0..REQUEST_THREADS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let jobs = Arc::clone(&jobs);
+            std::thread::spawn(move || {
+                while let Ok(request) = server.recv() {
+                    respond(&jobs, address.port(), request);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        let _ = thread.join();
+    }
+    Ok(())
+}
+
+// Only names of the loopback interface, so a web page that got its own
+// domain to resolve to 127.0.0.1 (DNS rebinding) is turned away
+fn host_allowed(host: Option<&str>, port: u16) -> bool {
+    host.is_some_and(|host| {
+        host.eq_ignore_ascii_case(&format!("127.0.0.1:{port}"))
+            || host.eq_ignore_ascii_case(&format!("localhost:{port}"))
+    })
+}
+
+fn respond(jobs: &Arc<Jobs>, port: u16, mut request: Request) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let host = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Host"))
+        .map(|header| header.value.as_str());
+    let reply = if !host_allowed(host, port) {
+        Reply::error(403, "requests must address localhost or 127.0.0.1")
+    } else {
+        match read_body(&mut request) {
+            Ok(body) => handle(jobs, &method, &url, body),
+            Err(reply) => reply,
+        }
+    };
+    eprintln!("{method} {url} {}", reply.status);
+
+    // the body is complete already, a plain Content-Length suits every client
+    let mut response = Response::from_data(reply.body)
+        .with_status_code(reply.status)
+        .with_chunked_threshold(usize::MAX);
+    let mut headers = vec![("Content-Type", reply.content_type.to_string())];
+    if let Some(file_name) = reply.file_name {
+        headers.push((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", file_name.replace('"', "")),
+        ));
+    }
+    for (name, value) in headers {
+        if let Ok(header) = Header::from_bytes(name, value) {
+            response = response.with_header(header);
+        }
+    }
+    // the client may be gone already, nothing left to tell it
+    let _ = request.respond(response);
+}
+
+fn read_body(request: &mut Request) -> Result<Vec<u8>, Reply> {
+    if *request.method() != Method::Post {
+        return Ok(Vec::new());
+    }
+    let too_large = || Reply::error(413, format!("uploads are limited to {MAX_UPLOAD} bytes"));
+    if request
+        .body_length()
+        .is_some_and(|length| length > MAX_UPLOAD)
+    {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_UPLOAD as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| Reply::error(400, e.to_string()))?;
+    if body.len() > MAX_UPLOAD {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn query_pairs(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_and_queries() {
+        let query = query_pairs("name=My%20Part+1.stp&format=glb&local");
+        assert_eq!(query["name"], "My Part 1.stp");
+        assert_eq!(query["format"], "glb");
+        assert_eq!(query["local"], "");
+
+        let jobs = Arc::new(Jobs::default());
+        let reply = handle(&jobs, &Method::Post, "/convert?format=step", Vec::new());
+        assert_eq!(reply.status, 400);
+        assert_eq!(handle(&jobs, &Method::Get, "/info", Vec::new()).status, 404);
+        assert_eq!(
+            handle(&jobs, &Method::Get, "/jobs/1", Vec::new()).status,
+            404
+        );
+        let reply = handle(&jobs, &Method::Post, "/info", b"not a step file".to_vec());
+        assert_eq!(reply.status, 422);
+        assert!(String::from_utf8_lossy(&reply.body).contains("\"error\""));
+    }
+
+    #[test]
+    fn only_loopback_hosts() {
+        assert!(host_allowed(Some("127.0.0.1:8707"), 8707));
+        assert!(host_allowed(Some("LocalHost:8707"), 8707));
+        assert!(!host_allowed(Some("localhost:8708"), 8707));
+        assert!(!host_allowed(Some("localhost"), 8707));
+        assert!(!host_allowed(Some("rebound.example:8707"), 8707));
+        assert!(!host_allowed(None, 8707));
+    }
+
+    #[test]
+    fn running_jobs_are_not_deleted() {
+        let jobs = Arc::new(Jobs::default());
+        let running = Job {
+            task: Task::Stats,
+            result: None,
+            finished: None,
+        };
+        jobs.lock().insert("running".to_string(), running);
+        jobs.lock()
+            .insert("done".to_string(), finished_job(Instant::now()));
+
+        let delete = |id: &str| handle(&jobs, &Method::Delete, &format!("/jobs/{id}"), Vec::new());
+        assert_eq!(delete("running").status, 409);
+        assert_eq!(delete("done").status, 200);
+        assert_eq!(delete("done").status, 404);
+        assert!(jobs.lock().contains_key("running"));
+    }
+
+    fn finished_job(at: Instant) -> Job {
+        Job {
+            task: Task::Info,
+            result: Some(Reply::error(422, "failed")),
+            finished: Some(at),
+        }
+    }
+
+    #[test]
+    fn finished_jobs_expire() {
+        let start = Instant::now();
+        let now = start + JOB_TTL + Duration::from_secs(100);
+        let mut jobs = HashMap::new();
+        jobs.insert("old".to_string(), finished_job(start));
+        jobs.insert(
+            "running".to_string(),
+            Job {
+                task: Task::Stats,
+                result: None,
+                finished: None,
+            },
+        );
+        // "0" finished last
+        for age in 0..=MAX_FINISHED_JOBS as u64 {
+            jobs.insert(
+                format!("{age}"),
+                finished_job(now - Duration::from_secs(age)),
+            );
+        }
+        expire(&mut jobs, now);
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 1);
+        assert!(jobs.contains_key("running") && jobs.contains_key("0"));
+        // the oldest one beyond the count went
+        assert!(!jobs.contains_key("old"));
+        assert!(!jobs.contains_key(&MAX_FINISHED_JOBS.to_string()));
+
+        let ids = [job_id().unwrap(), job_id().unwrap()];
+        assert_eq!(ids[0].len(), 32);
+        assert_ne!(ids[0], ids[1]);
+    }
+}
@@ -0,0 +1,221 @@
+// Runs `stepviz serve` on a free loopback port and talks HTTP to it
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+struct Server {
+    child: Child,
+    address: SocketAddr,
+}
+
+impl Server {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_stepviz"))
+            .args(["serve", "--port", "0"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let address = line
+            .trim()
+            .strip_prefix("listening on http://")
+            .unwrap_or_else(|| panic!("unexpected first line {line:?}"))
+            .parse()
+            .unwrap();
+        Self { child, address }
+    }
+
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> Reply {
+        self.request_to(&self.address.to_string(), method, path, body)
+    }
+
+    // `host` goes into the Host header, the connection is always to the server
+    fn request_to(&self, host: &str, method: &str, path: &str, body: &[u8]) -> Reply {
+        let mut stream = TcpStream::connect(self.address).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {host}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).unwrap();
+
+        let split = raw.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&raw[..split]).into_owned();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        Reply {
+            status,
+            head,
+            body: raw[split + 4..].to_vec(),
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct Reply {
+    status: u16,
+    head: String,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then_some(value.trim())
+        })
+    }
+}
+
+fn example(name: &str) -> Vec<u8> {
+    std::fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../examples")
+            .join(name),
+    )
+    .unwrap()
+}
+
+#[test]
+fn every_example_gets_an_answer() {
+    let server = Server::start();
+    assert!(server.address.ip().is_loopback());
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples");
+    let mut answered = 0;
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "stp") {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let body = std::fs::read(&path).unwrap();
+        for task in ["info", "stats"] {
+            let reply = server.request("POST", &format!("/{task}?name={name}"), &body);
+            let json = reply.json();
+            assert_eq!(reply.status, 200, "{task} {name}: {json}");
+            assert_eq!(json["file"], name.as_str());
+            match task {
+                "info" => assert!(json["entity_count"].as_u64().unwrap() > 0),
+                _ => assert!(json["volume"].as_f64().unwrap() > 0.0, "{name}: {json}"),
+            }
+            answered += 1;
+        }
+    }
+    // info and stats for each of the six examples
+    assert_eq!(answered, 12);
+    assert_eq!(server.request("GET", "/health", b"").status, 200);
+}
+
+#[test]
+fn mass_properties_and_conversions() {
+    let server = Server::start();
+    let assembly = example("as1-tc-214.stp");
+    let stats = server.request("POST", "/stats?name=as1-tc-214.stp", &assembly);
+    assert_eq!(stats.status, 200);
+    let stats = stats.json();
+    assert_eq!(stats["validation_passed"], true);
+    assert_eq!(stats["validation"].as_array().unwrap().len(), 27);
+
+    let part = example("Part1.stp");
+    let stl = server.request("POST", "/convert?format=stl&name=Part1.stp", &part);
+    assert_eq!(stl.status, 200);
+    assert_eq!(stl.header("Content-Type"), Some("model/stl"));
+    assert_eq!(
+        stl.header("Content-Disposition"),
+        Some("attachment; filename=\"Part1.stl\"")
+    );
+    let triangles = u32::from_le_bytes(stl.body[80..84].try_into().unwrap()) as usize;
+    assert_eq!(stl.body.len(), 84 + 50 * triangles);
+
+    let glb = server.request("POST", "/convert?format=glb", &assembly);
+    assert_eq!(glb.status, 200);
+    assert_eq!(&glb.body[..4], b"glTF");
+
+    // OBJ needs its material file next to it
+    let obj = server.request("POST", "/convert?format=obj&name=Part1.stp", &part);
+    assert_eq!(obj.header("Content-Type"), Some("application/zip"));
+    assert_eq!(&obj.body[..2], b"PK");
+
+    let unknown = server.request("POST", "/convert?format=iges", &part);
+    assert_eq!(unknown.status, 400);
+    assert_eq!(unknown.json()["error"], "unknown format 'iges'");
+    let garbage = server.request("POST", "/info", b"garbage");
+    assert_eq!(garbage.status, 422);
+    assert_eq!(garbage.json()["kind"], "syntax");
+}
+
+#[test]
+fn jobs_run_in_the_background() {
+    let server = Server::start();
+    let assembly = example("as1-tc-214.stp");
+    let started = server.request("POST", "/jobs/stats?name=as1-tc-214.stp", &assembly);
+    assert_eq!(started.status, 202);
+    let id = started.json()["id"].as_str().unwrap().to_string();
+
+    let deadline = Instant::now() + Duration::from_secs(120);
+    let status = loop {
+        let status = server.request("GET", &format!("/jobs/{id}"), b"").json();
+        if status["status"] != "running" || Instant::now() > deadline {
+            break status;
+        }
+        assert_eq!(
+            server
+                .request("GET", &format!("/jobs/{id}/result"), b"")
+                .status,
+            409
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert_eq!(status["status"], "done", "{status}");
+    assert_eq!(status["task"], "stats");
+
+    let result = server.request("GET", &format!("/jobs/{id}/result"), b"");
+    assert_eq!(result.status, 200);
+    assert_eq!(result.json()["validation_passed"], true);
+
+    assert_eq!(
+        server.request("DELETE", &format!("/jobs/{id}"), b"").status,
+        200
+    );
+    assert_eq!(
+        server.request("GET", &format!("/jobs/{id}"), b"").status,
+        404
+    );
+}
+
+#[test]
+fn other_hosts_are_refused() {
+    let server = Server::start();
+    let port = server.address.port();
+    for host in [format!("localhost:{port}"), format!("127.0.0.1:{port}")] {
+        assert_eq!(server.request_to(&host, "GET", "/health", b"").status, 200);
+    }
+    // a rebound domain, a foreign port
+    for host in [
+        format!("rebound.example:{port}"),
+        format!("localhost:{}", port + 1),
+    ] {
+        let reply = server.request_to(&host, "POST", "/info", &example("Part1.stp"));
+        assert_eq!(reply.status, 403, "{host}");
+        assert!(reply.json()["error"].is_string());
+    }
+}
@@ -0,0 +1,701 @@
+use crate::trace_span;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::entities::{EntityIndex, param_reals, param_ref, param_refs, param_str};
+use super::error::StepVizError;
+use super::math::{cross3_f64, dot3_f64, try_normalize3_f64};
+use super::types::ProductNode;
+
+pub type Transform = [f64; 16];
+
+pub const IDENTITY: Transform = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShellPlacement {
+    pub shell_id: u64,
+    pub transform: [f32; 16],
+    // product tree node owning this instance, None when outside the product structure
+    pub node_id: Option<usize>,
+}
+
+pub struct AssemblyStructure {
+    pub placements: HashMap<u64, Vec<ShellPlacement>>,
+    pub product_tree: Vec<ProductNode>,
+    // product definition and world transform of every node, indexed by node id
+    pub node_pd: Vec<u64>,
+    pub node_transforms: Vec<Transform>,
+    // usages skipped because they would contain their own ancestor
+    pub warnings: Vec<StepVizError>,
+}
+
+// Resolves the product tree and where every shell instance lands in world space.
+//
+// Product structure: root PRODUCT_DEFINITIONs are walked down through
+// NEXT_ASSEMBLY_USAGE_OCCURRENCE, each occurrence placed by the
+// ITEM_DEFINED_TRANSFORMATION of the representation relationship its
+// CONTEXT_DEPENDENT_SHAPE_REPRESENTATION points at. A shell used N times in
+// the assembly tree yields N placements.
+// Shells not reachable through the product structure keep an identity placement.
+// A usage that would place a product inside itself (malformed files with cyclic
+// NAUO chains) is skipped with a warning.
+pub fn resolve_assembly(index: &EntityIndex, shell_ids: &[u64]) -> AssemblyStructure {
+    trace_span!("resolve_assembly");
+    let resolver = AssemblyResolver::new(index, shell_ids);
+    let mut walk = AssemblyWalk::default();
+
+    let mut product_tree: Vec<ProductNode> = resolver
+        .roots()
+        .into_iter()
+        .map(|root| resolver.walk(root, None, IDENTITY, &mut Vec::new(), &mut walk))
+        .collect();
+    for node in &mut product_tree {
+        set_instance_counts(node, &walk);
+    }
+
+    let mut placements = walk.placements;
+    for &shell_id in shell_ids {
+        placements.entry(shell_id).or_insert_with(|| {
+            vec![ShellPlacement {
+                shell_id,
+                transform: to_f32(&IDENTITY),
+                node_id: None,
+            }]
+        });
+    }
+    AssemblyStructure {
+        placements,
+        product_tree,
+        node_pd: walk.node_pd,
+        node_transforms: walk.node_transforms,
+        warnings: walk.warnings,
+    }
+}
+
+#[derive(Default)]
+struct AssemblyWalk {
+    placements: HashMap<u64, Vec<ShellPlacement>>,
+    next_node_id: usize,
+    // product definition of every emitted node, indexed by node id
+    node_pd: Vec<u64>,
+    node_transforms: Vec<Transform>,
+    pd_instances: HashMap<u64, usize>,
+    warnings: Vec<StepVizError>,
+}
+
+fn set_instance_counts(node: &mut ProductNode, walk: &AssemblyWalk) {
+    node.instance_count = walk.pd_instances[&walk.node_pd[node.id]];
+    for child in &mut node.children {
+        set_instance_counts(child, walk);
+    }
+}
+
+struct Occurrence {
+    usage: u64,
+    child: u64,
+    transform: Transform,
+}
+
+struct AssemblyResolver<'i, 'a> {
+    index: &'i EntityIndex<'a>,
+    shells: HashSet<u64>,
+    // product definition -> representations describing its shape
+    reps_of_pd: HashMap<u64, BTreeSet<u64>>,
+    // parent product definition -> placed child occurrences
+    children: HashMap<u64, Vec<Occurrence>>,
+    product_definitions: Vec<u64>,
+}
+
+impl<'i, 'a> AssemblyResolver<'i, 'a> {
+    fn new(index: &'i EntityIndex<'a>, shell_ids: &[u64]) -> Self {
+        let mut resolver = Self {
+            index,
+            shells: shell_ids.iter().copied().collect(),
+            reps_of_pd: HashMap::new(),
+            children: HashMap::new(),
+            product_definitions: product_definition_ids(index),
+        };
+        resolver.collect_shape_representations();
+        resolver.collect_occurrences();
+        resolver
+    }
+
+    fn collect_shape_representations(&mut self) {
+        // representation relationships used by a CDSR are placements, not
+        // alternative descriptions of the same shape
+        let placement_rels: HashSet<u64> = self
+            .index
+            .ids_of("CONTEXT_DEPENDENT_SHAPE_REPRESENTATION")
+            .iter()
+            .filter_map(|&id| {
+                let params = self
+                    .index
+                    .params(id, "CONTEXT_DEPENDENT_SHAPE_REPRESENTATION")?;
+                param_ref(params.first()?)
+            })
+            .collect();
+
+        let mut same_shape: HashMap<u64, Vec<u64>> = HashMap::new();
+        for &rel in self.index.ids_of("REPRESENTATION_RELATIONSHIP") {
+            if placement_rels.contains(&rel)
+                || self
+                    .index
+                    .has_record(rel, "REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION")
+            {
+                continue;
+            }
+            if let Some((rep_1, rep_2)) = self.relationship_reps(rel) {
+                same_shape.entry(rep_1).or_default().push(rep_2);
+                same_shape.entry(rep_2).or_default().push(rep_1);
+            }
+        }
+        for &rel in self.index.ids_of("SHAPE_REPRESENTATION_RELATIONSHIP") {
+            if self.index.records(rel).len() > 1 {
+                continue; // complex form handled above
+            }
+            if let Some(params) = self.index.params(rel, "SHAPE_REPRESENTATION_RELATIONSHIP")
+                && let (Some(rep_1), Some(rep_2)) = (
+                    params.get(2).and_then(param_ref),
+                    params.get(3).and_then(param_ref),
+                )
+            {
+                same_shape.entry(rep_1).or_default().push(rep_2);
+                same_shape.entry(rep_2).or_default().push(rep_1);
+            }
+        }
+
+        for &sdr in self.index.ids_of("SHAPE_DEFINITION_REPRESENTATION") {
+            let Some(params) = self.index.params(sdr, "SHAPE_DEFINITION_REPRESENTATION") else {
+                continue;
+            };
+            let (Some(pds), Some(rep)) = (
+                params.first().and_then(param_ref),
+                params.get(1).and_then(param_ref),
+            ) else {
+                continue;
+            };
+            let Some(pd) = self
+                .index
+                .params(pds, "PRODUCT_DEFINITION_SHAPE")
+                .and_then(|p| p.get(2))
+                .and_then(param_ref)
+            else {
+                continue;
+            };
+            if !self.product_definitions.contains(&pd) {
+                continue;
+            }
+
+            let reps = self.reps_of_pd.entry(pd).or_default();
+            let mut stack = vec![rep];
+            while let Some(rep) = stack.pop() {
+                if reps.insert(rep)
+                    && let Some(linked) = same_shape.get(&rep)
+                {
+                    stack.extend(linked.iter().copied());
+                }
+            }
+        }
+    }
+
+    fn collect_occurrences(&mut self) {
+        // PRODUCT_DEFINITION_SHAPE of an occurrence -> CDSR relationship
+        let mut rel_of_pds: HashMap<u64, u64> = HashMap::new();
+        for &cdsr in self.index.ids_of("CONTEXT_DEPENDENT_SHAPE_REPRESENTATION") {
+            if let Some(params) = self
+                .index
+                .params(cdsr, "CONTEXT_DEPENDENT_SHAPE_REPRESENTATION")
+                && let (Some(rel), Some(pds)) = (
+                    params.first().and_then(param_ref),
+                    params.get(1).and_then(param_ref),
+                )
+            {
+                rel_of_pds.insert(pds, rel);
+            }
+        }
+        let mut pds_of_usage: HashMap<u64, u64> = HashMap::new();
+        for &pds in self.index.ids_of("PRODUCT_DEFINITION_SHAPE") {
+            if let Some(target) = self
+                .index
+                .params(pds, "PRODUCT_DEFINITION_SHAPE")
+                .and_then(|p| p.get(2))
+                .and_then(param_ref)
+            {
+                pds_of_usage.insert(target, pds);
+            }
+        }
+
+        for &nauo in self.index.ids_of("NEXT_ASSEMBLY_USAGE_OCCURRENCE") {
+            let Some(params) = self.index.params(nauo, "NEXT_ASSEMBLY_USAGE_OCCURRENCE") else {
+                continue;
+            };
+            let (Some(parent), Some(child)) = (
+                params.get(3).and_then(param_ref),
+                params.get(4).and_then(param_ref),
+            ) else {
+                continue;
+            };
+            let transform = pds_of_usage
+                .get(&nauo)
+                .and_then(|pds| rel_of_pds.get(pds))
+                .and_then(|&rel| self.relationship_transform(rel, child))
+                .unwrap_or(IDENTITY);
+            self.children.entry(parent).or_default().push(Occurrence {
+                usage: nauo,
+                child,
+                transform,
+            });
+        }
+    }
+
+    fn relationship_reps(&self, rel: u64) -> Option<(u64, u64)> {
+        let params = self.index.params(rel, "REPRESENTATION_RELATIONSHIP")?;
+        Some((
+            params.get(2).and_then(param_ref)?,
+            params.get(3).and_then(param_ref)?,
+        ))
+    }
+
+    // child -> parent transform carried by a (REPRESENTATION_RELATIONSHIP
+    // REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION ...) complex instance
+    fn relationship_transform(&self, rel: u64, child_pd: u64) -> Option<Transform> {
+        let (rep_1, rep_2) = self.relationship_reps(rel)?;
+        let idt = self
+            .index
+            .params(rel, "REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION")?
+            .first()
+            .and_then(param_ref)?;
+        let params = self.index.params(idt, "ITEM_DEFINED_TRANSFORMATION")?;
+        let item_1 = self.placement(params.get(2).and_then(param_ref)?);
+        let item_2 = self.placement(params.get(3).and_then(param_ref)?);
+
+        // rep_1/transform_item_1 should live in the child, some exporters swap them
+        let child_reps = self.reps_of_pd.get(&child_pd);
+        let swapped =
+            child_reps.is_some_and(|reps| !reps.contains(&rep_1) && reps.contains(&rep_2));
+        Some(if swapped {
+            multiply(&item_1, &rigid_inverse(&item_2))
+        } else {
+            multiply(&item_2, &rigid_inverse(&item_1))
+        })
+    }
+
+    // AXIS2_PLACEMENT_3D(name, location, axis, ref_direction) as a column-major matrix
+    fn placement(&self, id: u64) -> Transform {
+        let Some(params) = self.index.params(id, "AXIS2_PLACEMENT_3D") else {
+            return IDENTITY;
+        };
+        let location = params
+            .get(1)
+            .and_then(param_ref)
+            .and_then(|p| self.coordinates(p, "CARTESIAN_POINT"))
+            .unwrap_or([0.0; 3]);
+        let z = params
+            .get(2)
+            .and_then(param_ref)
+            .and_then(|d| self.coordinates(d, "DIRECTION"))
+            .and_then(try_normalize3_f64)
+            .unwrap_or([0.0, 0.0, 1.0]);
+        let ref_dir = params
+            .get(3)
+            .and_then(param_ref)
+            .and_then(|d| self.coordinates(d, "DIRECTION"))
+            .unwrap_or([1.0, 0.0, 0.0]);
+
+        // x is ref_direction projected onto the plane normal to z
+        let d = dot3_f64(ref_dir, z);
+        let x = try_normalize3_f64([
+            ref_dir[0] - d * z[0],
+            ref_dir[1] - d * z[1],
+            ref_dir[2] - d * z[2],
+        ])
+        .or_else(|| {
+            let fallback = if z[0].abs() < 0.9 {
+                [1.0, 0.0, 0.0]
+            } else {
+                [0.0, 1.0, 0.0]
+            };
+            try_normalize3_f64(cross3_f64(cross3_f64(z, fallback), z))
+        })
+        .unwrap_or([1.0, 0.0, 0.0]);
+        let y = cross3_f64(z, x);
+
+        [
+            x[0],
+            x[1],
+            x[2],
+            0.0,
+            y[0],
+            y[1],
+            y[2],
+            0.0,
+            z[0],
+            z[1],
+            z[2],
+            0.0,
+            location[0],
+            location[1],
+            location[2],
+            1.0,
+        ]
+    }
+
+    fn coordinates(&self, id: u64, name: &str) -> Option<[f64; 3]> {
+        let values = param_reals(self.index.params(id, name)?.get(1)?);
+        Some([
+            *values.first()?,
+            values.get(1).copied().unwrap_or(0.0),
+            values.get(2).copied().unwrap_or(0.0),
+        ])
+    }
+
+    fn roots(&self) -> Vec<u64> {
+        let used: HashSet<u64> = self
+            .children
+            .values()
+            .flatten()
+            .map(|occurrence| occurrence.child)
+            .collect();
+        self.product_definitions
+            .iter()
+            .copied()
+            .filter(|pd| !used.contains(pd))
+            .collect()
+    }
+
+    // shells reachable from the items of a product definition's representations
+    fn shells_of(&self, pd: u64) -> BTreeSet<u64> {
+        let mut shells = BTreeSet::new();
+        let Some(reps) = self.reps_of_pd.get(&pd) else {
+            return shells;
+        };
+        for &rep in reps {
+            let Some((_, params)) = self.index.simple_params(rep) else {
+                continue;
+            };
+            for item in params.get(1).map(param_refs).unwrap_or_default() {
+                if self.shells.contains(&item) {
+                    shells.insert(item);
+                    continue;
+                }
+                // MANIFOLD_SOLID_BREP, BREP_WITH_VOIDS, SHELL_BASED_SURFACE_MODEL ...
+                if let Some((_, item_params)) = self.index.simple_params(item) {
+                    for param in item_params {
+                        let refs = param_ref(param)
+                            .map(|id| vec![id])
+                            .unwrap_or_else(|| param_refs(param));
+                        shells.extend(refs.into_iter().filter(|id| self.shells.contains(id)));
+                    }
+                }
+            }
+        }
+        shells
+    }
+
+    fn walk(
+        &self,
+        pd: u64,
+        usage: Option<u64>,
+        world: Transform,
+        // product definitions from the root down to `pd`'s parent
+        path: &mut Vec<u64>,
+        walk: &mut AssemblyWalk,
+    ) -> ProductNode {
+        let id = walk.next_node_id;
+        walk.next_node_id += 1;
+        walk.node_pd.push(pd);
+        walk.node_transforms.push(world);
+        *walk.pd_instances.entry(pd).or_default() += 1;
+
+        for shell_id in self.shells_of(pd) {
+            walk.placements
+                .entry(shell_id)
+                .or_default()
+                .push(ShellPlacement {
+                    shell_id,
+                    transform: to_f32(&world),
+                    node_id: Some(id),
+                });
+        }
+
+        let mut children = Vec::new();
+        path.push(pd);
+        for occurrence in self.children.get(&pd).into_iter().flatten() {
+            if path.contains(&occurrence.child) {
+                walk.warnings.push(StepVizError::geometry(
+                    Some(occurrence.usage),
+                    format!(
+                        "assembly usage places {} inside itself, skipped",
+                        self.product_name(occurrence.child)
+                    ),
+                ));
+                continue;
+            }
+            let child_world = multiply(&world, &occurrence.transform);
+            children.push(self.walk(
+                occurrence.child,
+                Some(occurrence.usage),
+                child_world,
+                path,
+                walk,
+            ));
+        }
+        path.pop();
+
+        ProductNode {
+            id,
+            name: self.product_name(pd),
+            instance_name: usage.and_then(|nauo| self.usage_name(nauo)),
+            instance_count: 0,
+            children,
+        }
+    }
+
+    // PRODUCT_DEFINITION -> PRODUCT_DEFINITION_FORMATION -> PRODUCT(id, name, ...)
+    fn product_name(&self, pd: u64) -> String {
+        let product = self
+            .index
+            .simple_params(pd)
+            .and_then(|(_, params)| params.get(2).and_then(param_ref))
+            .and_then(|formation| self.index.simple_params(formation))
+            .and_then(|(_, params)| params.get(2).and_then(param_ref))
+            .and_then(|product| self.index.params(product, "PRODUCT"));
+        product
+            .and_then(|params| {
+                [params.get(1), params.first()]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(param_str)
+                    .find(|name| !name.trim().is_empty())
+            })
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("#{pd}"))
+    }
+
+    // NEXT_ASSEMBLY_USAGE_OCCURRENCE(id, name, description, ...)
+    fn usage_name(&self, nauo: u64) -> Option<String> {
+        let params = self.index.params(nauo, "NEXT_ASSEMBLY_USAGE_OCCURRENCE")?;
+        [params.get(1), params.first()]
+            .into_iter()
+            .flatten()
+            .filter_map(param_str)
+            .find(|name| !name.trim().is_empty())
+            .map(str::to_string)
+    }
+}
+
+fn product_definition_ids(index: &EntityIndex) -> Vec<u64> {
+    let mut ids: Vec<u64> = [
+        "PRODUCT_DEFINITION",
+        "PRODUCT_DEFINITION_WITH_ASSOCIATED_DOCUMENTS",
+    ]
+    .iter()
+    .flat_map(|name| index.ids_of(name).iter().copied())
+    .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+// column-major a * b, same layout as math::multiply_matrices
+pub fn multiply(a: &Transform, b: &Transform) -> Transform {
+    let mut result = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            result[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    result
+}
+
+// inverse of a rotation + translation matrix (placements are orthonormal)
+fn rigid_inverse(m: &Transform) -> Transform {
+    let t = [m[12], m[13], m[14]];
+    let mut inv = IDENTITY;
+    for row in 0..3 {
+        for col in 0..3 {
+            inv[col * 4 + row] = m[row * 4 + col];
+        }
+    }
+    for row in 0..3 {
+        inv[12 + row] = -(inv[row] * t[0] + inv[4 + row] * t[1] + inv[8 + row] * t[2]);
+    }
+    inv
+}
+
+pub fn to_f32(m: &Transform) -> [f32; 16] {
+    m.map(|v| v as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::step_file;
+    use crate::math::transform_point;
+
+    // product definition #<n>0 named `name`, with shape representation #<n>2
+    // holding `items`
+    fn product(n: u64, name: &str, items: &str) -> String {
+        format!(
+            "#{n}1=PRODUCT('{name}','{name}','',(#9));\n\
+             #{n}3=PRODUCT_DEFINITION_FORMATION('','',#{n}1);\n\
+             #{n}0=PRODUCT_DEFINITION('','',#{n}3,$);\n\
+             #{n}4=PRODUCT_DEFINITION_SHAPE('','',#{n}0);\n\
+             #{n}2=SHAPE_REPRESENTATION('',({items}),$);\n\
+             #{n}5=SHAPE_DEFINITION_REPRESENTATION(#{n}4,#{n}2);\n"
+        )
+    }
+
+    // occurrence #<n>0 of `child` in `parent`, placed by the transformation from
+    // `from` (in the child) to `to` (in the parent)
+    fn usage(n: u64, parent: u64, child: u64, from: u64, to: u64, swapped: bool) -> String {
+        let (parent_rep, child_rep) = (parent + 2, child + 2);
+        let (rep_1, rep_2, item_1, item_2) = if swapped {
+            (parent_rep, child_rep, to, from)
+        } else {
+            (child_rep, parent_rep, from, to)
+        };
+        format!(
+            "#{n}0=NEXT_ASSEMBLY_USAGE_OCCURRENCE('u{n}','usage {n}','',#{parent},#{child},$);\n\
+             #{n}1=PRODUCT_DEFINITION_SHAPE('','',#{n}0);\n\
+             #{n}2=CONTEXT_DEPENDENT_SHAPE_REPRESENTATION(#{n}3,#{n}1);\n\
+             #{n}3=(REPRESENTATION_RELATIONSHIP('','',#{rep_1},#{rep_2})\
+             REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION(#{n}4)\
+             SHAPE_REPRESENTATION_RELATIONSHIP());\n\
+             #{n}4=ITEM_DEFINED_TRANSFORMATION('','',#{item_1},#{item_2});\n"
+        )
+    }
+
+    // #900 is the identity, #901 a move by (10, 0, 0), #902 a quarter turn
+    // about z and a move by (0, 5, 0)
+    const PLACEMENTS: &str = "#910=CARTESIAN_POINT('',(0.,0.,0.));\n\
+        #911=CARTESIAN_POINT('',(10.,0.,0.));\n\
+        #912=CARTESIAN_POINT('',(0.,5.,0.));\n\
+        #920=DIRECTION('',(0.,0.,1.));\n\
+        #921=DIRECTION('',(1.,0.,0.));\n\
+        #922=DIRECTION('',(0.,1.,0.));\n\
+        #900=AXIS2_PLACEMENT_3D('',#910,#920,#921);\n\
+        #901=AXIS2_PLACEMENT_3D('',#911,#920,#921);\n\
+        #902=AXIS2_PLACEMENT_3D('',#912,#920,#922);\n\
+        #100=CLOSED_SHELL('',(#9));\n";
+
+    // assembly #10 > subassembly #20 > part #30 holding shell #100
+    fn nested(swapped: bool) -> String {
+        step_file(&[&[
+            product(1, "assembly", "#900"),
+            product(2, "subassembly", "#900"),
+            product(3, "part", "#100,#900"),
+            usage(5, 10, 20, 900, 901, false),
+            usage(6, 20, 30, 900, 902, swapped),
+            PLACEMENTS.to_string(),
+        ]
+        .concat()])
+    }
+
+    fn resolve(text: &str) -> AssemblyStructure {
+        let exchange = ruststep::parser::parse(text).unwrap();
+        resolve_assembly(&EntityIndex::from_data_section(&exchange.data[0]), &[100])
+    }
+
+    fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+        let close = (0..3).all(|i| (actual[i] - expected[i]).abs() < 1e-5);
+        assert!(close, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn nested_placements_are_composed() {
+        let assembly = resolve(&nested(false));
+        let [root] = &assembly.product_tree[..] else {
+            panic!("{:?}", assembly.product_tree);
+        };
+        assert_eq!(root.name, "assembly");
+        let sub = &root.children[0];
+        assert_eq!(sub.name, "subassembly");
+        assert_eq!(sub.instance_name.as_deref(), Some("usage 5"));
+        let part = &sub.children[0];
+        assert_eq!((part.name.as_str(), part.instance_count), ("part", 1));
+
+        let [placement] = &assembly.placements[&100][..] else {
+            panic!("{:?}", assembly.placements);
+        };
+        assert_eq!(placement.node_id, Some(part.id));
+        // turned onto +y, moved by (0, 5, 0) in the subassembly, then by (10, 0, 0)
+        assert_near(
+            transform_point(&placement.transform, [0.0; 3]),
+            [10.0, 5.0, 0.0],
+        );
+        assert_near(
+            transform_point(&placement.transform, [1.0, 0.0, 0.0]),
+            [10.0, 6.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn swapped_transformation_items_give_the_same_placement() {
+        let expected = resolve(&nested(false)).placements[&100][0].transform;
+        let swapped = resolve(&nested(true)).placements[&100][0].transform;
+        for (actual, expected) in swapped.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-5, "{swapped:?}");
+        }
+    }
+
+    #[test]
+    fn rigid_inverse_undoes_placements() {
+        // quarter turn about z, then a move
+        let mut m = IDENTITY;
+        m[0..2].copy_from_slice(&[0.0, 1.0]);
+        m[4..6].copy_from_slice(&[-1.0, 0.0]);
+        m[12..15].copy_from_slice(&[3.0, -2.0, 7.0]);
+        for product in [
+            multiply(&m, &rigid_inverse(&m)),
+            multiply(&rigid_inverse(&m), &m),
+        ] {
+            for (actual, expected) in product.iter().zip(IDENTITY) {
+                assert!((actual - expected).abs() < 1e-12, "{product:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn cyclic_usages_are_skipped() {
+        // #20 and #30 contain each other below the root #10, and #20 holds
+        // #30 a second time, fanning out the cycle
+        let text = step_file(&[&[
+            product(1, "assembly", "#900"),
+            product(2, "a", "#900"),
+            product(3, "b", "#100,#900"),
+            usage(5, 10, 20, 900, 900, false),
+            usage(6, 20, 30, 900, 900, false),
+            usage(7, 30, 20, 900, 900, false),
+            usage(8, 20, 30, 900, 901, false),
+            PLACEMENTS.to_string(),
+        ]
+        .concat()]);
+        let assembly = resolve(&text);
+        let [root] = &assembly.product_tree[..] else {
+            panic!("{:?}", assembly.product_tree);
+        };
+        let a = &root.children[0];
+        assert_eq!(a.name, "a");
+        let names: Vec<(&str, usize)> = a
+            .children
+            .iter()
+            .map(|b| (b.name.as_str(), b.children.len()))
+            .collect();
+        assert_eq!(names, [("b", 0), ("b", 0)]);
+        assert_eq!(assembly.node_pd, [10, 20, 30, 30]);
+        assert_eq!(assembly.placements[&100].len(), 2);
+
+        // usage #70 is met below both instances of b
+        let skipped: Vec<Option<u64>> = assembly
+            .warnings
+            .iter()
+            .map(|warning| match warning {
+                StepVizError::Geometry { entity, .. } => *entity,
+                other => panic!("{other:?}"),
+            })
+            .collect();
+        assert_eq!(skipped, [Some(70), Some(70)]);
+    }
+}
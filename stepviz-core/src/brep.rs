@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use truck_geometry::prelude::*;
+use truck_stepio::r#in::alias::{Curve3D, ElementarySurface, Surface, SweptCurve};
+
+// Analytic surface a tessellated face comes from, in part coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FaceSurface {
+    // `normal` points out of the material
+    Plane {
+        origin: [f64; 3],
+        normal: [f64; 3],
+    },
+    // `axis` is a unit vector through `origin`
+    Cylinder {
+        origin: [f64; 3],
+        axis: [f64; 3],
+        radius: f64,
+    },
+    Other,
+}
+
+// `indices[first_index..first_index + index_count]` of a part were tessellated
+// from one B-rep face
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PartFace {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub surface: FaceSurface,
+}
+
+impl PartFace {
+    pub fn contains_triangle(&self, triangle: usize) -> bool {
+        let first = triangle * 3;
+        first >= self.first_index as usize && first < (self.first_index + self.index_count) as usize
+    }
+}
+
+// Planes and cylinders of a STEP face, `outward` is any normal of the face
+// mesh (after orientation was applied) and decides which way planes face
+pub fn face_surface(surface: &Surface, outward: [f64; 3]) -> FaceSurface {
+    match surface {
+        Surface::ElementarySurface(surface) => match surface.as_ref() {
+            ElementarySurface::Plane(plane) => {
+                let mut normal = plane.normal();
+                if normal.dot(Vector3::from(outward)) < 0.0 {
+                    normal = -normal;
+                }
+                FaceSurface::Plane {
+                    origin: plane.origin().into(),
+                    normal: normal.into(),
+                }
+            }
+            // cones share the representation, `revolved_line` tells them apart
+            ElementarySurface::CylindricalSurface(cylinder) => {
+                let revolution = cylinder.entity();
+                revolved_line(
+                    revolution.origin(),
+                    revolution.axis(),
+                    revolution.entity_curve(),
+                    cylinder.transform(),
+                )
+            }
+            _ => FaceSurface::Other,
+        },
+        Surface::SweptCurve(swept) => match swept.as_ref() {
+            SweptCurve::RevolutedCurve(revolution) => match revolution.entity().entity_curve() {
+                Curve3D::Line(line) => revolved_line(
+                    revolution.entity().origin(),
+                    revolution.entity().axis(),
+                    line,
+                    revolution.transform(),
+                ),
+                _ => FaceSurface::Other,
+            },
+            _ => FaceSurface::Other,
+        },
+        _ => FaceSurface::Other,
+    }
+}
+
+// a line revolved around an axis is a cylinder when both are parallel
+fn revolved_line(
+    origin: Point3,
+    axis: Vector3,
+    line: &Line<Point3>,
+    transform: &Matrix4,
+) -> FaceSurface {
+    let origin = transform.transform_point(origin);
+    let axis = transform.transform_vector(axis);
+    let start = transform.transform_point(line.0);
+    let direction = transform.transform_point(line.1) - start;
+    if axis.magnitude() < TOLERANCE || direction.magnitude() < TOLERANCE {
+        return FaceSurface::Other;
+    }
+    let axis = axis.normalize();
+    if direction.normalize().cross(axis).magnitude() > 1e-6 {
+        return FaceSurface::Other;
+    }
+    let radius = (start - origin).cross(axis).magnitude();
+    if radius < TOLERANCE {
+        return FaceSurface::Other;
+    }
+    FaceSurface::Cylinder {
+        origin: origin.into(),
+        axis: axis.into(),
+        radius,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use truck_stepio::r#in::alias::CylindricalSurface;
+
+    fn assert_near(a: [f64; 3], b: [f64; 3]) {
+        for axis in 0..3 {
+            assert!((a[axis] - b[axis]).abs() < 1e-9, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn planes_face_outward() {
+        let plane = Plane::new(
+            Point3::new(0.0, 0.0, 2.0),
+            Point3::new(1.0, 0.0, 2.0),
+            Point3::new(0.0, 1.0, 2.0),
+        );
+        let surface = Surface::ElementarySurface(Box::new(ElementarySurface::Plane(plane)));
+        for outward in [[0.0, 0.0, 1.0], [0.0, 0.1, -1.0]] {
+            let FaceSurface::Plane { origin, normal } = face_surface(&surface, outward) else {
+                panic!("not a plane");
+            };
+            assert_near(origin, [0.0, 0.0, 2.0]);
+            assert_near(normal, [0.0, 0.0, outward[2].signum()]);
+        }
+    }
+
+    #[test]
+    fn cylinders_and_cones() {
+        // built like truck_stepio does for CYLINDRICAL_SURFACE
+        let revolved = |start: Point3, end: Point3| {
+            let mut surface = CylindricalSurface::new(RevolutedCurve::by_revolution(
+                Line(start, end),
+                Point3::new(1.0, 2.0, 0.0),
+                Vector3::unit_z(),
+            ));
+            surface.invert();
+            Surface::ElementarySurface(Box::new(ElementarySurface::CylindricalSurface(surface)))
+        };
+        let cylinder = revolved(Point3::new(4.0, 2.0, 0.0), Point3::new(4.0, 2.0, 1.0));
+        assert_eq!(
+            face_surface(&cylinder, [1.0, 0.0, 0.0]),
+            FaceSurface::Cylinder {
+                origin: [1.0, 2.0, 0.0],
+                axis: [0.0, 0.0, 1.0],
+                radius: 3.0,
+            }
+        );
+        let cone = revolved(Point3::new(4.0, 2.0, 0.0), Point3::new(5.0, 2.0, 1.0));
+        assert_eq!(face_surface(&cone, [1.0, 0.0, 0.0]), FaceSurface::Other);
+    }
+
+    #[test]
+    fn faces_own_their_triangles() {
+        let face = PartFace {
+            first_index: 6,
+            index_count: 6,
+            surface: FaceSurface::Other,
+        };
+        assert!(!face.contains_triangle(1));
+        assert!(face.contains_triangle(2));
+        assert!(face.contains_triangle(3));
+        assert!(!face.contains_triangle(4));
+    }
+}
@@ -0,0 +1,15 @@
+pub const STEP_TRACER: &str = "[STEP_TRACER]";
+pub const DEFAULT_TOLERANCE: f64 = 0.1;
+
+pub const COLORS: [[f32; 3]; 10] = [
+    [0.8, 0.2, 0.2],
+    [0.2, 0.8, 0.2],
+    [0.2, 0.2, 0.8],
+    [0.8, 0.8, 0.2],
+    [0.8, 0.2, 0.8],
+    [0.2, 0.8, 0.8],
+    [0.6, 0.4, 0.2],
+    [0.4, 0.6, 0.8],
+    [0.8, 0.6, 0.4],
+    [0.6, 0.8, 0.4],
+];
@@ -0,0 +1,538 @@
+use crate::trace_span;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::brep::{FaceSurface, PartFace};
+use super::measure::Measurement;
+use super::render::{GpuVertex, PartMesh, RenderablePart};
+use super::types::{Metadata, ProductNode, StepModel};
+
+// Binary layout of a stored model (all numbers little endian):
+//   magic "SVZM", version u8,
+//   u32 length + JSON of everything but the parts (small, evolves with serde defaults),
+//   since version 3: u32 mesh count, then per mesh:
+//     u32 n + n * 6 f32 vertices, u32 n + n u32 indices, u32 n + n * 3 f32
+//     vertex colors, u32 n + n faces (u32 first index, u32 index count,
+//     u8 surface kind + its f64 parameters), u32 n + n edges (u32 m + m * 3 f32)
+//   u32 part count, then per part:
+//     u32 mesh, 16 f32 model matrix, 3 f32 color, u8 visible, u32 section,
+//     u8 has node + u32 node id
+// Before version 3 every part carried its own mesh: vertices, indices, model
+// matrix, color, vertex colors, visible, section, node, and since version 2
+// faces and edges.
+const MAGIC: &[u8; 4] = b"SVZM";
+const VERSION: u8 = 3;
+// oldest version still read, older parts simply have no faces and edges
+const MIN_VERSION: u8 = 1;
+
+const SURFACE_OTHER: u8 = 0;
+const SURFACE_PLANE: u8 = 1;
+const SURFACE_CYLINDER: u8 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct ModelHeader {
+    id: String,
+    metadata: Metadata,
+    #[serde(default)]
+    product_tree: Vec<ProductNode>,
+    #[serde(default)]
+    measurements: Vec<Measurement>,
+}
+
+pub fn encode_model(model: &StepModel) -> Vec<u8> {
+    trace_span!("encode_model");
+    let header = ModelHeader {
+        id: model.id.clone(),
+        metadata: model.metadata.clone(),
+        product_tree: model.product_tree.clone(),
+        measurements: model.measurements.clone(),
+    };
+    let header = serde_json::to_vec(&header).expect("model header is always serializable");
+
+    // instances of a shell share their mesh, it is stored once
+    let mut meshes: Vec<&PartMesh> = Vec::new();
+    let mut positions: HashMap<*const PartMesh, usize> = HashMap::new();
+    let mesh_of_part: Vec<usize> = model
+        .render_parts
+        .iter()
+        .map(|part| {
+            *positions.entry(Arc::as_ptr(&part.mesh)).or_insert_with(|| {
+                meshes.push(&part.mesh);
+                meshes.len() - 1
+            })
+        })
+        .collect();
+
+    let mut out = ByteWriter::default();
+    out.bytes.extend_from_slice(MAGIC);
+    out.bytes.push(VERSION);
+    out.u32(header.len());
+    out.bytes.extend_from_slice(&header);
+    out.u32(meshes.len());
+    for mesh in meshes {
+        encode_mesh(&mut out, mesh);
+    }
+    out.u32(model.render_parts.len());
+    for (part, mesh) in model.render_parts.iter().zip(mesh_of_part) {
+        out.u32(mesh);
+        out.f32s(&part.model_matrix);
+        out.f32s(&part.color);
+        encode_part_tail(&mut out, part);
+    }
+    out.bytes
+}
+
+pub fn decode_model(bytes: &[u8]) -> Result<StepModel, String> {
+    trace_span!("decode_model");
+    let mut input = ByteReader { bytes, pos: 0 };
+    if input.take(MAGIC.len())? != MAGIC {
+        return Err("not a stored model".to_string());
+    }
+    let version = input.u8()?;
+    if !(MIN_VERSION..=VERSION).contains(&version) {
+        return Err(format!("unsupported model version {}", version));
+    }
+    let header_len = input.u32()?;
+    let header: ModelHeader = serde_json::from_slice(input.take(header_len)?)
+        .map_err(|e| format!("invalid model header: {e}"))?;
+    let render_parts = if version >= 3 {
+        decode_shared_parts(&mut input)?
+    } else {
+        let part_count = input.u32()?;
+        let mut parts = Vec::with_capacity(part_count.min(input.remaining()));
+        for _ in 0..part_count {
+            parts.push(decode_legacy_part(&mut input, version)?);
+        }
+        parts
+    };
+    if input.remaining() != 0 {
+        return Err("trailing bytes after model".to_string());
+    }
+    Ok(StepModel {
+        id: header.id,
+        metadata: header.metadata,
+        render_parts,
+        product_tree: header.product_tree,
+        measurements: header.measurements,
+    })
+}
+
+fn encode_mesh(out: &mut ByteWriter, mesh: &PartMesh) {
+    out.u32(mesh.vertices.len());
+    for vertex in &mesh.vertices {
+        out.f32s(&vertex.position);
+        out.f32s(&vertex.normal);
+    }
+    out.u32(mesh.indices.len());
+    for &index in &mesh.indices {
+        out.bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    encode_vertex_colors(out, &mesh.vertex_colors);
+    out.u32(mesh.faces.len());
+    for face in &mesh.faces {
+        out.u32(face.first_index as usize);
+        out.u32(face.index_count as usize);
+        match face.surface {
+            FaceSurface::Plane { origin, normal } => {
+                out.bytes.push(SURFACE_PLANE);
+                out.f64s(&origin);
+                out.f64s(&normal);
+            }
+            FaceSurface::Cylinder {
+                origin,
+                axis,
+                radius,
+            } => {
+                out.bytes.push(SURFACE_CYLINDER);
+                out.f64s(&origin);
+                out.f64s(&axis);
+                out.f64s(&[radius]);
+            }
+            FaceSurface::Other => out.bytes.push(SURFACE_OTHER),
+        }
+    }
+    out.u32(mesh.edges.len());
+    for edge in &mesh.edges {
+        out.u32(edge.len());
+        for point in edge {
+            out.f32s(point);
+        }
+    }
+}
+
+fn encode_vertex_colors(out: &mut ByteWriter, colors: &[[f32; 3]]) {
+    out.u32(colors.len());
+    for color in colors {
+        out.f32s(color);
+    }
+}
+
+// visible, section and node, the end of a part in every version
+fn encode_part_tail(out: &mut ByteWriter, part: &RenderablePart) {
+    out.bytes.push(u8::from(part.visible));
+    out.u32(part.section);
+    match part.node_id {
+        Some(id) => {
+            out.bytes.push(1);
+            out.u32(id);
+        }
+        None => out.bytes.push(0),
+    }
+}
+
+fn decode_shared_parts(input: &mut ByteReader) -> Result<Vec<RenderablePart>, String> {
+    let mesh_count = input.u32()?;
+    let mut meshes = Vec::with_capacity(mesh_count.min(input.remaining() / 20));
+    for _ in 0..mesh_count {
+        let vertices = decode_vertices(input)?;
+        let indices = decode_indices(input)?;
+        let vertex_colors = decode_vertex_colors(input)?;
+        let faces = decode_faces(input)?;
+        let edges = decode_edges(input)?;
+        meshes.push(Arc::new(PartMesh {
+            vertices,
+            indices,
+            vertex_colors,
+            faces,
+            edges,
+        }));
+    }
+    let part_count = input.u32()?;
+    let mut parts = Vec::with_capacity(part_count.min(input.remaining() / 82));
+    for _ in 0..part_count {
+        let mesh = input.u32()?;
+        let mesh = meshes
+            .get(mesh)
+            .ok_or_else(|| format!("part refers to missing mesh {mesh}"))?;
+        let model_matrix = input.f32s()?;
+        let color = input.f32s()?;
+        let (visible, section, node_id) = decode_part_tail(input)?;
+        parts.push(RenderablePart {
+            mesh: Arc::clone(mesh),
+            model_matrix,
+            color,
+            visible,
+            section,
+            node_id,
+        });
+    }
+    Ok(parts)
+}
+
+fn decode_legacy_part(input: &mut ByteReader, version: u8) -> Result<RenderablePart, String> {
+    let vertices = decode_vertices(input)?;
+    let indices = decode_indices(input)?;
+    let model_matrix = input.f32s()?;
+    let color = input.f32s()?;
+    let vertex_colors = decode_vertex_colors(input)?;
+    let (visible, section, node_id) = decode_part_tail(input)?;
+    let (faces, edges) = if version >= 2 {
+        (decode_faces(input)?, decode_edges(input)?)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    Ok(RenderablePart {
+        mesh: Arc::new(PartMesh {
+            vertices,
+            indices,
+            vertex_colors,
+            faces,
+            edges,
+        }),
+        model_matrix,
+        color,
+        visible,
+        section,
+        node_id,
+    })
+}
+
+fn decode_vertices(input: &mut ByteReader) -> Result<Vec<GpuVertex>, String> {
+    let vertex_count = input.u32()?;
+    let mut vertices = Vec::with_capacity(vertex_count.min(input.remaining() / 24));
+    for _ in 0..vertex_count {
+        vertices.push(GpuVertex {
+            position: input.f32s()?,
+            normal: input.f32s()?,
+        });
+    }
+    Ok(vertices)
+}
+
+fn decode_indices(input: &mut ByteReader) -> Result<Vec<u32>, String> {
+    let index_count = input.u32()?;
+    let mut indices = Vec::with_capacity(index_count.min(input.remaining() / 4));
+    for _ in 0..index_count {
+        indices.push(input.u32()? as u32);
+    }
+    Ok(indices)
+}
+
+fn decode_vertex_colors(input: &mut ByteReader) -> Result<Vec<[f32; 3]>, String> {
+    let color_count = input.u32()?;
+    let mut vertex_colors = Vec::with_capacity(color_count.min(input.remaining() / 12));
+    for _ in 0..color_count {
+        vertex_colors.push(input.f32s()?);
+    }
+    Ok(vertex_colors)
+}
+
+fn decode_part_tail(input: &mut ByteReader) -> Result<(bool, usize, Option<usize>), String> {
+    let visible = input.u8()? != 0;
+    let section = input.u32()?;
+    let node_id = match input.u8()? {
+        0 => None,
+        _ => Some(input.u32()?),
+    };
+    Ok((visible, section, node_id))
+}
+
+fn decode_faces(input: &mut ByteReader) -> Result<Vec<PartFace>, String> {
+    let face_count = input.u32()?;
+    let mut faces = Vec::with_capacity(face_count.min(input.remaining() / 9));
+    for _ in 0..face_count {
+        let first_index = input.u32()? as u32;
+        let index_count = input.u32()? as u32;
+        let surface = match input.u8()? {
+            SURFACE_PLANE => FaceSurface::Plane {
+                origin: input.f64s()?,
+                normal: input.f64s()?,
+            },
+            SURFACE_CYLINDER => FaceSurface::Cylinder {
+                origin: input.f64s()?,
+                axis: input.f64s()?,
+                radius: input.f64s::<1>()?[0],
+            },
+            SURFACE_OTHER => FaceSurface::Other,
+            kind => return Err(format!("unknown surface kind {kind}")),
+        };
+        faces.push(PartFace {
+            first_index,
+            index_count,
+            surface,
+        });
+    }
+    Ok(faces)
+}
+
+fn decode_edges(input: &mut ByteReader) -> Result<Vec<Vec<[f32; 3]>>, String> {
+    let edge_count = input.u32()?;
+    let mut edges = Vec::with_capacity(edge_count.min(input.remaining() / 4));
+    for _ in 0..edge_count {
+        let point_count = input.u32()?;
+        if point_count == 0 {
+            return Err("edge without points".to_string());
+        }
+        let mut edge = Vec::with_capacity(point_count.min(input.remaining() / 12));
+        for _ in 0..point_count {
+            edge.push(input.f32s()?);
+        }
+        edges.push(edge);
+    }
+    Ok(edges)
+}
+
+#[derive(Default)]
+struct ByteWriter {
+    bytes: Vec<u8>,
+}
+
+impl ByteWriter {
+    fn u32(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("stored counts fit in u32");
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn f64s(&mut self, values: &[f64]) {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.remaining() {
+            return Err("unexpected end of stored model".to_string());
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], String> {
+        let bytes = self.take(N * 4)?;
+        Ok(std::array::from_fn(|i| {
+            f32::from_le_bytes([
+                bytes[i * 4],
+                bytes[i * 4 + 1],
+                bytes[i * 4 + 2],
+                bytes[i * 4 + 3],
+            ])
+        }))
+    }
+
+    fn f64s<const N: usize>(&mut self) -> Result<[f64; N], String> {
+        let bytes = self.take(N * 8)?;
+        Ok(std::array::from_fn(|i| {
+            f64::from_le_bytes(std::array::from_fn(|j| bytes[i * 8 + j]))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::sample_model;
+
+    #[test]
+    fn model_round_trips() {
+        let model = sample_model("abc");
+        let decoded = decode_model(&encode_model(&model)).unwrap();
+        assert_eq!(decoded, model);
+    }
+
+    #[test]
+    fn parts_are_smaller_than_json() {
+        let mut model = sample_model("abc");
+        Arc::make_mut(&mut model.render_parts[0].mesh).vertices = (0..1000)
+            .map(|i| GpuVertex {
+                position: [i as f32 * 0.123, 1.0 / (i as f32 + 1.0), 3.3],
+                normal: [0.577, 0.577, 0.577],
+            })
+            .collect();
+        let binary = encode_model(&model).len();
+        let json = serde_json::to_vec(&model).unwrap().len();
+        assert!(
+            binary * 2 < json,
+            "binary {binary} bytes, json {json} bytes"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_data() {
+        let bytes = encode_model(&sample_model("abc"));
+        for len in [0, 3, 5, 20, bytes.len() - 1] {
+            assert!(decode_model(&bytes[..len]).is_err(), "accepted {len} bytes");
+        }
+        assert!(decode_model(b"{\"id\":\"abc\"}").is_err());
+
+        // a single face, its surface kind is followed by the edge count and
+        // the one part without a node
+        let mut model = sample_model("abc");
+        model.render_parts = vec![RenderablePart {
+            mesh: Arc::new(PartMesh {
+                faces: vec![PartFace {
+                    first_index: 0,
+                    index_count: 0,
+                    surface: FaceSurface::Other,
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+        let mut unknown_surface = encode_model(&model);
+        let kind = unknown_surface.len() - 95;
+        assert_eq!(unknown_surface[kind], SURFACE_OTHER);
+        unknown_surface[kind] = 9;
+        assert!(decode_model(&unknown_surface).is_err());
+
+        let mut newer = bytes.clone();
+        newer[4] = VERSION + 1;
+        assert!(decode_model(&newer).is_err());
+
+        // the one edge of the sample part, emptied. Its mesh is followed by
+        // the empty mesh of the second part and the two parts.
+        let mut empty_edge = encode_model(&sample_model("abc"));
+        let edges = empty_edge.len() - (20 + 4 + 90 + 86) - 32;
+        assert_eq!(empty_edge[edges..edges + 8], [1, 0, 0, 0, 2, 0, 0, 0]);
+        empty_edge[edges + 4] = 0;
+        empty_edge.drain(edges + 8..edges + 32);
+        assert_eq!(
+            decode_model(&empty_edge),
+            Err("edge without points".to_string())
+        );
+
+        let mut missing_mesh = encode_model(&model);
+        let mesh = missing_mesh.len() - 86;
+        missing_mesh[mesh] = 1;
+        assert!(decode_model(&missing_mesh).is_err());
+    }
+
+    #[test]
+    fn instances_share_one_stored_mesh() {
+        let mut model = sample_model("abc");
+        let single = encode_model(&model).len();
+        let mut copy = model.render_parts[0].clone();
+        copy.model_matrix[12] = 5.0;
+        model.render_parts.push(copy);
+        let bytes = encode_model(&model);
+        // the second instance adds a mesh index and its placement only
+        assert_eq!(bytes.len(), single + 90);
+        let decoded = decode_model(&bytes).unwrap();
+        assert!(Arc::ptr_eq(
+            &decoded.render_parts[0].mesh,
+            &decoded.render_parts.last().unwrap().mesh
+        ));
+        assert_eq!(decoded, model);
+    }
+
+    #[test]
+    fn reads_version_1() {
+        let mut model = sample_model("abc");
+        for part in &mut model.render_parts {
+            let mesh = Arc::make_mut(&mut part.mesh);
+            mesh.faces.clear();
+            mesh.edges.clear();
+        }
+        // version 1 parts carry their own mesh and end before the face and
+        // edge counts
+        let bytes = encode_model(&model);
+        let header_len = u32::from_le_bytes(bytes[5..9].try_into().unwrap()) as usize;
+        let mut out = ByteWriter::default();
+        out.bytes.extend_from_slice(&bytes[..9 + header_len]);
+        out.bytes[4] = 1;
+        out.u32(model.render_parts.len());
+        for part in &model.render_parts {
+            out.u32(part.mesh.vertices.len());
+            for vertex in &part.mesh.vertices {
+                out.f32s(&vertex.position);
+                out.f32s(&vertex.normal);
+            }
+            out.u32(part.mesh.indices.len());
+            for &index in &part.mesh.indices {
+                out.bytes.extend_from_slice(&index.to_le_bytes());
+            }
+            out.f32s(&part.model_matrix);
+            out.f32s(&part.color);
+            encode_vertex_colors(&mut out, &part.mesh.vertex_colors);
+            encode_part_tail(&mut out, part);
+        }
+        assert_eq!(decode_model(&out.bytes), Ok(model));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::trace_span;
+use ruststep::ast::{DataSection, EntityInstance, Name, Parameter, Record};
+use std::collections::HashMap;
+
+// Lightweight id -> record(s) lookup over a raw DATA section.
+// truck_stepio::Table only keeps geometry/topology, everything else
+// (product structure, placements, styles ...) has to be read from here.
+pub struct EntityIndex<'a> {
+    records: HashMap<u64, Vec<&'a Record>>,
+    by_name: HashMap<&'a str, Vec<u64>>,
+}
+
+impl<'a> EntityIndex<'a> {
+    pub fn from_data_section(section: &'a DataSection) -> Self {
+        trace_span!("EntityIndex::from_data_section");
+        let mut records: HashMap<u64, Vec<&'a Record>> = HashMap::new();
+        let mut by_name: HashMap<&'a str, Vec<u64>> = HashMap::new();
+        for entity in &section.entities {
+            let (id, recs): (u64, Vec<&'a Record>) = match entity {
+                EntityInstance::Simple { id, record } => (*id, vec![record]),
+                EntityInstance::Complex { id, subsuper } => (*id, subsuper.0.iter().collect()),
+            };
+            for record in &recs {
+                by_name.entry(record.name.as_str()).or_default().push(id);
+            }
+            records.insert(id, recs);
+        }
+        for ids in by_name.values_mut() {
+            ids.sort_unstable();
+        }
+        Self { records, by_name }
+    }
+
+    // ids of every instance having a record named `name` (simple or part of a complex), sorted
+    pub fn ids_of(&self, name: &str) -> &[u64] {
+        self.by_name.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn records(&self, id: u64) -> &[&'a Record] {
+        self.records.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn record(&self, id: u64, name: &str) -> Option<&'a Record> {
+        self.records(id).iter().copied().find(|r| r.name == name)
+    }
+
+    pub fn has_record(&self, id: u64, name: &str) -> bool {
+        self.record(id, name).is_some()
+    }
+
+    // parameters of the record `name` on instance `id`
+    pub fn params(&self, id: u64, name: &str) -> Option<&'a [Parameter]> {
+        self.record(id, name)
+            .and_then(|r| params_list(&r.parameter))
+    }
+
+    // parameters of the first record of a simple instance, whatever its name
+    pub fn simple_params(&self, id: u64) -> Option<(&'a str, &'a [Parameter])> {
+        let record = self.records(id).first()?;
+        Some((record.name.as_str(), params_list(&record.parameter)?))
+    }
+}
+
+pub fn params_list(param: &Parameter) -> Option<&[Parameter]> {
+    if let Parameter::List(list) = param {
+        Some(list)
+    } else {
+        None
+    }
+}
+
+pub fn param_ref(param: &Parameter) -> Option<u64> {
+    if let Parameter::Ref(Name::Entity(id)) = param {
+        Some(*id)
+    } else {
+        None
+    }
+}
+
+pub fn param_refs(param: &Parameter) -> Vec<u64> {
+    params_list(param)
+        .map(|list| list.iter().filter_map(param_ref).collect())
+        .unwrap_or_default()
+}
+
+pub fn param_str(param: &Parameter) -> Option<&str> {
+    if let Parameter::String(value) = param {
+        Some(value.as_str())
+    } else {
+        None
+    }
+}
+
+pub fn param_real(param: &Parameter) -> Option<f64> {
+    match param {
+        Parameter::Real(value) => Some(*value),
+        Parameter::Integer(value) => Some(*value as f64),
+        Parameter::Typed { parameter, .. } => param_real(parameter),
+        _ => None,
+    }
+}
+
+pub fn param_reals(param: &Parameter) -> Vec<f64> {
+    params_list(param)
+        .map(|list| list.iter().filter_map(param_real).collect())
+        .unwrap_or_default()
+}
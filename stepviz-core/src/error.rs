@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Everything that can go wrong between reading a file and drawing it.
+// Problems that don't stop a load (a shell that can't be tessellated) use the
+// same type and end up in `Metadata::warnings`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StepVizError {
+    Io {
+        message: String,
+    },
+    // 1-based position of the parser failure, 0 when the parser gave none
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Header {
+        message: String,
+    },
+    // `entity` is the #id of the offending instance, if there is one
+    Geometry {
+        entity: Option<u64>,
+        message: String,
+    },
+    Gpu {
+        message: String,
+    },
+    Storage {
+        message: String,
+    },
+}
+
+impl StepVizError {
+    pub fn io(message: impl fmt::Display) -> Self {
+        StepVizError::Io {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn gpu(message: impl fmt::Display) -> Self {
+        StepVizError::Gpu {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn storage(message: impl fmt::Display) -> Self {
+        StepVizError::Storage {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn geometry(entity: Option<u64>, message: impl fmt::Display) -> Self {
+        StepVizError::Geometry {
+            entity,
+            message: message.to_string(),
+        }
+    }
+
+    // Reads the position out of ruststep's error text, which is nom's
+    // `convert_error` output:
+    //   0: at line 5, in Tag:
+    //   #1=FOO(1.,;
+    //            ^
+    pub fn syntax(rendered: &str) -> Self {
+        let lines: Vec<&str> = rendered.lines().collect();
+        let found = lines.iter().enumerate().find_map(|(i, text)| {
+            let (_, rest) = text.split_once("at line ")?;
+            let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+            Some((i, digits.parse::<usize>().ok()?))
+        });
+        let Some((at, line)) = found else {
+            return StepVizError::Syntax {
+                line: 0,
+                column: 0,
+                message: rendered.trim().to_string(),
+            };
+        };
+        let source = lines.get(at + 1).copied().unwrap_or_default();
+        let column = lines
+            .get(at + 2)
+            .and_then(|caret| caret.find('^'))
+            .map_or(1, |offset| offset + 1);
+        let near: String = source.chars().skip(column - 1).take(40).collect();
+        StepVizError::Syntax {
+            line,
+            column,
+            message: if near.trim().is_empty() {
+                "unexpected end of input".to_string()
+            } else {
+                format!("unexpected input '{}'", near.trim_end())
+            },
+        }
+    }
+
+    // short name for reports and APIs
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StepVizError::Io { .. } => "io",
+            StepVizError::Syntax { .. } => "syntax",
+            StepVizError::Header { .. } => "header",
+            StepVizError::Geometry { .. } => "geometry",
+            StepVizError::Gpu { .. } => "gpu",
+            StepVizError::Storage { .. } => "storage",
+        }
+    }
+}
+
+impl fmt::Display for StepVizError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepVizError::Io { message } => write!(f, "I/O error: {message}"),
+            StepVizError::Syntax {
+                line: 0, message, ..
+            } => write!(f, "Syntax error: {message}"),
+            StepVizError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "Syntax error at line {line}, column {column}: {message}"),
+            StepVizError::Header { message } => write!(f, "Invalid STEP header: {message}"),
+            StepVizError::Geometry {
+                entity: Some(entity),
+                message,
+            } => write!(f, "Geometry error at #{entity}: {message}"),
+            StepVizError::Geometry {
+                entity: None,
+                message,
+            } => write!(f, "Geometry error: {message}"),
+            StepVizError::Gpu { message } => write!(f, "GPU error: {message}"),
+            StepVizError::Storage { message } => write!(f, "Storage error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for StepVizError {}
+
+impl From<ruststep::error::Error> for StepVizError {
+    fn from(error: ruststep::error::Error) -> Self {
+        StepVizError::syntax(&error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+// One line of the diagnostics of a load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: StepVizError,
+}
+
+impl Diagnostic {
+    pub fn warning(error: StepVizError) -> Self {
+        Self {
+            severity: Severity::Warning,
+            error,
+        }
+    }
+
+    pub fn error(error: StepVizError) -> Self {
+        Self {
+            severity: Severity::Error,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syntax_errors_carry_their_position() {
+        let text = "ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n#1=FOO(1.,;\nENDSEC;\n";
+        let error = StepVizError::from(ruststep::parser::parse(text).unwrap_err());
+        let StepVizError::Syntax { line, column, .. } = error else {
+            panic!("{error:?}");
+        };
+        assert!(line > 1 && column >= 1, "{error}");
+
+        let rendered =
+            "Error while tokenizing STEP input\n0: at line 7, in Tag:\n#1=FOO(1.,;\n          ^\n";
+        assert_eq!(
+            StepVizError::syntax(rendered).to_string(),
+            "Syntax error at line 7, column 11: unexpected input ';'"
+        );
+        assert_eq!(
+            StepVizError::syntax("garbage").to_string(),
+            "Syntax error: garbage"
+        );
+    }
+
+    #[test]
+    fn geometry_errors_name_the_entity() {
+        let error = StepVizError::geometry(Some(42), "shell could not be converted");
+        assert_eq!(
+            error.to_string(),
+            "Geometry error at #42: shell could not be converted"
+        );
+        assert_eq!(error.kind(), "geometry");
+    }
+}
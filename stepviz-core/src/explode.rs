@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::render::RenderablePart;
+use crate::trace_span;
+use crate::types::ProductNode;
+
+// at full explode a group sits this many times its offset further from the
+// center of the group it belongs to
+const EXPLODE_SCALE: f32 = 1.0;
+
+// Per part moves of an exploded view, computed once per model from the parts
+// at rest. Parts are grouped by the product tree (the whole model, then each
+// subassembly down to the part) and each group moves away from the center of
+// its parent group along their center offset. Levels explode one after the
+// other while the amount goes from 0 to 1: subassemblies separate first, then
+// their parts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExplodePlan {
+    // (level, center offset from the parent group) per part
+    steps: Vec<Vec<(usize, [f32; 3])>>,
+    levels: usize,
+}
+
+impl ExplodePlan {
+    pub fn new(parts: &[RenderablePart], product_tree: &[ProductNode]) -> Self {
+        trace_span!("ExplodePlan::new");
+        let mut paths = HashMap::new();
+        for root in product_tree {
+            collect_paths(root, &mut Vec::new(), &mut paths);
+        }
+        let bounds: Vec<_> = parts.iter().map(RenderablePart::world_bounds).collect();
+
+        // bounds and part count of every product node, over its subtree
+        let mut groups: HashMap<usize, Group> = HashMap::new();
+        let mut model = Group::default();
+        for (part, bounds) in parts.iter().zip(&bounds) {
+            let Some(bounds) = bounds else {
+                continue;
+            };
+            model.add(bounds);
+            let path = part.node_id.and_then(|id| paths.get(&id));
+            for id in path.into_iter().flatten() {
+                groups.entry(*id).or_default().add(bounds);
+            }
+        }
+
+        let mut levels = 0;
+        let steps = parts
+            .iter()
+            .zip(&bounds)
+            .map(|(part, bounds)| {
+                let Some(bounds) = bounds else {
+                    return Vec::new();
+                };
+                let mut own = Group::default();
+                own.add(bounds);
+                let path = part.node_id.and_then(|id| paths.get(&id));
+                let chain = std::iter::once(&model)
+                    .chain(path.into_iter().flatten().filter_map(|id| groups.get(id)))
+                    .chain(std::iter::once(&own));
+                let mut steps = Vec::new();
+                let mut parent: Option<&Group> = None;
+                for group in chain {
+                    // a group holding all of its parent's parts doesn't move
+                    // apart from it, it would only spend a level
+                    if let Some(parent) = parent.filter(|parent| parent.count > group.count) {
+                        let offset = std::array::from_fn(|axis| {
+                            group.center()[axis] - parent.center()[axis]
+                        });
+                        steps.push((steps.len(), offset));
+                    }
+                    parent = Some(group);
+                }
+                levels = levels.max(steps.len());
+                steps
+            })
+            .collect();
+        Self { steps, levels }
+    }
+
+    // translation of each part for `amount` in [0, 1], zero for parts
+    // without geometry
+    pub fn offsets(&self, amount: f32) -> Vec<[f32; 3]> {
+        let amount = amount.clamp(0.0, 1.0) * self.levels as f32;
+        self.steps
+            .iter()
+            .map(|steps| {
+                let mut offset = [0.0; 3];
+                for (level, step) in steps {
+                    let weight = (amount - *level as f32).clamp(0.0, 1.0) * EXPLODE_SCALE;
+                    for axis in 0..3 {
+                        offset[axis] += weight * step[axis];
+                    }
+                }
+                offset
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Group {
+    min: [f32; 3],
+    max: [f32; 3],
+    count: usize,
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Self {
+            min: [f32::INFINITY; 3],
+            max: [f32::NEG_INFINITY; 3],
+            count: 0,
+        }
+    }
+}
+
+impl Group {
+    fn add(&mut self, (min, max): &([f32; 3], [f32; 3])) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(min[axis]);
+            self.max[axis] = self.max[axis].max(max[axis]);
+        }
+        self.count += 1;
+    }
+
+    fn center(&self) -> [f32; 3] {
+        std::array::from_fn(|axis| (self.min[axis] + self.max[axis]) * 0.5)
+    }
+}
+
+// node ids from the root down to each node
+fn collect_paths(
+    node: &ProductNode,
+    path: &mut Vec<usize>,
+    paths: &mut HashMap<usize, Vec<usize>>,
+) {
+    path.push(node.id);
+    paths.insert(node.id, path.clone());
+    for child in &node.children {
+        collect_paths(child, path, paths);
+    }
+    path.pop();
+}
+
+// `matrix` moved by `offset` in world space
+pub fn translated(matrix: &[f32; 16], offset: [f32; 3]) -> [f32; 16] {
+    let mut matrix = *matrix;
+    for axis in 0..3 {
+        matrix[12 + axis] += offset[axis];
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cuboid;
+
+    // unit cube at `corner`, in product node `node_id`
+    fn cube(corner: [f32; 3], node_id: Option<usize>) -> RenderablePart {
+        RenderablePart {
+            node_id,
+            ..cuboid([1.0; 3], corner)
+        }
+    }
+
+    fn node(id: usize, children: Vec<ProductNode>) -> ProductNode {
+        ProductNode {
+            id,
+            name: format!("node {id}"),
+            instance_name: None,
+            instance_count: 1,
+            children,
+        }
+    }
+
+    fn assert_offsets(actual: Vec<[f32; 3]>, expected: &[[f32; 3]]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            for axis in 0..3 {
+                assert!(
+                    (a[axis] - e[axis]).abs() < 1e-5,
+                    "{actual:?} != {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flat_parts_move_away_from_the_center() {
+        let parts = [cube([0.0, 0.0, 0.0], None), cube([4.0, 0.0, 0.0], None)];
+        let plan = ExplodePlan::new(&parts, &[]);
+        assert_offsets(plan.offsets(0.0), &[[0.0; 3], [0.0; 3]]);
+        assert_offsets(plan.offsets(1.0), &[[-2.0, 0.0, 0.0], [2.0, 0.0, 0.0]]);
+        assert_offsets(plan.offsets(0.5), &[[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn subassemblies_explode_first() {
+        // root 0 holds subassemblies 1 (parts at x = 0 and 2) and 4 (parts at
+        // x = 8 and 10)
+        let tree = [node(
+            0,
+            vec![
+                node(1, vec![node(2, vec![]), node(3, vec![])]),
+                node(4, vec![node(5, vec![]), node(6, vec![])]),
+            ],
+        )];
+        let parts = [
+            cube([0.0, 0.0, 0.0], Some(2)),
+            cube([2.0, 0.0, 0.0], Some(3)),
+            cube([8.0, 0.0, 0.0], Some(5)),
+            cube([10.0, 0.0, 0.0], Some(6)),
+        ];
+        let plan = ExplodePlan::new(&parts, &tree);
+        // subassembly centers are at 1.5 and 9.5, the model's at 5.5
+        assert_offsets(
+            plan.offsets(0.5),
+            &[
+                [-4.0, 0.0, 0.0],
+                [-4.0, 0.0, 0.0],
+                [4.0, 0.0, 0.0],
+                [4.0, 0.0, 0.0],
+            ],
+        );
+        assert_offsets(
+            plan.offsets(1.0),
+            &[
+                [-5.0, 0.0, 0.0],
+                [-3.0, 0.0, 0.0],
+                [3.0, 0.0, 0.0],
+                [5.0, 0.0, 0.0],
+            ],
+        );
+        // the parts themselves are left as they are
+        assert_eq!(parts[0].model_matrix[12], 0.0);
+    }
+
+    #[test]
+    fn parts_without_geometry_stay() {
+        let parts = [
+            RenderablePart::default(),
+            cube([0.0, 0.0, 0.0], Some(9)),
+            cube([0.0, 2.0, 0.0], Some(9)),
+        ];
+        let plan = ExplodePlan::new(&parts, &[node(9, vec![])]);
+        assert_offsets(
+            plan.offsets(1.0),
+            &[[0.0; 3], [0.0, -1.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use super::gltf::write_glb;
+use super::math::{normalize3, sub3, transform_point};
+use super::obj::write_obj;
+use super::ply::write_ply;
+use super::render::GpuVertex;
+use super::stl::{StlFormat, write_stl};
+use super::types::StepModel;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Stl(StlFormat),
+    Glb,
+    Obj,
+    Ply,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 5] = [
+        ExportFormat::Stl(StlFormat::Binary),
+        ExportFormat::Stl(StlFormat::Ascii),
+        ExportFormat::Glb,
+        ExportFormat::Obj,
+        ExportFormat::Ply,
+    ];
+
+    // short name used on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Stl(StlFormat::Binary) => "stl",
+            ExportFormat::Stl(StlFormat::Ascii) => "stl-ascii",
+            ExportFormat::Glb => "glb",
+            ExportFormat::Obj => "obj",
+            ExportFormat::Ply => "ply",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Stl(StlFormat::Binary) => "STL (binary)",
+            ExportFormat::Stl(StlFormat::Ascii) => "STL (ASCII)",
+            ExportFormat::Glb => "glTF (GLB)",
+            ExportFormat::Obj => "OBJ + MTL",
+            ExportFormat::Ply => "PLY (binary)",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Stl(_) => "stl",
+            ExportFormat::Glb => "glb",
+            ExportFormat::Obj => "obj",
+            ExportFormat::Ply => "ply",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            ExportFormat::Stl(_) => "model/stl",
+            ExportFormat::Glb => "model/gltf-binary",
+            ExportFormat::Obj => "model/obj",
+            ExportFormat::Ply => "application/x-ply",
+        }
+    }
+
+    // STL is always written in the world and GLB keeps the matrices
+    pub fn has_transform_option(self) -> bool {
+        matches!(self, ExportFormat::Obj | ExportFormat::Ply)
+    }
+
+    // (file name, content) of the files written for the parts at `parts`,
+    // named after `stem`: OBJ comes with its MTL file
+    pub fn write(
+        self,
+        model: &StepModel,
+        options: ExportOptions,
+        stem: &str,
+        parts: &[usize],
+    ) -> Vec<(String, Vec<u8>)> {
+        let file_name = format!("{stem}.{}", self.extension());
+        match self {
+            ExportFormat::Stl(stl) => {
+                let parts = parts.iter().map(|&index| &model.render_parts[index]);
+                vec![(file_name, write_stl(stem, parts, stl))]
+            }
+            ExportFormat::Glb => vec![(file_name, write_glb(model, parts))],
+            ExportFormat::Obj => {
+                let mtl_name = format!("{stem}.mtl");
+                let (obj, mtl) = write_obj(&mtl_name, &export_meshes(model, parts, options));
+                vec![(file_name, obj), (mtl_name, mtl)]
+            }
+            ExportFormat::Ply => {
+                let meshes = export_meshes(model, parts, options);
+                vec![(file_name, write_ply(stem, &meshes))]
+            }
+        }
+    }
+}
+
+// Which parts the OBJ and PLY writers get and in which coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExportOptions {
+    pub visible_only: bool,
+    // world coordinates, else every part in its own coordinates
+    pub apply_transforms: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            visible_only: true,
+            apply_transforms: true,
+        }
+    }
+}
+
+// A part ready to be written: triangles only index existing vertices
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportMesh {
+    pub name: String,
+    pub vertices: Vec<GpuVertex>,
+    pub indices: Vec<u32>,
+    pub color: [f32; 3],
+    // per-vertex colors, empty when the whole part uses `color`
+    pub vertex_colors: Vec<[f32; 3]>,
+}
+
+impl ExportMesh {
+    pub fn of_part(model: &StepModel, index: usize, apply_transforms: bool) -> Option<Self> {
+        let part = model.render_parts.get(index)?;
+        let count = part.mesh.vertices.len();
+        let indices: Vec<u32> = part
+            .mesh
+            .indices
+            .chunks_exact(3)
+            .filter(|triangle| triangle.iter().all(|&index| (index as usize) < count))
+            .flatten()
+            .copied()
+            .collect();
+        if indices.is_empty() {
+            return None;
+        }
+        let matrix = &part.model_matrix;
+        let vertices = part
+            .mesh
+            .vertices
+            .iter()
+            .map(|vertex| {
+                if !apply_transforms {
+                    return *vertex;
+                }
+                // placements are rigid, the normal only turns with the part
+                let origin = transform_point(matrix, [0.0; 3]);
+                GpuVertex {
+                    position: transform_point(matrix, vertex.position),
+                    normal: normalize3(sub3(transform_point(matrix, vertex.normal), origin)),
+                }
+            })
+            .collect();
+        Some(Self {
+            name: model.part_name(index),
+            vertices,
+            indices,
+            color: part.color,
+            vertex_colors: if part.mesh.vertex_colors.len() == count {
+                part.mesh.vertex_colors.clone()
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    pub fn vertex_color(&self, vertex: usize) -> [f32; 3] {
+        self.vertex_colors
+            .get(vertex)
+            .copied()
+            .unwrap_or(self.color)
+    }
+}
+
+// indices of the parts `options` selects, in model order
+pub fn exported_parts(model: &StepModel, options: ExportOptions) -> Vec<usize> {
+    (0..model.render_parts.len())
+        .filter(|&index| !options.visible_only || model.render_parts[index].visible)
+        .collect()
+}
+
+// meshes of `parts`, parts without triangles are left out
+pub fn export_meshes(
+    model: &StepModel,
+    parts: &[usize],
+    options: ExportOptions,
+) -> Vec<ExportMesh> {
+    parts
+        .iter()
+        .filter_map(|&index| ExportMesh::of_part(model, index, options.apply_transforms))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::sample_model;
+    use std::sync::Arc;
+
+    #[test]
+    fn options_pick_parts_and_coordinates() {
+        let mut model = sample_model("a");
+        assert_eq!(exported_parts(&model, ExportOptions::default()), [0]);
+        let all = ExportOptions {
+            visible_only: false,
+            apply_transforms: false,
+        };
+        assert_eq!(exported_parts(&model, all), [0, 1]);
+        // the hidden part has no triangles
+        assert_eq!(export_meshes(&model, &[0, 1], all).len(), 1);
+
+        // a quarter turn about z, then 10 along x
+        model.render_parts[0].model_matrix = [
+            0.0, 1.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 10.0, 0.0, 0.0, 1.0,
+        ];
+        Arc::make_mut(&mut model.render_parts[0].mesh).vertices[1].normal = [1.0, 0.0, 0.0];
+        let placed = ExportMesh::of_part(&model, 0, true).unwrap();
+        assert_eq!(placed.vertices[1].position, [8.0, 1.0, -1.0]);
+        assert_eq!(placed.vertices[1].normal, [0.0, 1.0, 0.0]);
+        assert_eq!(placed.name, "part");
+        assert_eq!(placed.vertex_color(2), [1.0, 0.0, 0.0]);
+
+        let local = ExportMesh::of_part(&model, 0, false).unwrap();
+        assert_eq!(local.vertices, model.render_parts[0].mesh.vertices);
+    }
+}
@@ -0,0 +1,274 @@
+// Models and parts for unit tests, here and in the crates that enable the
+// `test-fixtures` feature.
+use crate::brep::{FaceSurface, PartFace};
+use crate::error::StepVizError;
+use crate::measure::Measurement;
+use crate::render::{GpuVertex, PartMesh, RenderablePart};
+use crate::types::{BoundingBox, Metadata, ProductNode, StepHeader, StepModel};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub fn sample_model(id: &str) -> StepModel {
+    let header = StepHeader {
+        file_description: "test".to_string(),
+        implementation_level: "2;1".to_string(),
+        file_name: format!("{id}.stp"),
+        time_stamp: "2024-01-01T00:00:00".to_string(),
+        author: vec!["a".to_string()],
+        organization: vec![],
+        preprocessor_version: String::new(),
+        originating_system: String::new(),
+        authorization: String::new(),
+        file_schema: "AP214".to_string(),
+    };
+    let vertex = |x: f32| GpuVertex {
+        position: [x, 2.0 * x, -x],
+        normal: [0.0, 0.0, 1.0],
+    };
+    StepModel {
+        id: id.to_string(),
+        metadata: Metadata {
+            header,
+            entity_count: 42,
+            bounding_box: Some(BoundingBox {
+                min: [0.0, 0.0, 0.0],
+                max: [1.0, 2.0, 3.0],
+            }),
+            units: Some("mm".to_string()),
+            vertex_count: 3,
+            triangle_count: 1,
+            volume: Some(1.5),
+            surface_area: None,
+            section_entity_counts: vec![42],
+            validation_properties: Vec::new(),
+            warnings: vec![StepVizError::geometry(
+                Some(7),
+                "shell could not be converted",
+            )],
+        },
+        render_parts: vec![
+            RenderablePart {
+                mesh: Arc::new(PartMesh {
+                    vertices: vec![vertex(0.0), vertex(1.0), vertex(0.5)],
+                    indices: vec![0, 1, 2],
+                    vertex_colors: vec![[1.0, 0.0, 0.0]; 3],
+                    faces: vec![
+                        PartFace {
+                            first_index: 0,
+                            index_count: 3,
+                            surface: FaceSurface::Plane {
+                                origin: [0.0, 0.5, 0.0],
+                                normal: [0.0, 0.0, 1.0],
+                            },
+                        },
+                        PartFace {
+                            first_index: 3,
+                            index_count: 0,
+                            surface: FaceSurface::Cylinder {
+                                origin: [1.0, 2.0, 3.0],
+                                axis: [0.0, 1.0, 0.0],
+                                radius: 0.25,
+                            },
+                        },
+                        PartFace {
+                            first_index: 3,
+                            index_count: 0,
+                            surface: FaceSurface::Other,
+                        },
+                    ],
+                    edges: vec![vec![[0.0, 0.0, 0.0], [1.0, 2.0, -1.0]]],
+                }),
+                color: [0.1, 0.2, 0.3],
+                node_id: Some(7),
+                section: 1,
+                ..Default::default()
+            },
+            RenderablePart {
+                visible: false,
+                ..Default::default()
+            },
+        ],
+        product_tree: vec![ProductNode {
+            id: 7,
+            name: "part".to_string(),
+            instance_name: None,
+            instance_count: 1,
+            children: vec![],
+        }],
+        measurements: vec![Measurement::Distance {
+            from: [0.0, 0.0, 0.0],
+            to: [1.0, 2.0, 3.0],
+        }],
+    }
+}
+
+// Box from the origin to `size`, moved to `corner` by its model matrix. Faces
+// come in the order -x, +x, -y, +y, -z, +z, two outward wound triangles and
+// four vertices of their own each, like tessellated B-rep faces.
+pub fn cuboid(size: [f32; 3], corner: [f32; 3]) -> RenderablePart {
+    let mut mesh = PartMesh::default();
+    for axis in 0..3 {
+        // e_a x e_b = e_axis
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        for high in [false, true] {
+            let base = mesh.vertices.len() as u32;
+            for (u, v) in [(0, 0), (1, 0), (1, 1), (0, 1)] {
+                let mut position = [0.0; 3];
+                position[axis] = if high { size[axis] } else { 0.0 };
+                position[a] = u as f32 * size[a];
+                position[b] = v as f32 * size[b];
+                let mut normal = [0.0; 3];
+                normal[axis] = if high { 1.0 } else { -1.0 };
+                mesh.vertices.push(GpuVertex { position, normal });
+            }
+            let quad = if high {
+                [0, 1, 2, 0, 2, 3]
+            } else {
+                [0, 2, 1, 0, 3, 2]
+            };
+            mesh.indices.extend(quad.map(|index| base + index));
+        }
+    }
+    let mut part = RenderablePart {
+        mesh: Arc::new(mesh),
+        ..Default::default()
+    };
+    part.model_matrix[12..15].copy_from_slice(&corner);
+    part
+}
+
+// A complete exchange file with one DATA section per entry of `sections`
+pub fn step_file(sections: &[&str]) -> String {
+    let mut text = "ISO-10303-21;\nHEADER;\nFILE_DESCRIPTION((''),'2;1');\n\
+        FILE_NAME('test.stp','2024-01-01T00:00:00',(''),(''),'','','');\n\
+        FILE_SCHEMA(('AUTOMOTIVE_DESIGN'));\nENDSEC;\n"
+        .to_string();
+    for section in sections {
+        text.push_str("DATA;\n");
+        text.push_str(section);
+        text.push_str("ENDSEC;\n");
+    }
+    text.push_str("END-ISO-10303-21;\n");
+    text
+}
+
+// DATA entities of a product `name` whose shape is a cube of edge `size` at
+// the origin, product definition #3, geometry from #100 on
+pub fn step_cube(name: &str, size: f64) -> String {
+    let mut entities: Vec<String> = Vec::new();
+    let push = |entities: &mut Vec<String>, entity: String| {
+        entities.push(entity);
+        99 + entities.len()
+    };
+    // corner i has its bit k set when it lies on the far side along axis k
+    let points: Vec<usize> = (0..8)
+        .map(|i| {
+            let [x, y, z] = [0, 1, 2].map(|axis| ((i >> axis) & 1) as f64 * size);
+            push(
+                &mut entities,
+                format!("CARTESIAN_POINT('',({x:?},{y:?},{z:?}))"),
+            )
+        })
+        .collect();
+    let vertices: Vec<usize> = points
+        .iter()
+        .map(|point| push(&mut entities, format!("VERTEX_POINT('',#{point})")))
+        .collect();
+    // directions[axis][0] points along the axis, [1] against it
+    let directions: Vec<[usize; 2]> = (0..3)
+        .map(|axis| {
+            [1.0, -1.0].map(|sign| {
+                let [x, y, z] = [0, 1, 2].map(|k| if k == axis { sign } else { 0.0 });
+                push(&mut entities, format!("DIRECTION('',({x:?},{y:?},{z:?}))"))
+            })
+        })
+        .collect();
+    // edges run from the near corner to the far one
+    let mut edges = HashMap::new();
+    for start in 0..8 {
+        for axis in (0..3).filter(|axis| start >> axis & 1 == 0) {
+            let end = start | 1 << axis;
+            let vector = push(
+                &mut entities,
+                format!("VECTOR('',#{},{size:?})", directions[axis][0]),
+            );
+            let line = push(
+                &mut entities,
+                format!("LINE('',#{},#{vector})", points[start]),
+            );
+            let edge = push(
+                &mut entities,
+                format!(
+                    "EDGE_CURVE('',#{},#{},#{line},.T.)",
+                    vertices[start], vertices[end]
+                ),
+            );
+            edges.insert((start, end), edge);
+        }
+    }
+    let mut faces = Vec::new();
+    for axis in 0..3 {
+        let (u, w) = ((axis + 1) % 3, (axis + 2) % 3);
+        for far in [false, true] {
+            let base = usize::from(far) << axis;
+            // counter-clockwise seen from outside
+            let mut corners = [base, base | 1 << u, base | 1 << u | 1 << w, base | 1 << w];
+            if !far {
+                corners.reverse();
+            }
+            let oriented: Vec<String> = (0..4)
+                .map(|k| {
+                    let (from, to) = (corners[k], corners[(k + 1) % 4]);
+                    let edge = edges[&(from.min(to), from.max(to))];
+                    let forward = if from < to { ".T." } else { ".F." };
+                    let id = push(
+                        &mut entities,
+                        format!("ORIENTED_EDGE('',*,*,#{edge},{forward})"),
+                    );
+                    format!("#{id}")
+                })
+                .collect();
+            let edge_loop = push(
+                &mut entities,
+                format!("EDGE_LOOP('',({}))", oriented.join(",")),
+            );
+            let bound = push(
+                &mut entities,
+                format!("FACE_OUTER_BOUND('',#{edge_loop},.T.)"),
+            );
+            let placement = push(
+                &mut entities,
+                format!(
+                    "AXIS2_PLACEMENT_3D('',#{},#{},#{})",
+                    points[base],
+                    directions[axis][usize::from(!far)],
+                    directions[u][0]
+                ),
+            );
+            let plane = push(&mut entities, format!("PLANE('',#{placement})"));
+            let face = push(
+                &mut entities,
+                format!("ADVANCED_FACE('',(#{bound}),#{plane},.T.)"),
+            );
+            faces.push(format!("#{face}"));
+        }
+    }
+    let shell = push(
+        &mut entities,
+        format!("CLOSED_SHELL('',({}))", faces.join(",")),
+    );
+    let solid = push(&mut entities, format!("MANIFOLD_SOLID_BREP('',#{shell})"));
+
+    let mut text = format!(
+        "#1=PRODUCT('{name}','{name}','',(#9));\n\
+         #2=PRODUCT_DEFINITION_FORMATION('','',#1);\n\
+         #3=PRODUCT_DEFINITION('','',#2,$);\n\
+         #4=PRODUCT_DEFINITION_SHAPE('','',#3);\n\
+         #5=ADVANCED_BREP_SHAPE_REPRESENTATION('',(#{solid}),$);\n\
+         #6=SHAPE_DEFINITION_REPRESENTATION(#4,#5);\n"
+    );
+    for (id, entity) in (100..).zip(entities) {
+        text.push_str(&format!("#{id}={entity};\n"));
+    }
+    text
+}
@@ -0,0 +1,559 @@
+use crate::trace_span;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+use super::math::normalize3;
+use super::render::{GpuVertex, RenderablePart};
+use super::types::{ProductNode, StepModel};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+const TRIANGLES: u32 = 4;
+const IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+// Writes the parts at `parts` as a binary glTF 2.0 file. Every part is a mesh
+// on a node carrying its model matrix, the node hangs below the nodes of its
+// product and the assemblies above it so names and structure survive.
+pub fn write_glb(model: &StepModel, parts: &[usize]) -> Vec<u8> {
+    trace_span!("write_glb");
+    let mut gltf = Gltf::default();
+
+    // product node id -> glTF nodes of its parts
+    let mut part_nodes: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for &index in parts {
+        let Some(part) = model.render_parts.get(index) else {
+            continue;
+        };
+        let Some(mesh) = gltf.push_mesh(&model.part_name(index), part) else {
+            continue;
+        };
+        let mut node = json!({ "name": model.part_name(index), "mesh": mesh });
+        if part.model_matrix != IDENTITY {
+            node["matrix"] = json!(part.model_matrix);
+        }
+        gltf.nodes.push(node);
+        match part.node_id {
+            Some(id) => part_nodes.entry(id).or_default().push(gltf.nodes.len() - 1),
+            None => roots.push(gltf.nodes.len() - 1),
+        }
+    }
+    for product in &model.product_tree {
+        roots.extend(gltf.push_product(product, &part_nodes));
+    }
+
+    let stem = model
+        .metadata
+        .header
+        .file_name
+        .rsplit_once('.')
+        .map_or(model.metadata.header.file_name.as_str(), |(stem, _)| stem);
+    gltf.into_glb(stem, roots)
+}
+
+#[derive(Default)]
+struct Gltf {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    materials: Vec<Value>,
+    // (base color, uses vertex colors) of every material
+    material_keys: Vec<([f32; 3], bool)>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+}
+
+impl Gltf {
+    // adds the geometry of `part`, None when it has no triangle
+    fn push_mesh(&mut self, name: &str, part: &RenderablePart) -> Option<usize> {
+        let count = part.mesh.vertices.len();
+        let indices: Vec<u32> = part
+            .mesh
+            .indices
+            .chunks_exact(3)
+            .filter(|triangle| triangle.iter().all(|&index| (index as usize) < count))
+            .flatten()
+            .copied()
+            .collect();
+        if indices.is_empty() {
+            return None;
+        }
+
+        // positions and normals stay interleaved as in GpuVertex, normals
+        // must be unit length in glTF
+        let vertices: Vec<GpuVertex> = part
+            .mesh
+            .vertices
+            .iter()
+            .map(|vertex| GpuVertex {
+                position: vertex.position,
+                normal: match normalize3(vertex.normal) {
+                    [0.0, 0.0, 0.0] => [0.0, 0.0, 1.0],
+                    normal => normal,
+                },
+            })
+            .collect();
+        let vertex_view = self.push_view(
+            bytemuck::cast_slice(&vertices),
+            Some(std::mem::size_of::<GpuVertex>()),
+            ARRAY_BUFFER,
+        );
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for vertex in &vertices {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+            }
+        }
+        let position = self.push_accessor(json!({
+            "bufferView": vertex_view,
+            "componentType": FLOAT,
+            "count": count,
+            "type": "VEC3",
+            "min": min,
+            "max": max,
+        }));
+        let normal = self.push_accessor(json!({
+            "bufferView": vertex_view,
+            "byteOffset": 12,
+            "componentType": FLOAT,
+            "count": count,
+            "type": "VEC3",
+        }));
+        let mut attributes = json!({ "POSITION": position, "NORMAL": normal });
+
+        let vertex_colors = part.mesh.vertex_colors.len() == count;
+        if vertex_colors {
+            let colors: Vec<[f32; 3]> = part
+                .mesh
+                .vertex_colors
+                .iter()
+                .map(|color| color.map(to_linear))
+                .collect();
+            let view = self.push_view(bytemuck::cast_slice(&colors), None, ARRAY_BUFFER);
+            attributes["COLOR_0"] = json!(self.push_accessor(json!({
+                "bufferView": view,
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC3",
+            })));
+        }
+
+        let index_view = self.push_view(bytemuck::cast_slice(&indices), None, ELEMENT_ARRAY_BUFFER);
+        let index_accessor = self.push_accessor(json!({
+            "bufferView": index_view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+
+        let material = self.material(part.color, vertex_colors);
+        self.meshes.push(json!({
+            "name": name,
+            "primitives": [{
+                "attributes": attributes,
+                "indices": index_accessor,
+                "material": material,
+                "mode": TRIANGLES,
+            }],
+        }));
+        Some(self.meshes.len() - 1)
+    }
+
+    // the node of `product` with its parts and sub assemblies, None when
+    // nothing below it was exported
+    fn push_product(
+        &mut self,
+        product: &ProductNode,
+        part_nodes: &HashMap<usize, Vec<usize>>,
+    ) -> Option<usize> {
+        let mut children = part_nodes.get(&product.id).cloned().unwrap_or_default();
+        for child in &product.children {
+            children.extend(self.push_product(child, part_nodes));
+        }
+        if children.is_empty() {
+            return None;
+        }
+        self.nodes.push(json!({
+            "name": product.instance_name.as_ref().unwrap_or(&product.name),
+            "children": children,
+        }));
+        Some(self.nodes.len() - 1)
+    }
+
+    // materials are shared by parts of the same color. STEP colors are sRGB,
+    // glTF factors are linear; vertex colors multiply a white base.
+    fn material(&mut self, color: [f32; 3], vertex_colors: bool) -> usize {
+        let color = if vertex_colors { [1.0; 3] } else { color };
+        let key = (color, vertex_colors);
+        if let Some(index) = self.material_keys.iter().position(|known| *known == key) {
+            return index;
+        }
+        let [r, g, b] = color.map(to_linear);
+        self.materials.push(json!({
+            "name": format!("color_{}", self.materials.len() + 1),
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [r, g, b, 1.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.5,
+            },
+            // the viewer draws both sides, STEP shells are not always closed
+            "doubleSided": true,
+        }));
+        self.material_keys.push(key);
+        self.materials.len() - 1
+    }
+
+    fn push_view(&mut self, bytes: &[u8], stride: Option<usize>, target: u32) -> usize {
+        // every view starts 4 byte aligned, enough for any component type
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        });
+        if let Some(stride) = stride {
+            view["byteStride"] = json!(stride);
+        }
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn into_glb(self, name: &str, roots: Vec<usize>) -> Vec<u8> {
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "stepvisualizer" },
+            "scene": 0,
+            "scenes": [{ "name": name }],
+        });
+        // empty arrays are not allowed, a model without triangles has none
+        if !self.nodes.is_empty() {
+            document["scenes"][0]["nodes"] = json!(roots);
+            document["nodes"] = json!(self.nodes);
+        }
+        if !self.buffer.is_empty() {
+            document["buffers"] = json!([{ "byteLength": self.buffer.len() }]);
+            document["bufferViews"] = json!(self.buffer_views);
+            document["accessors"] = json!(self.accessors);
+            document["materials"] = json!(self.materials);
+            document["meshes"] = json!(self.meshes);
+        }
+
+        // JSON is padded with spaces and binary data with zeros to 4 bytes
+        let mut json = serde_json::to_vec(&document).unwrap_or_default();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut bin = self.buffer;
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+        let bin_chunk = if bin.is_empty() { 0 } else { 8 + bin.len() };
+        let length = 12 + 8 + json.len() + bin_chunk;
+
+        let mut out = Vec::with_capacity(length);
+        for word in [GLB_MAGIC, 2, length as u32, json.len() as u32, CHUNK_JSON] {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out.extend_from_slice(&json);
+        if !bin.is_empty() {
+            out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            out.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+            out.extend_from_slice(&bin);
+        }
+        out
+    }
+}
+
+fn to_linear(channel: f32) -> f32 {
+    let channel = channel.clamp(0.0, 1.0);
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explode::translated;
+    use crate::pipeline::{parse_step, tessellate_all};
+    use crate::render::PartMesh;
+    use std::sync::Arc;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn f32_at(bytes: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn index(value: &Value) -> usize {
+        value.as_u64().unwrap() as usize
+    }
+
+    // Checks the rules of the Khronos validator that can be checked without
+    // it: GLB layout and padding, views inside the buffer, aligned accessors
+    // inside their views, exact position bounds, unit normals, indices in
+    // range, valid references and nodes forming a tree. Returns the JSON.
+    fn check_glb(glb: &[u8]) -> Value {
+        assert_eq!(u32_at(glb, 0), GLB_MAGIC);
+        assert_eq!(u32_at(glb, 4), 2);
+        assert_eq!(u32_at(glb, 8) as usize, glb.len());
+        let json_length = u32_at(glb, 12) as usize;
+        assert_eq!(u32_at(glb, 16), CHUNK_JSON);
+        assert_eq!(json_length % 4, 0);
+        let document: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        let bin = if glb.len() > 20 + json_length {
+            let at = 20 + json_length;
+            let bin_length = u32_at(glb, at) as usize;
+            assert_eq!(u32_at(glb, at + 4), CHUNK_BIN);
+            assert_eq!(bin_length % 4, 0);
+            assert_eq!(at + 8 + bin_length, glb.len());
+            &glb[at + 8..]
+        } else {
+            &glb[glb.len()..]
+        };
+        assert_eq!(document["asset"]["version"], "2.0");
+
+        let empty = Vec::new();
+        let list = |key: &str| document[key].as_array().unwrap_or(&empty).clone();
+        let buffers = list("buffers");
+        if let [buffer] = buffers.as_slice() {
+            let length = index(&buffer["byteLength"]);
+            assert!(length <= bin.len() && bin.len() - length < 4);
+        } else {
+            assert!(buffers.is_empty() && bin.is_empty());
+        }
+        let views = list("bufferViews");
+        for view in &views {
+            let offset = index(&view["byteOffset"]);
+            assert_eq!(offset % 4, 0, "{view}");
+            assert!(offset + index(&view["byteLength"]) <= bin.len(), "{view}");
+            if let Some(stride) = view.get("byteStride") {
+                let stride = index(stride);
+                assert!(
+                    stride.is_multiple_of(4) && (4..=252).contains(&stride),
+                    "{view}"
+                );
+                assert_ne!(view["target"], ELEMENT_ARRAY_BUFFER);
+            }
+        }
+
+        // accessor -> its values as f32 or u32, one Vec per element
+        let accessors = list("accessors");
+        let read = |accessor: usize| -> Vec<Vec<f64>> {
+            let accessor = &accessors[accessor];
+            let view = &views[index(&accessor["bufferView"])];
+            let components = match accessor["type"].as_str().unwrap() {
+                "SCALAR" => 1,
+                "VEC3" => 3,
+                other => panic!("{other}"),
+            };
+            let count = index(&accessor["count"]);
+            let offset = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+            let element = components * 4;
+            let stride = view.get("byteStride").map_or(element, index);
+            assert!(count > 0);
+            assert_eq!(offset % 4, 0);
+            assert!(offset + stride * (count - 1) + element <= index(&view["byteLength"]));
+            let start = index(&view["byteOffset"]) + offset;
+            (0..count)
+                .map(|i| {
+                    (0..components)
+                        .map(|c| {
+                            let at = start + i * stride + c * 4;
+                            if accessor["componentType"] == FLOAT {
+                                f32_at(bin, at) as f64
+                            } else {
+                                u32_at(bin, at) as f64
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        for (i, accessor) in accessors.iter().enumerate() {
+            if let (Some(min), Some(max)) = (accessor.get("min"), accessor.get("max")) {
+                let values = read(i);
+                for axis in 0..3 {
+                    let axis_values = values.iter().map(|value| value[axis]);
+                    let low = axis_values.clone().fold(f64::INFINITY, f64::min);
+                    let high = axis_values.fold(f64::NEG_INFINITY, f64::max);
+                    assert_eq!(min[axis].as_f64().unwrap() as f32, low as f32);
+                    assert_eq!(max[axis].as_f64().unwrap() as f32, high as f32);
+                }
+            }
+        }
+
+        let materials = list("materials");
+        for mesh in list("meshes") {
+            for primitive in mesh["primitives"].as_array().unwrap() {
+                let attributes = &primitive["attributes"];
+                let position = index(&attributes["POSITION"]);
+                assert!(accessors[position].get("min").is_some());
+                let vertex_count = index(&accessors[position]["count"]);
+                for normal in read(index(&attributes["NORMAL"])) {
+                    let length = normal.iter().map(|x| x * x).sum::<f64>().sqrt();
+                    assert!((length - 1.0).abs() < 5e-4, "{normal:?}");
+                }
+                if let Some(colors) = attributes.get("COLOR_0") {
+                    assert_eq!(index(&accessors[index(colors)]["count"]), vertex_count);
+                }
+                let indices = read(index(&primitive["indices"]));
+                assert_eq!(indices.len() % 3, 0);
+                assert!(indices.iter().all(|i| (i[0] as usize) < vertex_count));
+                assert!(index(&primitive["material"]) < materials.len());
+            }
+        }
+
+        // every node has one parent at most and is reached from the scene
+        let nodes = list("nodes");
+        let mut parents = vec![0; nodes.len()];
+        for node in &nodes {
+            if let Some(mesh) = node.get("mesh") {
+                assert!(index(mesh) < list("meshes").len());
+            }
+            if let Some(matrix) = node.get("matrix") {
+                assert_eq!(matrix.as_array().unwrap().len(), 16);
+            }
+            for child in node["children"].as_array().unwrap_or(&empty) {
+                parents[index(child)] += 1;
+            }
+        }
+        let roots = document["scenes"][0]["nodes"].as_array().unwrap_or(&empty);
+        for root in roots {
+            parents[index(root)] += 1;
+        }
+        assert!(parents.iter().all(|count| *count == 1), "{parents:?}");
+        document
+    }
+
+    fn vertex(position: [f32; 3], normal: [f32; 3]) -> GpuVertex {
+        GpuVertex { position, normal }
+    }
+
+    fn triangle(offset: [f32; 3], color: [f32; 3], node_id: Option<usize>) -> RenderablePart {
+        RenderablePart {
+            mesh: Arc::new(PartMesh {
+                vertices: vec![
+                    vertex([0.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+                    vertex([2.0, 0.0, 0.0], [0.0, 0.0, 3.0]),
+                    vertex([0.0, 1.0, -1.0], [0.0, 0.0, 0.0]),
+                ],
+                // the second triangle points past the vertices and is dropped
+                indices: vec![0, 1, 2, 0, 2, 7],
+                ..Default::default()
+            }),
+            model_matrix: translated(&IDENTITY, offset),
+            color,
+            node_id,
+            ..Default::default()
+        }
+    }
+
+    fn product(id: usize, name: &str, children: Vec<ProductNode>) -> ProductNode {
+        ProductNode {
+            id,
+            name: name.to_string(),
+            instance_name: None,
+            instance_count: 1,
+            children,
+        }
+    }
+
+    fn load(text: &str) -> StepModel {
+        let parsed = parse_step(text, "test.stp").unwrap();
+        StepModel {
+            id: parsed.id,
+            metadata: parsed.metadata,
+            product_tree: parsed.prepared.product_tree,
+            render_parts: tessellate_all(parsed.prepared.sections).0,
+            measurements: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parts_keep_structure_names_and_colors() {
+        let mut model = load(include_str!("../../examples/Part1.stp"));
+        model.product_tree = vec![product(
+            0,
+            "assembly",
+            vec![
+                product(1, "bracket", Vec::new()),
+                product(2, "spare", Vec::new()),
+            ],
+        )];
+        let red = [1.0, 0.0, 0.0];
+        model.render_parts = vec![
+            triangle([1.0, 2.0, 3.0], red, Some(1)),
+            triangle([0.0; 3], red, Some(1)),
+            triangle([0.0; 3], [0.5, 0.5, 0.5], None),
+        ];
+        Arc::make_mut(&mut model.render_parts[2].mesh).vertex_colors = vec![[0.0, 1.0, 0.0]; 3];
+
+        let document = check_glb(&write_glb(&model, &[0, 1, 2]));
+        let nodes = document["nodes"].as_array().unwrap();
+        let names: Vec<&str> = nodes.iter().map(|n| n["name"].as_str().unwrap()).collect();
+        // the spare product has no parts and is left out
+        assert_eq!(
+            names,
+            ["bracket", "bracket", "Mesh 3", "bracket", "assembly"]
+        );
+        assert_eq!(nodes[3]["children"], json!([0, 1]));
+        assert_eq!(nodes[4]["children"], json!([3]));
+        assert_eq!(document["scenes"][0]["nodes"], json!([2, 4]));
+        assert_eq!(nodes[0]["matrix"][12], 1.0);
+        assert_eq!(nodes[0]["matrix"][14], 3.0);
+        assert!(nodes[1].get("matrix").is_none());
+
+        // red is shared, vertex colors get a white base
+        let materials = document["materials"].as_array().unwrap();
+        assert_eq!(materials.len(), 2);
+        let base = &materials[0]["pbrMetallicRoughness"]["baseColorFactor"];
+        assert_eq!(base, &json!([1.0, 0.0, 0.0, 1.0]));
+        let meshes = document["meshes"].as_array().unwrap();
+        assert_eq!(meshes[2]["primitives"][0]["material"], 1);
+        assert!(meshes[2]["primitives"][0]["attributes"]["COLOR_0"].is_u64());
+        assert_eq!(document["accessors"][0]["max"], json!([2.0, 1.0, 0.0]));
+        assert_eq!(document["accessors"][2]["count"], 3);
+    }
+
+    #[test]
+    fn example_assembly_passes_checks() {
+        let model = load(include_str!("../../examples/as1-tc-214.stp"));
+        let parts: Vec<usize> = (0..model.render_parts.len()).collect();
+        let document = check_glb(&write_glb(&model, &parts));
+        assert_eq!(document["meshes"].as_array().unwrap().len(), parts.len());
+        let roots = document["scenes"][0]["nodes"].as_array().unwrap();
+        assert_eq!(roots.len(), 1);
+        let root = &document["nodes"][index(&roots[0])];
+        assert_eq!(root["name"], model.product_tree[0].name.as_str());
+    }
+
+    #[test]
+    fn empty_selection_is_still_valid() {
+        let model = load(include_str!("../../examples/Part1.stp"));
+        let document = check_glb(&write_glb(&model, &[]));
+        assert!(document.get("buffers").is_none());
+        assert!(document.get("nodes").is_none());
+    }
+}
@@ -0,0 +1,57 @@
+// STEP parsing, tessellation, analysis and mesh export without any browser
+// dependency, shared by the web viewer and the stepviz command line tool
+pub mod assembly;
+pub mod brep;
+pub mod constants;
+pub mod encoding;
+pub mod entities;
+pub mod error;
+pub mod explode;
+pub mod export;
+pub mod gltf;
+pub mod mass;
+pub mod math;
+pub mod measure;
+pub mod mesh_check;
+pub mod obj;
+pub mod parser;
+pub mod pipeline;
+pub mod ply;
+pub mod render;
+pub mod section;
+pub mod stl;
+pub mod styles;
+pub mod types;
+pub mod validation;
+pub mod zip;
+
+// models, parts and STEP files for tests, also used by the tests of
+// dependents through the `test-fixtures` feature
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod fixtures;
+
+pub use brep::PartFace;
+pub use constants::*;
+pub use error::{Diagnostic, Severity, StepVizError};
+pub use math::{
+    add3, create_look_at_matrix, create_perspective_matrix, cross3, dot3, length3,
+    multiply_matrices, normalize3, scale3, sub3, transform_point,
+};
+pub use measure::Measurement;
+pub use pipeline::{PipelineProgress, TessellationJob, load_step, parse_step, tessellate_all};
+pub use render::{GpuVertex, PartMesh, RenderablePart};
+pub use section::ClipPlane;
+pub use types::{BoundingBox, FileIndexItem, Metadata, ProductNode, StepModel, find_product_node};
+
+#[macro_export]
+macro_rules! trace_span {
+    ($name:expr) => {
+        let sp = tracing::span!(
+            tracing::Level::INFO,
+            $crate::constants::STEP_TRACER,
+            "{}",
+            $name
+        );
+        let _ = sp.entered();
+    };
+}
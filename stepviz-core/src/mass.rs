@@ -0,0 +1,278 @@
+use crate::math::{add3_f64, cross3_f64, dot3_f64, length3_f64, sub3_f64};
+use crate::render::RenderablePart;
+use crate::trace_span;
+
+// Mass properties of a closed mesh at unit density, in world space and model
+// units. Scale `volume` and `inertia` by a density to get mass and the mass
+// moments of inertia.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MassProperties {
+    pub volume: f64,
+    pub area: f64,
+    // center of mass
+    pub centroid: [f64; 3],
+    // inertia tensor about `centroid`, world axes
+    pub inertia: [[f64; 3]; 3],
+}
+
+impl MassProperties {
+    // Sums signed tetrahedra between a reference point and every triangle,
+    // the part's winding may be flipped as a whole
+    pub fn of_part(part: &RenderablePart) -> Self {
+        trace_span!("MassProperties::of_part");
+        let world: Vec<[f64; 3]> = part
+            .mesh
+            .vertices
+            .iter()
+            .map(|vertex| transform(&part.model_matrix, vertex.position))
+            .collect();
+        // integrating around a point on the part keeps the products small
+        let Some(&reference) = world.first() else {
+            return Self::default();
+        };
+
+        let mut volume = 0.0;
+        let mut area = 0.0;
+        let mut first = [0.0; 3];
+        // ∫ x_i x_j dV around `reference`
+        let mut second = [[0.0; 3]; 3];
+        for triangle in part.mesh.indices.chunks_exact(3) {
+            let Some(corners) = triangle
+                .iter()
+                .map(|&index| world.get(index as usize).map(|p| sub3_f64(*p, reference)))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let (a, b, c) = (corners[0], corners[1], corners[2]);
+            let normal = cross3_f64(sub3_f64(b, a), sub3_f64(c, a));
+            area += length3_f64(normal) * 0.5;
+
+            let det = dot3_f64(a, cross3_f64(b, c));
+            volume += det / 6.0;
+            let sum = add3_f64(add3_f64(a, b), c);
+            for i in 0..3 {
+                first[i] += det / 24.0 * sum[i];
+                for j in 0..3 {
+                    let products = a[i] * a[j] + b[i] * b[j] + c[i] * c[j];
+                    second[i][j] += det / 120.0 * (products + sum[i] * sum[j]);
+                }
+            }
+        }
+
+        // inward facing winding gives negated integrals
+        if volume < 0.0 {
+            volume = -volume;
+            first = first.map(|x| -x);
+            second = second.map(|row| row.map(|x| -x));
+        }
+        if volume <= f64::EPSILON {
+            return Self {
+                area,
+                centroid: reference,
+                ..Default::default()
+            };
+        }
+        let offset = first.map(|x| x / volume);
+        // moved to the centroid
+        let covariance: [[f64; 3]; 3] = std::array::from_fn(|i| {
+            std::array::from_fn(|j| second[i][j] - volume * offset[i] * offset[j])
+        });
+        Self {
+            volume,
+            area,
+            centroid: add3_f64(reference, offset),
+            inertia: inertia_from_covariance(&covariance),
+        }
+    }
+
+    // properties of several bodies taken as one, moments are moved to the
+    // common centroid with the parallel axis theorem
+    pub fn combine(bodies: &[MassProperties]) -> Self {
+        let volume: f64 = bodies.iter().map(|body| body.volume).sum();
+        let area = bodies.iter().map(|body| body.area).sum();
+        if volume <= f64::EPSILON {
+            return Self {
+                area,
+                ..Default::default()
+            };
+        }
+        let mut centroid = [0.0; 3];
+        for body in bodies {
+            for (sum, x) in centroid.iter_mut().zip(body.centroid) {
+                *sum += x * body.volume / volume;
+            }
+        }
+        let mut inertia = [[0.0; 3]; 3];
+        for body in bodies {
+            let d = sub3_f64(body.centroid, centroid);
+            let d_sq = dot3_f64(d, d);
+            for i in 0..3 {
+                for j in 0..3 {
+                    let identity = if i == j { 1.0 } else { 0.0 };
+                    inertia[i][j] +=
+                        body.inertia[i][j] + body.volume * (d_sq * identity - d[i] * d[j]);
+                }
+            }
+        }
+        Self {
+            volume,
+            area,
+            centroid,
+            inertia,
+        }
+    }
+
+    // (principal moments ascending, unit principal axes in the same order)
+    pub fn principal_axes(&self) -> ([f64; 3], [[f64; 3]; 3]) {
+        symmetric_eigen(self.inertia)
+    }
+}
+
+// I = tr(C) Id - C for the second moment C
+fn inertia_from_covariance(covariance: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let trace = covariance[0][0] + covariance[1][1] + covariance[2][2];
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let identity = if i == j { trace } else { 0.0 };
+            identity - covariance[i][j]
+        })
+    })
+}
+
+// cyclic Jacobi rotations, returns the eigenvalues ascending with their unit
+// eigenvectors
+fn symmetric_eigen(matrix: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut a = matrix;
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    let scale: f64 = a.iter().flatten().map(|x| x * x).sum();
+    for _ in 0..32 {
+        let off = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off <= scale * 1e-30 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in a.iter_mut() {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+            for row in v.iter_mut() {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+        }
+    }
+    let mut order = [0, 1, 2];
+    order.sort_by(|x, y| a[*x][*x].total_cmp(&a[*y][*y]));
+    (
+        order.map(|k| a[k][k]),
+        order.map(|k| [v[0][k], v[1][k], v[2][k]]),
+    )
+}
+
+fn transform(matrix: &[f32; 16], point: [f32; 3]) -> [f64; 3] {
+    let m = |i: usize| matrix[i] as f64;
+    let p = point.map(|x| x as f64);
+    std::array::from_fn(|row| m(row) * p[0] + m(4 + row) * p[1] + m(8 + row) * p[2] + m(12 + row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cuboid;
+    use std::sync::Arc;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    fn assert_diagonal(inertia: [[f64; 3]; 3], expected: [f64; 3]) {
+        for (i, row) in inertia.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                assert_close(*value, if i == j { expected[i] } else { 0.0 });
+            }
+        }
+    }
+
+    #[test]
+    fn box_properties() {
+        let properties = MassProperties::of_part(&cuboid([1.0, 2.0, 3.0], [10.0, 0.0, -5.0]));
+        assert_close(properties.volume, 6.0);
+        assert_close(properties.area, 22.0);
+        for (axis, expected) in [10.5, 1.0, -3.5].into_iter().enumerate() {
+            assert_close(properties.centroid[axis], expected);
+        }
+        // V (b² + c²) / 12 ...
+        assert_diagonal(properties.inertia, [6.5, 5.0, 2.5]);
+    }
+
+    #[test]
+    fn winding_doesnt_matter() {
+        let mut part = cuboid([1.0, 2.0, 3.0], [0.0; 3]);
+        for triangle in Arc::make_mut(&mut part.mesh).indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        let flipped = MassProperties::of_part(&part);
+        assert_close(flipped.volume, 6.0);
+        assert_diagonal(flipped.inertia, [6.5, 5.0, 2.5]);
+    }
+
+    #[test]
+    fn combined_bodies() {
+        let halves = [
+            MassProperties::of_part(&cuboid([1.0, 1.0, 1.0], [0.0; 3])),
+            MassProperties::of_part(&cuboid([1.0, 1.0, 1.0], [1.0, 0.0, 0.0])),
+        ];
+        let whole = MassProperties::combine(&halves);
+        let expected = MassProperties::of_part(&cuboid([2.0, 1.0, 1.0], [0.0; 3]));
+        assert_close(whole.volume, expected.volume);
+        assert_close(whole.area, 12.0);
+        for axis in 0..3 {
+            assert_close(whole.centroid[axis], expected.centroid[axis]);
+            for other in 0..3 {
+                assert_close(whole.inertia[axis][other], expected.inertia[axis][other]);
+            }
+        }
+        assert_eq!(MassProperties::combine(&[]), MassProperties::default());
+    }
+
+    #[test]
+    fn principal_axes_of_a_rotated_box() {
+        let mut part = cuboid([1.0, 2.0, 3.0], [0.0; 3]);
+        // 30° around z
+        let (sin, cos) = (30f32.to_radians().sin(), 30f32.to_radians().cos());
+        part.model_matrix[0] = cos;
+        part.model_matrix[1] = sin;
+        part.model_matrix[4] = -sin;
+        part.model_matrix[5] = cos;
+        let properties = MassProperties::of_part(&part);
+        let (moments, axes) = properties.principal_axes();
+        for (moment, expected) in moments.iter().zip([2.5, 5.0, 6.5]) {
+            assert!((moment - expected).abs() < 1e-5, "{moments:?}");
+        }
+        // smallest moment around the long side, z stays z
+        assert!((axes[0][2].abs() - 1.0).abs() < 1e-6, "{axes:?}");
+        // the largest one around the rotated x axis
+        let x = [cos as f64, sin as f64, 0.0];
+        assert!((dot3_f64(axes[2], x).abs() - 1.0).abs() < 1e-6, "{axes:?}");
+        // I v = λ v
+        for (moment, axis) in moments.iter().zip(axes) {
+            for i in 0..3 {
+                let product = dot3_f64(properties.inertia[i], axis);
+                assert!((product - moment * axis[i]).abs() < 1e-5);
+            }
+        }
+    }
+}
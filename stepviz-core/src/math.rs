@@ -1,6 +1,7 @@
 use crate::trace_span;
 // meth iz hard
 // fuck math
+// right handed, depth mapped to [0, 1] as WebGPU clips it
 #[inline(always)]
 pub fn create_perspective_matrix(fov_y: f32, aspect: f32, near: f32, far: f32) -> [f32; 16] {
     trace_span!("create_perspective_matrix");
@@ -18,11 +19,11 @@ pub fn create_perspective_matrix(fov_y: f32, aspect: f32, near: f32, far: f32) -
         0.0,
         0.0,
         0.0,
-        (far + near) * nf,
+        far * nf,
         -1.0,
         0.0,
         0.0,
-        (2.0 * far * near) * nf,
+        far * near * nf,
         0.0,
     ]
 }
@@ -52,8 +53,9 @@ pub fn create_look_at_matrix(eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> [
     let ty = -(u[0] * eye[0] + u[1] * eye[1] + u[2] * eye[2]);
     let tz = -(-f[0] * eye[0] + -f[1] * eye[1] + -f[2] * eye[2]);
 
+    // rows s, u, -f (column-major storage)
     [
-        s[0], s[1], s[2], 0.0, u[0], u[1], u[2], 0.0, -f[0], -f[1], -f[2], 0.0, tx, ty, tz, 1.0,
+        s[0], u[0], -f[0], 0.0, s[1], u[1], -f[1], 0.0, s[2], u[2], -f[2], 0.0, tx, ty, tz, 1.0,
     ]
 }
 
@@ -73,6 +75,106 @@ pub fn multiply_matrices(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
     result
 }
 
+// column-major matrix applied to a point (w = 1)
+#[inline(always)]
+pub fn transform_point(m: &[f32; 16], p: [f32; 3]) -> [f32; 3] {
+    [
+        m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12],
+        m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13],
+        m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14],
+    ]
+}
+
+#[inline(always)]
+pub fn add3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[inline(always)]
+pub fn sub3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+#[inline(always)]
+pub fn scale3(a: [f32; 3], k: f32) -> [f32; 3] {
+    [a[0] * k, a[1] * k, a[2] * k]
+}
+
+#[inline(always)]
+pub fn dot3(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline(always)]
+pub fn cross3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[inline(always)]
+pub fn length3(a: [f32; 3]) -> f32 {
+    dot3(a, a).sqrt()
+}
+
+// zero vectors are returned as is
+#[inline(always)]
+pub fn normalize3(a: [f32; 3]) -> [f32; 3] {
+    let len = length3(a);
+    if len > 0.0 { scale3(a, 1.0 / len) } else { a }
+}
+
+// f64 versions for the geometry code (mass properties, sections, measurements,
+// placements), which works in file units
+#[inline(always)]
+pub fn add3_f64(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+#[inline(always)]
+pub fn sub3_f64(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+#[inline(always)]
+pub fn scale3_f64(a: [f64; 3], k: f64) -> [f64; 3] {
+    [a[0] * k, a[1] * k, a[2] * k]
+}
+
+#[inline(always)]
+pub fn dot3_f64(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[inline(always)]
+pub fn cross3_f64(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[inline(always)]
+pub fn length3_f64(a: [f64; 3]) -> f64 {
+    dot3_f64(a, a).sqrt()
+}
+
+// None for (nearly) zero vectors
+#[inline(always)]
+pub fn try_normalize3_f64(a: [f64; 3]) -> Option<[f64; 3]> {
+    let len = length3_f64(a);
+    (len > f64::EPSILON).then(|| scale3_f64(a, 1.0 / len))
+}
+
+// (nearly) zero vectors are returned as is
+#[inline(always)]
+pub fn normalize3_f64(a: [f64; 3]) -> [f64; 3] {
+    try_normalize3_f64(a).unwrap_or(a)
+}
+
 //FIXME : replace above, but first fix result,
 // it doesn't yield same output as multiply_matrices
 //TODO : fix using hints below
@@ -0,0 +1,418 @@
+use serde::{Deserialize, Serialize};
+
+use crate::brep::FaceSurface;
+use crate::math::{add3_f64, dot3_f64, normalize3_f64, scale3_f64, sub3_f64, transform_point};
+use crate::render::RenderablePart;
+use crate::trace_span;
+
+// A measurement taken on the model, in world coordinates and model units
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Measurement {
+    Distance {
+        from: [f64; 3],
+        to: [f64; 3],
+    },
+    // a B-rep edge, `points` is its polyline
+    EdgeLength {
+        points: Vec<[f64; 3]>,
+    },
+    // between two planar faces, picked at `anchors`, with outward `normals`
+    Angle {
+        anchors: [[f64; 3]; 2],
+        normals: [[f64; 3]; 2],
+    },
+    // of a cylindrical face, `center` is the axis point closest to `point`
+    Radius {
+        center: [f64; 3],
+        point: [f64; 3],
+        radius: f64,
+    },
+}
+
+impl Measurement {
+    // distance, edge length or radius in model units, angle in degrees
+    pub fn value(&self) -> f64 {
+        match self {
+            Measurement::Distance { from, to } => distance(*from, *to),
+            Measurement::EdgeLength { points } => points
+                .windows(2)
+                .map(|segment| distance(segment[0], segment[1]))
+                .sum(),
+            Measurement::Angle { normals, .. } => {
+                let cos = dot3_f64(normals[0], normals[1]).clamp(-1.0, 1.0);
+                cos.acos().to_degrees()
+            }
+            Measurement::Radius { radius, .. } => *radius,
+        }
+    }
+
+    pub fn label(&self, units: Option<&str>) -> String {
+        let units = units.map(|units| format!(" {units}")).unwrap_or_default();
+        let value = self.value();
+        match self {
+            Measurement::Distance { .. } => format!("{value:.3}{units}"),
+            Measurement::EdgeLength { .. } => format!("Edge {value:.3}{units}"),
+            Measurement::Angle { .. } => format!("{value:.2}°"),
+            Measurement::Radius { .. } => {
+                format!("R {value:.3}{units} (Ø {:.3}{units})", 2.0 * value)
+            }
+        }
+    }
+
+    // polyline drawn for the annotation
+    pub fn outline(&self) -> Vec<[f64; 3]> {
+        match self {
+            Measurement::Distance { from, to } => vec![*from, *to],
+            Measurement::EdgeLength { points } => points.clone(),
+            Measurement::Angle { anchors, .. } => anchors.to_vec(),
+            Measurement::Radius { center, point, .. } => vec![*center, *point],
+        }
+    }
+
+    // where the label is attached
+    pub fn label_anchor(&self) -> [f64; 3] {
+        let outline = self.outline();
+        match self {
+            // halfway along the edge, not between its ends
+            Measurement::EdgeLength { .. } => {
+                let mut remaining = self.value() * 0.5;
+                for segment in outline.windows(2) {
+                    let length = distance(segment[0], segment[1]);
+                    if length >= remaining && length > 0.0 {
+                        return lerp(segment[0], segment[1], remaining / length);
+                    }
+                    remaining -= length;
+                }
+                outline.last().copied().unwrap_or_default()
+            }
+            _ => lerp(outline[0], outline[outline.len() - 1], 0.5),
+        }
+    }
+}
+
+// Point the user meant when clicking `hit` on `triangle` of `part`: the
+// closest tessellation vertex of that triangle or B-rep edge end within
+// `tolerance`, else the closest point on a B-rep edge, else `hit` itself
+pub fn snap_point(
+    part: &RenderablePart,
+    triangle: usize,
+    hit: [f32; 3],
+    tolerance: f32,
+) -> [f64; 3] {
+    trace_span!("snap_point");
+    let hit = widen(hit);
+    let tolerance = tolerance as f64;
+    let corners = part
+        .mesh
+        .indices
+        .get(triangle * 3..triangle * 3 + 3)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|&index| part.mesh.vertices.get(index as usize))
+        .map(|vertex| to_world(part, vertex.position));
+    let edge_ends = part
+        .mesh
+        .edges
+        .iter()
+        .filter_map(|edge| Some([*edge.first()?, *edge.last()?]))
+        .flatten()
+        .map(|point| to_world(part, point));
+    if let Some(vertex) = closest(corners.chain(edge_ends), hit, tolerance) {
+        return vertex;
+    }
+    let on_edges = part
+        .mesh
+        .edges
+        .iter()
+        .filter_map(|edge| closest_on_polyline(&world_polyline(part, edge), hit));
+    closest(on_edges, hit, tolerance).unwrap_or(hit)
+}
+
+// B-rep edge of `part` passing within `tolerance` of `point`, as a world polyline
+pub fn edge_near(part: &RenderablePart, point: [f32; 3], tolerance: f32) -> Option<Vec<[f64; 3]>> {
+    trace_span!("edge_near");
+    let point = widen(point);
+    part.mesh
+        .edges
+        .iter()
+        .map(|edge| world_polyline(part, edge))
+        .filter_map(|edge| Some((distance(closest_on_polyline(&edge, point)?, point), edge)))
+        .filter(|(gap, _)| *gap <= tolerance as f64)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, edge)| edge)
+}
+
+// analytic surface of the face `triangle` was tessellated from
+pub fn face_surface_at(part: &RenderablePart, triangle: usize) -> Option<FaceSurface> {
+    part.mesh
+        .faces
+        .iter()
+        .find(|face| face.contains_triangle(triangle))
+        .map(|face| face.surface)
+}
+
+// (anchor, outward normal) in world space when `triangle` lies on a plane
+pub fn plane_at(
+    part: &RenderablePart,
+    triangle: usize,
+    hit: [f32; 3],
+) -> Option<([f64; 3], [f64; 3])> {
+    match face_surface_at(part, triangle)? {
+        FaceSurface::Plane { normal, .. } => {
+            Some((widen(hit), normalize3_f64(to_world_direction(part, normal))))
+        }
+        _ => None,
+    }
+}
+
+pub fn radius_at(part: &RenderablePart, triangle: usize, hit: [f32; 3]) -> Option<Measurement> {
+    let FaceSurface::Cylinder {
+        origin,
+        axis,
+        radius,
+    } = face_surface_at(part, triangle)?
+    else {
+        return None;
+    };
+    let origin = transform_f64(&part.model_matrix, origin, 1.0);
+    let axis = normalize3_f64(to_world_direction(part, axis));
+    let point = widen(hit);
+    let along = dot3_f64(sub3_f64(point, origin), axis);
+    let center = add3_f64(origin, scale3_f64(axis, along));
+    // the snapped-out annotation ends on the surface, not on the facets
+    let outward = normalize3_f64(sub3_f64(point, center));
+    Some(Measurement::Radius {
+        center,
+        point: add3_f64(center, scale3_f64(outward, radius)),
+        radius,
+    })
+}
+
+fn closest(
+    candidates: impl Iterator<Item = [f64; 3]>,
+    target: [f64; 3],
+    tolerance: f64,
+) -> Option<[f64; 3]> {
+    candidates
+        .map(|candidate| (distance(candidate, target), candidate))
+        .filter(|(gap, _)| *gap <= tolerance)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+// None for an empty polyline
+fn closest_on_polyline(polyline: &[[f64; 3]], point: [f64; 3]) -> Option<[f64; 3]> {
+    polyline
+        .windows(2)
+        .map(|segment| closest_on_segment(segment[0], segment[1], point))
+        .min_by(|a, b| distance(*a, point).total_cmp(&distance(*b, point)))
+        .or_else(|| polyline.first().copied())
+}
+
+fn closest_on_segment(a: [f64; 3], b: [f64; 3], point: [f64; 3]) -> [f64; 3] {
+    let ab = sub3_f64(b, a);
+    let length_sq = dot3_f64(ab, ab);
+    if length_sq <= f64::EPSILON {
+        return a;
+    }
+    lerp(
+        a,
+        b,
+        (dot3_f64(sub3_f64(point, a), ab) / length_sq).clamp(0.0, 1.0),
+    )
+}
+
+fn world_polyline(part: &RenderablePart, edge: &[[f32; 3]]) -> Vec<[f64; 3]> {
+    edge.iter().map(|point| to_world(part, *point)).collect()
+}
+
+fn to_world(part: &RenderablePart, point: [f32; 3]) -> [f64; 3] {
+    widen(transform_point(&part.model_matrix, point))
+}
+
+// placements are rigid, so directions only need the rotation part
+fn to_world_direction(part: &RenderablePart, direction: [f64; 3]) -> [f64; 3] {
+    transform_f64(&part.model_matrix, direction, 0.0)
+}
+
+fn transform_f64(matrix: &[f32; 16], v: [f64; 3], w: f64) -> [f64; 3] {
+    let m = |i: usize| matrix[i] as f64;
+    std::array::from_fn(|row| {
+        m(row) * v[0] + m(4 + row) * v[1] + m(8 + row) * v[2] + m(12 + row) * w
+    })
+}
+
+fn widen(v: [f32; 3]) -> [f64; 3] {
+    v.map(|x| x as f64)
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let d = sub3_f64(a, b);
+    dot3_f64(d, d).sqrt()
+}
+
+fn lerp(a: [f64; 3], b: [f64; 3], t: f64) -> [f64; 3] {
+    add3_f64(a, scale3_f64(sub3_f64(b, a), t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuVertex, PartFace, PartMesh};
+    use std::sync::Arc;
+
+    // unit square in the z = 0 plane (two triangles), moved by (10, 0, 0),
+    // with its bottom edge as B-rep edge
+    fn square() -> RenderablePart {
+        let vertex = |x: f32, y: f32| GpuVertex {
+            position: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        };
+        let mut part = RenderablePart {
+            mesh: Arc::new(PartMesh {
+                vertices: vec![
+                    vertex(0.0, 0.0),
+                    vertex(1.0, 0.0),
+                    vertex(1.0, 1.0),
+                    vertex(0.0, 1.0),
+                ],
+                indices: vec![0, 1, 2, 0, 2, 3],
+                faces: vec![PartFace {
+                    first_index: 0,
+                    index_count: 6,
+                    surface: FaceSurface::Plane {
+                        origin: [0.0; 3],
+                        normal: [0.0, 0.0, 1.0],
+                    },
+                }],
+                edges: vec![vec![[0.0, 0.0, 0.0], [0.5, 0.0, 0.0], [1.0, 0.0, 0.0]]],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        part.model_matrix[12] = 10.0;
+        part
+    }
+
+    fn assert_near(a: [f64; 3], b: [f64; 3]) {
+        for axis in 0..3 {
+            assert!((a[axis] - b[axis]).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn snapping_prefers_vertices_then_edges() {
+        let part = square();
+        // near the corner (1, 1)
+        assert_near(
+            snap_point(&part, 0, [10.95, 0.97, 0.0], 0.1),
+            [11.0, 1.0, 0.0],
+        );
+        // near the bottom edge, away from its ends
+        assert_near(
+            snap_point(&part, 0, [10.3, 0.05, 0.0], 0.1),
+            [10.3, 0.0, 0.0],
+        );
+        // nothing close, the hit is kept
+        assert_near(
+            snap_point(&part, 0, [10.4, 0.5, 0.0], 0.1),
+            [10.4, 0.5, 0.0],
+        );
+    }
+
+    #[test]
+    fn edges_are_found_and_measured() {
+        let part = square();
+        let edge = edge_near(&part, [10.5, 0.05, 0.0], 0.1).unwrap();
+        let measurement = Measurement::EdgeLength { points: edge };
+        assert!((measurement.value() - 1.0).abs() < 1e-9);
+        assert_near(measurement.label_anchor(), [10.5, 0.0, 0.0]);
+        assert_eq!(edge_near(&part, [10.5, 0.5, 0.0], 0.1), None);
+    }
+
+    #[test]
+    fn empty_edges_are_skipped() {
+        let mut part = square();
+        Arc::make_mut(&mut part.mesh).edges.insert(0, Vec::new());
+        assert_near(
+            snap_point(&part, 0, [10.3, 0.05, 0.0], 0.1),
+            [10.3, 0.0, 0.0],
+        );
+        assert!(edge_near(&part, [10.5, 0.05, 0.0], 0.1).is_some());
+    }
+
+    #[test]
+    fn angles_between_planes() {
+        let mut part = square();
+        let (anchor, normal) = plane_at(&part, 1, [10.5, 0.5, 0.0]).unwrap();
+        assert_near(anchor, [10.5, 0.5, 0.0]);
+        assert_near(normal, [0.0, 0.0, 1.0]);
+
+        // the same square turned 90 degrees around x
+        part.model_matrix[5] = 0.0;
+        part.model_matrix[6] = 1.0;
+        part.model_matrix[9] = -1.0;
+        part.model_matrix[10] = 0.0;
+        let (_, turned) = plane_at(&part, 1, [10.5, 0.0, 0.5]).unwrap();
+        assert_near(turned, [0.0, -1.0, 0.0]);
+
+        let angle = Measurement::Angle {
+            anchors: [anchor, anchor],
+            normals: [normal, turned],
+        };
+        assert!((angle.value() - 90.0).abs() < 1e-9);
+        assert_eq!(angle.label(Some("mm")), "90.00°");
+    }
+
+    #[test]
+    fn radius_of_cylinders() {
+        let mut part = square();
+        Arc::make_mut(&mut part.mesh).faces[0].surface = FaceSurface::Cylinder {
+            origin: [0.0, 0.0, 0.0],
+            axis: [0.0, 0.0, 1.0],
+            radius: 2.0,
+        };
+        assert_eq!(plane_at(&part, 0, [10.0, 0.0, 0.0]), None);
+        let Some(Measurement::Radius {
+            center,
+            point,
+            radius,
+        }) = radius_at(&part, 0, [10.0, 1.9, 3.0])
+        else {
+            panic!("no radius");
+        };
+        assert_near(center, [10.0, 0.0, 3.0]);
+        assert_near(point, [10.0, 2.0, 3.0]);
+        assert_eq!(radius, 2.0);
+        let measurement = Measurement::Radius {
+            center,
+            point,
+            radius,
+        };
+        assert_eq!(measurement.label(Some("mm")), "R 2.000 mm (Ø 4.000 mm)");
+    }
+
+    #[test]
+    fn distances() {
+        let measurement = Measurement::Distance {
+            from: [0.0, 0.0, 0.0],
+            to: [3.0, 4.0, 0.0],
+        };
+        assert_eq!(measurement.value(), 5.0);
+        assert_eq!(measurement.label(None), "5.000");
+        assert_near(measurement.label_anchor(), [1.5, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn labels_use_the_length_unit() {
+        let measurement = Measurement::Distance {
+            from: [0.0; 3],
+            to: [0.0, 0.0, 12.3],
+        };
+        let mut metadata = crate::fixtures::sample_model("units").metadata;
+        assert_eq!(measurement.label(metadata.length_unit()), "12.300 mm");
+        // saved before lengths were resolved, the angle unit is dropped
+        metadata.units = Some("steradian".to_string());
+        assert_eq!(measurement.label(metadata.length_unit()), "12.300");
+    }
+}
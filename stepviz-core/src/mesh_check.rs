@@ -0,0 +1,251 @@
+use crate::trace_span;
+use std::collections::HashMap;
+
+use super::render::RenderablePart;
+
+// vertices closer than this fraction of the part's bounding box diagonal are
+// the same point, faces are tessellated separately and don't share vertices
+const WELD_TOLERANCE: f32 = 1e-5;
+
+// Topology of a tessellated part once coincident vertices are welded. Edges
+// are segments in part coordinates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshCheck {
+    // distinct points left after welding
+    pub welded_vertices: usize,
+    // triangles with two corners welded together or without area
+    pub degenerate_triangles: usize,
+    // edges used by a single triangle
+    pub open_edges: Vec<[[f32; 3]; 2]>,
+    // edges shared by more than two triangles
+    pub non_manifold_edges: Vec<[[f32; 3]; 2]>,
+    // edges whose two triangles run them the same way
+    pub flipped_edges: Vec<[[f32; 3]; 2]>,
+}
+
+impl MeshCheck {
+    pub fn of_part(part: &RenderablePart) -> Self {
+        trace_span!("MeshCheck::of_part");
+        let positions: Vec<[f32; 3]> = part.mesh.vertices.iter().map(|v| v.position).collect();
+        let (welded, points) = weld(&positions);
+
+        let mut degenerate_triangles = 0;
+        // welded edge (low, high) -> directions it is used in, true for low -> high
+        let mut edges: HashMap<(u32, u32), Vec<bool>> = HashMap::new();
+        for triangle in part.mesh.indices.chunks_exact(3) {
+            let Some(corners) = triangle
+                .iter()
+                .map(|&index| welded.get(index as usize).copied())
+                .collect::<Option<Vec<u32>>>()
+            else {
+                degenerate_triangles += 1;
+                continue;
+            };
+            let [a, b, c] = [corners[0], corners[1], corners[2]];
+            if a == b || b == c || a == c || is_flat(&points, a, b, c) {
+                degenerate_triangles += 1;
+                continue;
+            }
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                edges
+                    .entry((from.min(to), from.max(to)))
+                    .or_default()
+                    .push(from < to);
+            }
+        }
+
+        let mut check = Self {
+            welded_vertices: points.len(),
+            degenerate_triangles,
+            ..Default::default()
+        };
+        let mut sorted: Vec<_> = edges.into_iter().collect();
+        sorted.sort_unstable_by_key(|(edge, _)| *edge);
+        for ((low, high), uses) in sorted {
+            let segment = [points[low as usize], points[high as usize]];
+            match uses.as_slice() {
+                [_] => check.open_edges.push(segment),
+                [first, second] if first == second => check.flipped_edges.push(segment),
+                [_, _] => {}
+                _ => check.non_manifold_edges.push(segment),
+            }
+        }
+        check
+    }
+
+    // every edge joins exactly two triangles
+    pub fn is_closed(&self) -> bool {
+        self.open_edges.is_empty() && self.non_manifold_edges.is_empty()
+    }
+
+    // the enclosed volume only means something for a closed, consistently
+    // oriented shell
+    pub fn volume_reliable(&self) -> bool {
+        self.is_closed() && self.flipped_edges.is_empty()
+    }
+
+    pub fn problem_edges(&self) -> impl Iterator<Item = &[[f32; 3]; 2]> {
+        self.open_edges
+            .iter()
+            .chain(&self.non_manifold_edges)
+            .chain(&self.flipped_edges)
+    }
+
+    // one line summary for the panels, empty when nothing is wrong
+    pub fn summary(&self) -> String {
+        [
+            (self.open_edges.len(), "open"),
+            (self.non_manifold_edges.len(), "non-manifold"),
+            (self.flipped_edges.len(), "flipped"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, kind)| format!("{count} {kind} edges"))
+        .chain(
+            (self.degenerate_triangles > 0)
+                .then(|| format!("{} degenerate triangles", self.degenerate_triangles)),
+        )
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+// (welded index of every vertex, welded points). Points are hashed on a grid
+// of the tolerance, neighbouring cells are searched so points on both sides
+// of a cell border still meet.
+fn weld(positions: &[[f32; 3]]) -> (Vec<u32>, Vec<[f32; 3]>) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for position in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+    let diagonal = (0..3)
+        .map(|axis| (max[axis] - min[axis]).powi(2))
+        .sum::<f32>()
+        .sqrt();
+    let tolerance = (diagonal * WELD_TOLERANCE).max(f32::MIN_POSITIVE);
+    let cell = |p: &[f32; 3]| p.map(|x| (x / tolerance).floor() as i64);
+
+    let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut points: Vec<[f32; 3]> = Vec::new();
+    let welded = positions
+        .iter()
+        .map(|position| {
+            let key = cell(position);
+            let mut found = None;
+            'search: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let neighbour = [key[0] + dx, key[1] + dy, key[2] + dz];
+                        for &index in grid.get(&neighbour).into_iter().flatten() {
+                            let point = points[index as usize];
+                            let distance_sq = (0..3)
+                                .map(|axis| (point[axis] - position[axis]).powi(2))
+                                .sum::<f32>();
+                            if distance_sq <= tolerance * tolerance {
+                                found = Some(index);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            found.unwrap_or_else(|| {
+                let index = points.len() as u32;
+                points.push(*position);
+                grid.entry(key).or_default().push(index);
+                index
+            })
+        })
+        .collect();
+    (welded, points)
+}
+
+// corners on one line, the triangle has no normal
+fn is_flat(points: &[[f32; 3]], a: u32, b: u32, c: u32) -> bool {
+    let [a, b, c] = [a, b, c].map(|i| points[i as usize].map(|x| x as f64));
+    let u: [f64; 3] = std::array::from_fn(|axis| b[axis] - a[axis]);
+    let v: [f64; 3] = std::array::from_fn(|axis| c[axis] - a[axis]);
+    let normal = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let area_sq = normal.iter().map(|x| x * x).sum::<f64>();
+    let scale = u.iter().chain(&v).map(|x| x * x).sum::<f64>();
+    area_sq <= scale * scale * 1e-14
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GpuVertex;
+    use crate::fixtures::cuboid;
+    use std::sync::Arc;
+
+    #[test]
+    fn separate_faces_are_welded_closed() {
+        let mut part = cuboid([1.0; 3], [0.0; 3]);
+        // well below the tolerance
+        Arc::make_mut(&mut part.mesh).vertices[5].position[0] += 1e-7;
+        let check = MeshCheck::of_part(&part);
+        assert_eq!(check.welded_vertices, 8);
+        assert!(check.volume_reliable(), "{check:?}");
+        assert_eq!(check.summary(), "");
+    }
+
+    #[test]
+    fn missing_triangle_leaves_open_edges() {
+        let mut part = cuboid([1.0; 3], [0.0; 3]);
+        let mesh = Arc::make_mut(&mut part.mesh);
+        mesh.indices.truncate(mesh.indices.len() - 3);
+        let check = MeshCheck::of_part(&part);
+        assert_eq!(check.open_edges.len(), 3);
+        assert!(!check.is_closed());
+        assert!(!check.volume_reliable());
+        assert_eq!(check.summary(), "3 open edges");
+    }
+
+    #[test]
+    fn flipped_triangle_is_found() {
+        let mut part = cuboid([1.0; 3], [0.0; 3]);
+        Arc::make_mut(&mut part.mesh).indices.swap(1, 2);
+        let check = MeshCheck::of_part(&part);
+        assert!(check.is_closed());
+        assert_eq!(check.flipped_edges.len(), 3);
+        assert!(!check.volume_reliable());
+    }
+
+    #[test]
+    fn fins_and_degenerate_triangles() {
+        let mut part = cuboid([1.0; 3], [0.0; 3]);
+        let mesh = Arc::make_mut(&mut part.mesh);
+        let base = mesh.vertices.len() as u32;
+        // a fin on the edge (0,0,0)-(1,0,0), and a triangle collapsed to a line
+        for position in [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.5, -1.0, -1.0],
+            [2.0, 2.0, 2.0],
+            [3.0, 3.0, 3.0],
+        ] {
+            mesh.vertices.push(GpuVertex {
+                position,
+                normal: [0.0; 3],
+            });
+        }
+        mesh.indices
+            .extend([base, base + 1, base + 2, base + 3, base + 4, base + 3]);
+        let check = MeshCheck::of_part(&part);
+        assert_eq!(
+            check.non_manifold_edges,
+            [[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]]
+        );
+        assert_eq!(check.open_edges.len(), 2);
+        assert_eq!(check.degenerate_triangles, 1);
+        assert_eq!(check.problem_edges().count(), 3);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::trace_span;
+use std::fmt::Write;
+
+use super::export::ExportMesh;
+
+// Writes `meshes` as one OBJ object each, with the MTL file the OBJ refers to
+// as `mtl_file`. Meshes of the same color share a material. Returns
+// (obj, mtl).
+pub fn write_obj(mtl_file: &str, meshes: &[ExportMesh]) -> (Vec<u8>, Vec<u8>) {
+    trace_span!("write_obj");
+    let mut obj = String::new();
+    let mut mtl = String::new();
+    let mut colors: Vec<[f32; 3]> = Vec::new();
+    let _ = writeln!(obj, "mtllib {mtl_file}");
+    // OBJ indices are 1-based and count over the whole file
+    let mut offset = 1;
+    for mesh in meshes {
+        let _ = writeln!(obj, "o {}", token(&mesh.name));
+        for (index, vertex) in mesh.vertices.iter().enumerate() {
+            let [x, y, z] = vertex.position;
+            if mesh.vertex_colors.is_empty() {
+                let _ = writeln!(obj, "v {x} {y} {z}");
+            } else {
+                // the vertex color extension MeshLab and Blender read
+                let [r, g, b] = mesh.vertex_color(index);
+                let _ = writeln!(obj, "v {x} {y} {z} {r} {g} {b}");
+            }
+        }
+        for vertex in &mesh.vertices {
+            let [x, y, z] = vertex.normal;
+            let _ = writeln!(obj, "vn {x} {y} {z}");
+        }
+
+        let material = match colors.iter().position(|color| *color == mesh.color) {
+            Some(material) => material,
+            None => {
+                let [r, g, b] = mesh.color;
+                let _ = writeln!(mtl, "newmtl color_{}", colors.len() + 1);
+                let _ = writeln!(mtl, "Ka 0 0 0");
+                let _ = writeln!(mtl, "Kd {r} {g} {b}");
+                let _ = writeln!(mtl, "Ks 0 0 0");
+                let _ = writeln!(mtl, "d 1");
+                let _ = writeln!(mtl, "illum 1");
+                let _ = writeln!(mtl);
+                colors.push(mesh.color);
+                colors.len() - 1
+            }
+        };
+        let _ = writeln!(obj, "usemtl color_{}", material + 1);
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize + offset);
+            let _ = writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}");
+        }
+        offset += mesh.vertices.len();
+    }
+    (obj.into_bytes(), mtl.into_bytes())
+}
+
+// object names end at the line, some readers also split them at spaces
+fn token(name: &str) -> String {
+    let name = name.split_whitespace().collect::<Vec<_>>().join("_");
+    if name.is_empty() {
+        "part".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ExportOptions, export_meshes};
+    use crate::fixtures::sample_model;
+    use std::sync::Arc;
+
+    fn values(line: &str) -> Vec<f32> {
+        line.split_whitespace()
+            .skip(1)
+            .map(|value| value.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn objects_share_vertices_and_materials() {
+        let mut model = sample_model("a");
+        let mut second = model.render_parts[0].clone();
+        Arc::make_mut(&mut second.mesh).vertex_colors.clear();
+        second.color = [0.5, 0.25, 1.0];
+        second.node_id = None;
+        model.render_parts.push(second.clone());
+        second.model_matrix[12] = 4.0;
+        model.render_parts.push(second);
+        let options = ExportOptions::default();
+        let meshes = export_meshes(&model, &[0, 2, 3], options);
+
+        let (obj, mtl) = write_obj("a.mtl", &meshes);
+        let obj = String::from_utf8(obj).unwrap();
+        let mtl = String::from_utf8(mtl).unwrap();
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(lines[0], "mtllib a.mtl");
+        let objects: Vec<&str> = lines
+            .iter()
+            .filter(|l| l.starts_with("o "))
+            .copied()
+            .collect();
+        assert_eq!(objects, ["o part", "o Mesh_3", "o Mesh_4"]);
+
+        let vertices: Vec<Vec<f32>> = lines
+            .iter()
+            .filter(|l| l.starts_with("v "))
+            .map(|l| values(l))
+            .collect();
+        assert_eq!(vertices.len(), 9);
+        // colored vertices carry their color, the moved copy is in the world
+        assert_eq!(vertices[1], [1.0, 2.0, -1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(vertices[7], [5.0, 2.0, -1.0]);
+        assert_eq!(lines.iter().filter(|l| l.starts_with("vn ")).count(), 9);
+
+        let faces: Vec<&str> = lines
+            .iter()
+            .filter(|l| l.starts_with("f "))
+            .copied()
+            .collect();
+        assert_eq!(
+            faces,
+            ["f 1//1 2//2 3//3", "f 4//4 5//5 6//6", "f 7//7 8//8 9//9"]
+        );
+        let used: Vec<&str> = lines
+            .iter()
+            .filter(|l| l.starts_with("usemtl"))
+            .copied()
+            .collect();
+        assert_eq!(used, ["usemtl color_1", "usemtl color_2", "usemtl color_2"]);
+        assert_eq!(mtl.matches("newmtl").count(), 2);
+        assert!(mtl.contains("newmtl color_2\nKa 0 0 0\nKd 0.5 0.25 1\n"));
+    }
+}
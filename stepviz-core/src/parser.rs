@@ -0,0 +1,123 @@
+use crate::trace_span;
+use ruststep::ast::{EntityInstance, Exchange, Parameter, Record};
+use ruststep::header::Header;
+
+use super::entities::{param_str, params_list};
+use super::error::StepVizError;
+use super::types::StepHeader;
+
+pub fn convert_header(header_in: &[Record]) -> Result<StepHeader, StepVizError> {
+    trace_span!("convert_header");
+    let header_in = Header::from_records(header_in).map_err(|e| StepVizError::Header {
+        message: e.to_string(),
+    })?;
+    let file_description = header_in.file_description.description;
+    Ok(StepHeader {
+        file_description: file_description.join("; "),
+        implementation_level: header_in.file_description.implementation_level,
+        file_name: header_in.file_name.name,
+        time_stamp: header_in.file_name.time_stamp,
+        author: header_in.file_name.author,
+        organization: header_in.file_name.organization,
+        preprocessor_version: header_in.file_name.preprocessor_version,
+        originating_system: header_in.file_name.originating_system,
+        authorization: header_in.file_name.authorization,
+        file_schema: header_in.file_schema.schema.join("; "),
+    })
+}
+
+// The length unit of the file, as a short label ("mm", "in"). Plane and solid
+// angle units are SI_UNITs as well, so only the complex instances that also
+// carry LENGTH_UNIT() count:
+//   #4=(LENGTH_UNIT()NAMED_UNIT(*)SI_UNIT(.MILLI.,.METRE.));
+//   #9=(CONVERSION_BASED_UNIT('INCH',#8)LENGTH_UNIT()NAMED_UNIT(#7));
+pub fn parse_length_unit(exchange: &Exchange) -> Option<String> {
+    trace_span!("parse_length_unit");
+    exchange
+        .data
+        .iter()
+        .flat_map(|section| &section.entities)
+        .find_map(|entity| match entity {
+            EntityInstance::Complex { subsuper, .. } => {
+                let records = &subsuper.0;
+                records
+                    .iter()
+                    .any(|record| record.name.eq_ignore_ascii_case("LENGTH_UNIT"))
+                    .then(|| records.iter().find_map(length_unit_from_record))
+                    .flatten()
+            }
+            EntityInstance::Simple { .. } => None,
+        })
+}
+
+fn param_to_enum<'a>(param: &'a Parameter) -> Option<&'a str> {
+    if let Parameter::Enumeration(value) = param {
+        Some(value.as_str())
+    } else {
+        None
+    }
+}
+
+fn length_unit_from_record(record: &Record) -> Option<String> {
+    let params = params_list(&record.parameter)?;
+    if record.name.eq_ignore_ascii_case("CONVERSION_BASED_UNIT") {
+        let name = params.first().and_then(param_str)?;
+        return Some(match name.to_ascii_uppercase().as_str() {
+            "INCH" => "in".to_string(),
+            "FOOT" | "FEET" => "ft".to_string(),
+            _ => name.to_ascii_lowercase(),
+        });
+    }
+    if !record.name.eq_ignore_ascii_case("SI_UNIT") {
+        return None;
+    }
+    let prefix = params.first().and_then(param_to_enum);
+    let unit = params.get(1).and_then(param_to_enum)?;
+    if unit != "METRE" {
+        return None;
+    }
+    Some(
+        match prefix {
+            Some("MICRO") => "µm",
+            Some("MILLI") => "mm",
+            Some("CENTI") => "cm",
+            Some("DECI") => "dm",
+            Some("KILO") => "km",
+            _ => "m",
+        }
+        .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::step_file;
+
+    fn length_unit(units: &str) -> Option<String> {
+        parse_length_unit(&ruststep::parser::parse(&step_file(&[units])).unwrap())
+    }
+
+    #[test]
+    fn length_unit_skips_angle_units() {
+        let units = "#3=(NAMED_UNIT(*)SI_UNIT($,.STERADIAN.)SOLID_ANGLE_UNIT());\n\
+                     #4=(NAMED_UNIT(*)PLANE_ANGLE_UNIT()SI_UNIT($,.RADIAN.));\n\
+                     #5=(LENGTH_UNIT()NAMED_UNIT(*)SI_UNIT(.MILLI.,.METRE.));\n";
+        assert_eq!(length_unit(units).as_deref(), Some("mm"));
+        let metres = "#5=(LENGTH_UNIT()NAMED_UNIT(*)SI_UNIT($,.METRE.));\n";
+        assert_eq!(length_unit(metres).as_deref(), Some("m"));
+    }
+
+    #[test]
+    fn length_unit_reads_conversion_based_units() {
+        let units = "#7=(LENGTH_UNIT()NAMED_UNIT(*)SI_UNIT(.MILLI.,.METRE.));\n\
+                     #8=LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(25.4),#7);\n\
+                     #9=(CONVERSION_BASED_UNIT('INCH',#8)LENGTH_UNIT()NAMED_UNIT(#6));\n";
+        // the first length unit wins, the inch is defined through millimetres
+        assert_eq!(length_unit(units).as_deref(), Some("mm"));
+        let inch = "#9=(CONVERSION_BASED_UNIT('INCH',#8)LENGTH_UNIT()NAMED_UNIT(#6));\n";
+        assert_eq!(length_unit(inch).as_deref(), Some("in"));
+        let angle_only = "#4=(NAMED_UNIT(*)PLANE_ANGLE_UNIT()SI_UNIT($,.RADIAN.));\n";
+        assert_eq!(length_unit(angle_only), None);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::trace_span;
+use ruststep::ast::Exchange;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::Hasher;
+use truck_stepio::r#in::Table;
+
+use super::assembly::{ShellPlacement, resolve_assembly};
+use super::entities::EntityIndex;
+use super::error::StepVizError;
+use super::parser::{convert_header, parse_length_unit};
+use super::render::{RenderablePart, shell_queue, tessellate_shell};
+use super::styles::{ShellStyle, resolve_shell_styles};
+use super::types::{BoundingBox, Metadata, ProductNode, StepModel};
+use super::validation::{ValidationProperty, collect_validation_properties};
+
+// Everything tessellation needs from one DATA section
+pub struct StepSection {
+    // position of the section in the exchange file
+    pub index: usize,
+    pub table: Table,
+    pub placements: HashMap<u64, Vec<ShellPlacement>>,
+    pub styles: HashMap<u64, ShellStyle>,
+}
+
+pub struct PreparedStep {
+    pub sections: Vec<StepSection>,
+    // product trees of all sections, node ids unique across sections
+    pub product_tree: Vec<ProductNode>,
+    pub section_entity_counts: Vec<usize>,
+    pub validation_properties: Vec<ValidationProperty>,
+    // problems found while resolving the assemblies
+    pub warnings: Vec<StepVizError>,
+}
+
+// Builds geometry tables, placements and styles for every usable DATA section.
+// Edition 3 files may spread geometry over several sections.
+pub fn prepare_sections(exchange: &Exchange) -> PreparedStep {
+    trace_span!("prepare_sections");
+    let mut sections = Vec::new();
+    let mut product_tree = Vec::new();
+    let mut validation_properties = Vec::new();
+    let mut warnings = Vec::new();
+    let mut next_node_id = 0;
+
+    for (index, section) in exchange.data.iter().enumerate() {
+        if section.entities.is_empty() {
+            continue;
+        }
+        let table = Table::from_data_section(section);
+        let entity_index = EntityIndex::from_data_section(section);
+        let shell_ids: Vec<u64> = table.shell.keys().copied().collect();
+        let mut assembly = resolve_assembly(&entity_index, &shell_ids);
+        let styles = resolve_shell_styles(&entity_index, &shell_ids);
+        let mut validation = collect_validation_properties(
+            &entity_index,
+            &assembly.node_pd,
+            &assembly.node_transforms,
+        );
+
+        // node ids restart at 0 per section
+        let offset = next_node_id;
+        for node in &mut assembly.product_tree {
+            next_node_id += node.shift_ids(offset);
+        }
+        for placement in assembly.placements.values_mut().flatten() {
+            placement.node_id = placement.node_id.map(|id| id + offset);
+        }
+        for property in &mut validation {
+            property.node_id += offset;
+        }
+        product_tree.extend(assembly.product_tree);
+        validation_properties.extend(validation);
+        warnings.extend(assembly.warnings);
+
+        sections.push(StepSection {
+            index,
+            table,
+            placements: assembly.placements,
+            styles,
+        });
+    }
+
+    PreparedStep {
+        sections,
+        product_tree,
+        section_entity_counts: exchange
+            .data
+            .iter()
+            .map(|section| section.entities.len())
+            .collect(),
+        validation_properties,
+        warnings,
+    }
+}
+
+// A STEP file parsed and prepared, waiting to be tessellated
+pub struct ParsedStep {
+    pub id: String,
+    pub metadata: Metadata,
+    pub prepared: PreparedStep,
+}
+
+pub fn parse_step(text: &str, file_name: &str) -> Result<ParsedStep, StepVizError> {
+    trace_span!("parse_step");
+    let parsed = ruststep::parser::parse(text)?;
+    if parsed.data.is_empty() {
+        return Err(StepVizError::geometry(
+            None,
+            "no data sections found in the STEP file",
+        ));
+    }
+    let prepared = prepare_sections(&parsed);
+    if prepared.sections.is_empty() {
+        return Err(StepVizError::geometry(
+            None,
+            "STEP file has no usable data sections (empty meta/entities)",
+        ));
+    }
+
+    let mut header = convert_header(&parsed.header)?;
+    if header.file_name.is_empty() {
+        header.file_name = file_name.to_string();
+    }
+    let metadata = Metadata {
+        header,
+        entity_count: prepared.section_entity_counts.iter().sum(),
+        bounding_box: None,
+        units: parse_length_unit(&parsed),
+        vertex_count: 0,
+        triangle_count: 0,
+        volume: None,
+        surface_area: None,
+        section_entity_counts: prepared.section_entity_counts.clone(),
+        validation_properties: prepared.validation_properties.clone(),
+        warnings: prepared.warnings.clone(),
+    };
+    Ok(ParsedStep {
+        id: hash_text_to_id(text),
+        metadata,
+        prepared,
+    })
+}
+
+pub fn hash_text_to_id(text: &str) -> String {
+    trace_span!("hash_text_to_id");
+    let mut hasher = DefaultHasher::new();
+    std::hash::Hash::hash(&text, &mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PipelineProgress {
+    Reading,
+    Parsing,
+    Tessellating { done: usize, total: usize },
+}
+
+impl fmt::Display for PipelineProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineProgress::Reading => write!(f, "Reading file..."),
+            PipelineProgress::Parsing => write!(f, "Parsing STEP..."),
+            PipelineProgress::Tessellating { done, total } => {
+                write!(f, "Tessellating shell {}/{}", done, total)
+            }
+        }
+    }
+}
+
+// Tessellates prepared sections one shell at a time, so callers can report
+// progress and stop between shells.
+pub struct TessellationJob {
+    sections: Vec<StepSection>,
+    queue: Vec<(usize, u64)>,
+    next: usize,
+    part_count: usize,
+}
+
+impl TessellationJob {
+    pub fn new(sections: Vec<StepSection>) -> Self {
+        let queue = shell_queue(&sections);
+        Self {
+            sections,
+            queue,
+            next: 0,
+            part_count: 0,
+        }
+    }
+
+    pub fn progress(&self) -> PipelineProgress {
+        PipelineProgress::Tessellating {
+            done: self.next,
+            total: self.queue.len(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.queue.len()
+    }
+}
+
+impl Iterator for TessellationJob {
+    // parts of one shell, or why it was skipped; later shells still follow
+    type Item = Result<Vec<RenderablePart>, StepVizError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &(section_pos, shell_id) = self.queue.get(self.next)?;
+        self.next += 1;
+        let parts = tessellate_shell(&self.sections[section_pos], shell_id, self.part_count);
+        if let Ok(parts) = &parts {
+            self.part_count += parts.len();
+        }
+        Some(parts)
+    }
+}
+
+// Tessellates every shell, skipped shells become warnings
+pub fn tessellate_all(sections: Vec<StepSection>) -> (Vec<RenderablePart>, Vec<StepVizError>) {
+    let mut parts = Vec::new();
+    let mut warnings = Vec::new();
+    for shell in TessellationJob::new(sections) {
+        match shell {
+            Ok(shell_parts) => parts.extend(shell_parts),
+            Err(warning) => warnings.push(warning),
+        }
+    }
+    (parts, warnings)
+}
+
+// Parses and tessellates a whole file at once, for callers that don't report
+// progress or need to cancel
+pub fn load_step(text: &str, file_name: &str) -> Result<StepModel, StepVizError> {
+    trace_span!("load_step");
+    let parsed = parse_step(text, file_name)?;
+    let (render_parts, warnings) = tessellate_all(parsed.prepared.sections);
+    let mut metadata = parsed.metadata;
+    metadata.warnings.extend(warnings);
+    metadata.bounding_box = BoundingBox::of_parts(&render_parts);
+    metadata.vertex_count = render_parts.iter().map(|p| p.mesh.vertices.len()).sum();
+    metadata.triangle_count = render_parts.iter().map(|p| p.mesh.indices.len() / 3).sum();
+    Ok(StepModel {
+        id: parsed.id,
+        metadata,
+        render_parts,
+        product_tree: parsed.prepared.product_tree,
+        measurements: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{step_cube, step_file};
+
+    #[test]
+    fn sections_are_combined() {
+        let text = step_file(&[&step_cube("small", 1.0), "", &step_cube("large", 2.0)]);
+        let exchange = ruststep::parser::parse(&text).unwrap();
+        let prepared = prepare_sections(&exchange);
+
+        // the empty section is skipped but keeps its position
+        let indices: Vec<usize> = prepared.sections.iter().map(|s| s.index).collect();
+        assert_eq!(indices, [0, 2]);
+        let counts = &prepared.section_entity_counts;
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[1], 0);
+        assert_eq!(counts[0], exchange.data[0].entities.len());
+        assert_eq!(counts[2], counts[0]);
+
+        // both trees number their nodes from 0, the second one is moved on
+        let nodes: Vec<(&str, usize)> = prepared
+            .product_tree
+            .iter()
+            .map(|node| (node.name.as_str(), node.id))
+            .collect();
+        assert_eq!(nodes, [("small", 0), ("large", 1)]);
+
+        let (parts, warnings) = tessellate_all(prepared.sections);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let parts: Vec<(usize, Option<usize>)> = parts
+            .iter()
+            .map(|part| (part.section, part.node_id))
+            .collect();
+        assert_eq!(parts, [(0, Some(0)), (2, Some(1))]);
+
+        let model = load_step(&text, "two.stp").unwrap();
+        assert_eq!(model.metadata.entity_count, counts.iter().sum::<usize>());
+        assert_eq!(model.metadata.section_entity_counts, *counts);
+        let bounds = model.metadata.bounding_box.unwrap();
+        assert_eq!((bounds.min, bounds.max), ([0.0; 3], [2.0; 3]));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::trace_span;
+use std::fmt::Write;
+
+use super::export::ExportMesh;
+
+// Writes `meshes` as one binary little endian PLY mesh. PLY has no groups:
+// every face carries the index of its part in a `part` property and the part
+// names are listed in the header comments.
+pub fn write_ply(name: &str, meshes: &[ExportMesh]) -> Vec<u8> {
+    trace_span!("write_ply");
+    let vertex_count: usize = meshes.iter().map(|mesh| mesh.vertices.len()).sum();
+    let face_count: usize = meshes.iter().map(|mesh| mesh.indices.len() / 3).sum();
+
+    let mut header = String::new();
+    let _ = writeln!(header, "ply");
+    let _ = writeln!(header, "format binary_little_endian 1.0");
+    let _ = writeln!(header, "comment {}", line(name));
+    for (part, mesh) in meshes.iter().enumerate() {
+        let _ = writeln!(header, "comment part {part} {}", line(&mesh.name));
+    }
+    let _ = writeln!(header, "element vertex {vertex_count}");
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        let _ = writeln!(header, "property float {property}");
+    }
+    for property in ["red", "green", "blue"] {
+        let _ = writeln!(header, "property uchar {property}");
+    }
+    let _ = writeln!(header, "element face {face_count}");
+    let _ = writeln!(header, "property list uchar uint vertex_indices");
+    let _ = writeln!(header, "property uint part");
+    let _ = writeln!(header, "end_header");
+
+    let mut out = header.into_bytes();
+    out.reserve(vertex_count * 27 + face_count * 17);
+    for mesh in meshes {
+        for (index, vertex) in mesh.vertices.iter().enumerate() {
+            for value in vertex.position.iter().chain(&vertex.normal) {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            let color = mesh.vertex_color(index);
+            out.extend(color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8));
+        }
+    }
+    let mut offset = 0;
+    for (part, mesh) in meshes.iter().enumerate() {
+        for triangle in mesh.indices.chunks_exact(3) {
+            out.push(3);
+            for index in triangle {
+                out.extend_from_slice(&(index + offset).to_le_bytes());
+            }
+            out.extend_from_slice(&(part as u32).to_le_bytes());
+        }
+        offset += mesh.vertices.len() as u32;
+    }
+    out
+}
+
+// header comments end at the line
+fn line(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ExportOptions, export_meshes};
+    use crate::fixtures::sample_model;
+    use std::sync::Arc;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn f32_at(bytes: &[u8], at: usize) -> f32 {
+        f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_and_elements_match() {
+        let mut model = sample_model("a");
+        let mut second = model.render_parts[0].clone();
+        Arc::make_mut(&mut second.mesh).vertex_colors.clear();
+        second.color = [0.0, 0.0, 1.0];
+        second.model_matrix[13] = -2.0;
+        second.node_id = None;
+        model.render_parts.push(second);
+        let meshes = export_meshes(&model, &[0, 2], ExportOptions::default());
+
+        let ply = write_ply("two\nparts", &meshes);
+        let end = b"end_header\n";
+        let body = ply.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+        let header = std::str::from_utf8(&ply[..body]).unwrap();
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\ncomment two parts\n"));
+        assert!(header.contains("comment part 0 part\ncomment part 1 Mesh 3\n"));
+        assert!(header.contains("element vertex 6\n"));
+        assert!(header.contains("element face 2\n"));
+
+        // 6 vertices of 6 floats and 3 color bytes, 2 faces of 1 + 3 * 4 + 4 bytes
+        assert_eq!(ply.len(), body + 6 * 27 + 2 * 17);
+        let vertex = |i: usize| body + i * 27;
+        assert_eq!(f32_at(&ply, vertex(4)), 1.0);
+        assert_eq!(f32_at(&ply, vertex(4) + 4), 0.0);
+        assert_eq!(f32_at(&ply, vertex(4) + 20), 1.0);
+        assert_eq!(ply[vertex(0) + 24..vertex(0) + 27], [255, 0, 0]);
+        assert_eq!(ply[vertex(5) + 24..vertex(5) + 27], [0, 0, 255]);
+
+        let face = body + 6 * 27 + 17;
+        assert_eq!(ply[face], 3);
+        let corners: Vec<u32> = (0..3).map(|c| u32_at(&ply, face + 1 + c * 4)).collect();
+        assert_eq!(corners, [3, 4, 5]);
+        assert_eq!(u32_at(&ply, face + 13), 1);
+    }
+}
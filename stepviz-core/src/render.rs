@@ -0,0 +1,442 @@
+use crate::trace_span;
+use bytemuck::{Pod, Zeroable};
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use truck_geometry::prelude::*;
+use truck_meshalgo::prelude::*;
+
+use crate::PartFace;
+use crate::brep::face_surface;
+use crate::constants::{COLORS, STEP_TRACER};
+use crate::error::StepVizError;
+use crate::mass::MassProperties;
+use crate::math::transform_point;
+use crate::pipeline::StepSection;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable, PartialEq, Serialize, Deserialize)]
+pub struct GpuVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+// Tessellation of one shell in part coordinates, shared by every placed
+// instance of the shell
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartMesh {
+    pub vertices: Vec<GpuVertex>,
+    pub indices: Vec<u32>,
+    // per-vertex colors from face styles, empty when the whole part uses `color`
+    #[serde(default)]
+    pub vertex_colors: Vec<[f32; 3]>,
+    // B-rep faces behind the triangles, in index order
+    #[serde(default)]
+    pub faces: Vec<PartFace>,
+    // B-rep edges as polylines, in part coordinates
+    #[serde(default)]
+    pub edges: Vec<Vec<[f32; 3]>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderablePart {
+    // flattened, so parts stored before meshes were shared still read
+    #[serde(flatten)]
+    pub mesh: Arc<PartMesh>,
+    pub model_matrix: [f32; 16],
+    pub color: [f32; 3],
+    pub visible: bool,
+    // DATA section the shell was read from
+    #[serde(default)]
+    pub section: usize,
+    // product tree node this instance belongs to
+    #[serde(default)]
+    pub node_id: Option<usize>,
+}
+
+impl Default for RenderablePart {
+    fn default() -> Self {
+        Self {
+            mesh: Arc::default(),
+            model_matrix: [
+                1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+            ],
+            color: [0.8, 0.8, 0.8],
+            visible: true,
+            section: 0,
+            node_id: None,
+        }
+    }
+}
+
+impl RenderablePart {
+    // (min, max) of the vertices once placed by `model_matrix`
+    pub fn world_bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        trace_span!("RenderablePart::world_bounds");
+        let mut vertices = self.mesh.vertices.iter();
+        let first = transform_point(&self.model_matrix, vertices.next()?.position);
+        Some(vertices.fold((first, first), |(mut min, mut max), vertex| {
+            let pos = transform_point(&self.model_matrix, vertex.position);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(pos[axis]);
+                max[axis] = max[axis].max(pos[axis]);
+            }
+            (min, max)
+        }))
+    }
+
+    // enclosed volume, see MassProperties for the other properties
+    pub fn calculate_volume(&self) -> f64 {
+        MassProperties::of_part(self).volume
+    }
+
+    pub fn calculate_surface_area(&self) -> f64 {
+        MassProperties::of_part(self).area
+    }
+}
+
+// (index into `sections`, shell id) for every shell, in tessellation order.
+// Sorted so part order (and palette colors) is stable between runs.
+pub fn shell_queue(sections: &[StepSection]) -> Vec<(usize, u64)> {
+    let mut queue = Vec::new();
+    for (section_pos, section) in sections.iter().enumerate() {
+        let mut shell_ids: Vec<u64> = section.table.shell.keys().copied().collect();
+        shell_ids.sort_unstable();
+        queue.extend(shell_ids.into_iter().map(|id| (section_pos, id)));
+    }
+    queue
+}
+
+// Tessellates one shell into one part per placed instance. `palette_index` picks
+// the fallback color for shells without presentation style. A shell truck can't
+// convert is an error naming it, the rest of the file is still usable.
+pub fn tessellate_shell(
+    section: &StepSection,
+    shell_id: u64,
+    palette_index: usize,
+) -> std::result::Result<Vec<RenderablePart>, StepVizError> {
+    trace_span!("tessellate_shell");
+    let StepSection {
+        index: section_index,
+        table,
+        placements,
+        styles,
+    } = section;
+    let mut parts = Vec::new();
+    let Some(shell) = table.shell.get(&shell_id) else {
+        return Ok(parts);
+    };
+
+    let compress_start = now_ms();
+    let cshell = table.to_compressed_shell(shell).map_err(|err| {
+        StepVizError::geometry(
+            Some(shell_id),
+            format!("shell could not be converted: {err}"),
+        )
+    })?;
+    let compress_ms = now_ms() - compress_start;
+
+    let tri_start = now_ms();
+
+    //this has to be smaller than the radius of the sphere
+    //FIXME: this is a hack
+    //allow user to set tolerance (trigger 3D scene re-render)
+    use crate::constants::DEFAULT_TOLERANCE;
+    let tolerance = DEFAULT_TOLERANCE; // smaller => higher quality, but slower
+    let poly_shell = cshell.triangulation(tolerance);
+    let triangulation_ms = now_ms() - tri_start;
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut faces = Vec::new();
+    let edges: Vec<Vec<[f32; 3]>> = poly_shell
+        .edges
+        .iter()
+        .filter(|edge| edge.curve.0.len() >= 2)
+        .map(|edge| {
+            edge.curve
+                .0
+                .iter()
+                .map(|p| [p.x as f32, p.y as f32, p.z as f32])
+                .collect()
+        })
+        .collect();
+
+    let style = styles.get(&shell_id);
+    let face_colors = style
+        .map(|style| style.face_colors_for(poly_shell.faces.len()))
+        .unwrap_or_default();
+    let mut vertex_face_colors: Vec<Option<[f32; 3]>> = Vec::new();
+
+    for (face_index, face) in poly_shell.faces.into_iter().enumerate() {
+        if let Some(mut mesh) = face.surface {
+            let needs_invert = !face.orientation;
+            if needs_invert {
+                mesh.invert();
+            }
+
+            let face_positions = mesh.positions();
+            let face_normals = mesh.normals();
+
+            let base_index = vertices.len() as u32;
+
+            vertices.extend(
+                face_positions
+                    .iter()
+                    .zip(face_normals.iter())
+                    .map(|(p, n)| GpuVertex {
+                        position: [p.x as f32, p.y as f32, p.z as f32],
+                        normal: [n.x as f32, n.y as f32, n.z as f32],
+                    }),
+            );
+            if !face_colors.is_empty() {
+                vertex_face_colors.resize(vertices.len(), face_colors[face_index]);
+            }
+
+            let first_index = indices.len() as u32;
+            let outward = face_normals.first().map_or([0.0; 3], |n| [n.x, n.y, n.z]);
+
+            let mesh_faces = mesh.faces();
+
+            let tri_faces = mesh_faces.tri_faces();
+            let quad_faces = mesh_faces.quad_faces();
+
+            for tri in tri_faces {
+                if needs_invert {
+                    indices.push(base_index + tri[0].pos as u32);
+                    indices.push(base_index + tri[2].pos as u32);
+                    indices.push(base_index + tri[1].pos as u32);
+                } else {
+                    indices.push(base_index + tri[0].pos as u32);
+                    indices.push(base_index + tri[1].pos as u32);
+                    indices.push(base_index + tri[2].pos as u32);
+                }
+            }
+
+            for quad in quad_faces {
+                if needs_invert {
+                    indices.push(base_index + quad[0].pos as u32);
+                    indices.push(base_index + quad[2].pos as u32);
+                    indices.push(base_index + quad[1].pos as u32);
+
+                    indices.push(base_index + quad[0].pos as u32);
+                    indices.push(base_index + quad[3].pos as u32);
+                    indices.push(base_index + quad[2].pos as u32);
+                } else {
+                    indices.push(base_index + quad[0].pos as u32);
+                    indices.push(base_index + quad[1].pos as u32);
+                    indices.push(base_index + quad[2].pos as u32);
+
+                    indices.push(base_index + quad[0].pos as u32);
+                    indices.push(base_index + quad[2].pos as u32);
+                    indices.push(base_index + quad[3].pos as u32);
+                }
+            }
+
+            let index_count = indices.len() as u32 - first_index;
+            if index_count > 0 {
+                faces.push(PartFace {
+                    first_index,
+                    index_count,
+                    surface: face_surface(&cshell.faces[face_index].surface, outward),
+                });
+            }
+        }
+    }
+
+    if !vertices.is_empty() && !indices.is_empty() {
+        // the palette is only a fallback for shells without presentation style
+        let color = style
+            .and_then(|style| style.color)
+            .or_else(|| uniform_color(&vertex_face_colors))
+            .unwrap_or(COLORS[palette_index % COLORS.len()]);
+        let vertex_colors: Vec<[f32; 3]> = if vertex_face_colors
+            .iter()
+            .any(|c| c.is_some_and(|c| c != color))
+        {
+            vertex_face_colors
+                .iter()
+                .map(|c| c.unwrap_or(color))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mesh = Arc::new(PartMesh {
+            vertices,
+            indices,
+            vertex_colors,
+            faces,
+            edges,
+        });
+        // one part per placed instance of the shell, all sharing its mesh
+        let instances = placements
+            .get(&shell_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if instances.is_empty() {
+            parts.push(RenderablePart {
+                mesh,
+                color,
+                section: *section_index,
+                ..Default::default()
+            });
+        } else {
+            for placement in instances {
+                parts.push(RenderablePart {
+                    mesh: Arc::clone(&mesh),
+                    model_matrix: placement.transform,
+                    color,
+                    visible: true,
+                    section: *section_index,
+                    node_id: placement.node_id,
+                });
+            }
+        }
+    }
+
+    let shell_msg = format!(
+        "tessellate_shell => shell #{} processed (compress {:.2} ms, triangulation {:.2} ms, parts={})",
+        shell_id,
+        compress_ms,
+        triangulation_ms,
+        parts.len()
+    );
+    tracing::debug!("{} {}", STEP_TRACER, shell_msg);
+    Ok(parts)
+}
+
+// Serde form of parts that sends every distinct mesh once, followed by the
+// parts naming their mesh by position. Use with `#[serde(with = ...)]` where
+// many instances cross a boundary, the plain form repeats shared meshes.
+pub mod shared_meshes {
+    use super::{PartMesh, RenderablePart};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize)]
+    struct Instance {
+        mesh: usize,
+        model_matrix: [f32; 16],
+        color: [f32; 3],
+        visible: bool,
+        section: usize,
+        node_id: Option<usize>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        parts: &[RenderablePart],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut meshes: Vec<&PartMesh> = Vec::new();
+        let mut positions: HashMap<*const PartMesh, usize> = HashMap::new();
+        let instances: Vec<Instance> = parts
+            .iter()
+            .map(|part| Instance {
+                mesh: *positions.entry(Arc::as_ptr(&part.mesh)).or_insert_with(|| {
+                    meshes.push(&part.mesh);
+                    meshes.len() - 1
+                }),
+                model_matrix: part.model_matrix,
+                color: part.color,
+                visible: part.visible,
+                section: part.section,
+                node_id: part.node_id,
+            })
+            .collect();
+        (meshes, instances).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<RenderablePart>, D::Error> {
+        let (meshes, instances): (Vec<PartMesh>, Vec<Instance>) =
+            Deserialize::deserialize(deserializer)?;
+        let meshes: Vec<Arc<PartMesh>> = meshes.into_iter().map(Arc::new).collect();
+        instances
+            .into_iter()
+            .map(|instance| {
+                let mesh = meshes
+                    .get(instance.mesh)
+                    .ok_or_else(|| D::Error::custom(format!("no mesh {}", instance.mesh)))?;
+                Ok(RenderablePart {
+                    mesh: Arc::clone(mesh),
+                    model_matrix: instance.model_matrix,
+                    color: instance.color,
+                    visible: instance.visible,
+                    section: instance.section,
+                    node_id: instance.node_id,
+                })
+            })
+            .collect()
+    }
+}
+
+// the color shared by every vertex, if all of them are styled alike
+fn uniform_color(colors: &[Option<[f32; 3]>]) -> Option<[f32; 3]> {
+    let first = (*colors.first()?)?;
+    colors.iter().all(|c| *c == Some(first)).then_some(first)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::sample_model;
+
+    #[derive(Serialize, Deserialize)]
+    struct Shell {
+        #[serde(with = "shared_meshes")]
+        parts: Vec<RenderablePart>,
+    }
+
+    #[test]
+    fn shared_meshes_are_sent_once() {
+        let part = sample_model("a").render_parts.swap_remove(0);
+        let mut moved = part.clone();
+        moved.model_matrix[12] = 3.0;
+        let shell = Shell {
+            parts: vec![part, moved],
+        };
+        let json = serde_json::to_string(&shell).unwrap();
+        assert_eq!(json.matches("\"vertices\"").count(), 1);
+
+        let read: Shell = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.parts, shell.parts);
+        assert!(Arc::ptr_eq(&read.parts[0].mesh, &read.parts[1].mesh));
+
+        let missing = json.replace("\"mesh\":0", "\"mesh\":7");
+        assert!(serde_json::from_str::<Shell>(&missing).is_err());
+    }
+
+    #[test]
+    fn reads_parts_stored_with_their_own_mesh() {
+        let json = r#"{"vertices":[{"position":[1,2,3],"normal":[0,0,1]}],"indices":[0,0,0],
+            "model_matrix":[1,0,0,0,0,1,0,0,0,0,1,0,0,0,0,1],"color":[1,0,0],"visible":true}"#;
+        let part: RenderablePart = serde_json::from_str(json).unwrap();
+        assert_eq!(part.mesh.vertices[0].position, [1.0, 2.0, 3.0]);
+        assert_eq!(part.mesh.indices, [0, 0, 0]);
+        assert!(part.mesh.faces.is_empty());
+        assert_eq!(
+            serde_json::to_string(&part)
+                .unwrap()
+                .matches("\"mesh\"")
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{cross3_f64, dot3, dot3_f64, normalize3_f64, transform_point};
+use crate::render::RenderablePart;
+use crate::trace_span;
+
+// Points with dot(normal, p) > offset (world space) are cut away, `normal` is
+// a unit vector
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClipPlane {
+    pub normal: [f32; 3],
+    pub offset: f32,
+}
+
+impl ClipPlane {
+    // cuts away the positive side of `axis` (0 = x, 1 = y, 2 = z)
+    pub fn axis(axis: usize, offset: f32) -> Self {
+        let mut normal = [0.0; 3];
+        normal[axis] = 1.0;
+        Self { normal, offset }
+    }
+
+    pub fn cuts(&self, point: [f32; 3]) -> bool {
+        dot3(self.normal, point) > self.offset
+    }
+
+    // keeps the other half
+    pub fn flipped(&self) -> Self {
+        Self {
+            normal: self.normal.map(|x| -x),
+            offset: -self.offset,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let axis = (0..3).find(|&axis| self.normal[axis].abs() == 1.0);
+        match axis {
+            Some(axis) => {
+                let sign = if self.normal[axis] < 0.0 { "-" } else { "+" };
+                format!("{sign}{}", ["X", "Y", "Z"][axis])
+            }
+            None => format!(
+                "({:.2}, {:.2}, {:.2})",
+                self.normal[0], self.normal[1], self.normal[2]
+            ),
+        }
+    }
+}
+
+// offsets between which the plane goes through `bounds`
+pub fn offset_range(
+    bounds: impl IntoIterator<Item = ([f32; 3], [f32; 3])>,
+    normal: [f32; 3],
+) -> Option<(f32, f32)> {
+    bounds
+        .into_iter()
+        .flat_map(|(min, max)| {
+            (0..8).map(move |corner| {
+                let pick = |axis: usize| {
+                    if corner >> axis & 1 == 0 {
+                        min[axis]
+                    } else {
+                        max[axis]
+                    }
+                };
+                dot3(normal, [pick(0), pick(1), pick(2)])
+            })
+        })
+        .fold(None, |range, offset| match range {
+            None => Some((offset, offset)),
+            Some((low, high)) => Some((offset.min(low), offset.max(high))),
+        })
+}
+
+// Cross-section of solids with a plane, in model units. Contours the mesh
+// doesn't close (open shells, gaps in the tessellation) only count in
+// `open_contours`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Section {
+    pub area: f64,
+    pub perimeter: f64,
+    pub contours: usize,
+    pub open_contours: usize,
+}
+
+// Section through the visible parts, each part is cut as a solid of its own
+pub fn cross_section(parts: &[RenderablePart], plane: &ClipPlane) -> Section {
+    trace_span!("cross_section");
+    parts
+        .iter()
+        .filter(|part| part.visible)
+        .map(|part| part_section(part, plane))
+        .fold(Section::default(), |total, section| Section {
+            area: total.area + section.area,
+            perimeter: total.perimeter + section.perimeter,
+            contours: total.contours + section.contours,
+            open_contours: total.open_contours + section.open_contours,
+        })
+}
+
+pub fn part_section(part: &RenderablePart, plane: &ClipPlane) -> Section {
+    trace_span!("part_section");
+    let normal = normalize3_f64(plane.normal.map(|x| x as f64));
+    let (u, v) = plane_basis(normal);
+    let world: Vec<[f64; 3]> = part
+        .mesh
+        .vertices
+        .iter()
+        .map(|vertex| transform_point(&part.model_matrix, vertex.position).map(|x| x as f64))
+        .collect();
+    let side: Vec<f64> = world
+        .iter()
+        .map(|point| dot3_f64(normal, *point) - plane.offset as f64)
+        .collect();
+
+    let mut segments = Vec::new();
+    for triangle in part.mesh.indices.chunks_exact(3) {
+        let corners = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        if corners.iter().any(|&index| index >= world.len()) {
+            continue;
+        }
+        let mut crossings = Vec::with_capacity(2);
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let (mut a, mut b) = (corners[a], corners[b]);
+            // points on the plane count as above, so a triangle crosses it
+            // on no edge or on exactly two
+            if (side[a] >= 0.0) == (side[b] >= 0.0) {
+                continue;
+            }
+            // same order for both triangles of an edge, same crossing point
+            if world[b] < world[a] {
+                std::mem::swap(&mut a, &mut b);
+            }
+            let t = side[a] / (side[a] - side[b]);
+            let point: [f64; 3] =
+                std::array::from_fn(|i| world[a][i] + t * (world[b][i] - world[a][i]));
+            crossings.push([dot3_f64(point, u), dot3_f64(point, v)]);
+        }
+        if let [start, end] = crossings[..] {
+            segments.push((start, end));
+        }
+    }
+
+    let extent = part
+        .world_bounds()
+        .map(|(min, max)| {
+            (0..3)
+                .map(|axis| (max[axis] - min[axis]) as f64)
+                .fold(0.0, f64::max)
+        })
+        .unwrap_or(1.0);
+    let (contours, open_contours) = chain(&segments, extent.max(f64::EPSILON) * 1e-7);
+    Section {
+        area: nested_area(&contours),
+        perimeter: contours.iter().map(|contour| contour_length(contour)).sum(),
+        contours: contours.len(),
+        open_contours,
+    }
+}
+
+// Joins segments sharing end points (within `tolerance`) into closed
+// contours, returns them with the number of chains that didn't close
+fn chain(segments: &[([f64; 2], [f64; 2])], tolerance: f64) -> (Vec<Vec<[f64; 2]>>, usize) {
+    let key = |point: [f64; 2]| {
+        (
+            (point[0] / tolerance).round() as i64,
+            (point[1] / tolerance).round() as i64,
+        )
+    };
+    // segments collapsing to a point (a corner on the plane) join nothing
+    let mut used: Vec<bool> = segments
+        .iter()
+        .map(|(start, end)| key(*start) == key(*end))
+        .collect();
+    let mut ends: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (index, (start, end)) in segments.iter().enumerate() {
+        if !used[index] {
+            ends.entry(key(*start)).or_default().push(index);
+            ends.entry(key(*end)).or_default().push(index);
+        }
+    }
+
+    let mut contours = Vec::new();
+    let mut open = 0;
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let (start, end) = segments[first];
+        let mut contour = vec![start, end];
+        let mut current = key(end);
+        let closed = loop {
+            if current == key(start) {
+                break true;
+            }
+            let next = ends
+                .get(&current)
+                .and_then(|candidates| candidates.iter().find(|&&index| !used[index]));
+            let Some(&next) = next else {
+                break false;
+            };
+            used[next] = true;
+            let (a, b) = segments[next];
+            let point = if key(a) == current { b } else { a };
+            contour.push(point);
+            current = key(point);
+        };
+        if closed {
+            contour.pop();
+            contours.push(contour);
+        } else {
+            open += 1;
+        }
+    }
+    (contours, open)
+}
+
+// Area enclosed by closed contours, contours inside an odd number of others
+// are holes
+fn nested_area(contours: &[Vec<[f64; 2]>]) -> f64 {
+    contours
+        .iter()
+        .enumerate()
+        .map(|(index, contour)| {
+            // a point strictly inside the contour's boundary band
+            let inside = midpoint(contour[0], contour[1]);
+            let depth = contours
+                .iter()
+                .enumerate()
+                .filter(|(other, polygon)| *other != index && contains(polygon, inside))
+                .count();
+            let area = signed_area(contour).abs();
+            if depth % 2 == 0 { area } else { -area }
+        })
+        .sum()
+}
+
+fn signed_area(contour: &[[f64; 2]]) -> f64 {
+    let count = contour.len();
+    (0..count)
+        .map(|i| {
+            let (a, b) = (contour[i], contour[(i + 1) % count]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum::<f64>()
+        * 0.5
+}
+
+fn contour_length(contour: &[[f64; 2]]) -> f64 {
+    let count = contour.len();
+    (0..count)
+        .map(|i| {
+            let (a, b) = (contour[i], contour[(i + 1) % count]);
+            (b[0] - a[0]).hypot(b[1] - a[1])
+        })
+        .sum()
+}
+
+// even-odd rule
+fn contains(polygon: &[[f64; 2]], point: [f64; 2]) -> bool {
+    let count = polygon.len();
+    let mut inside = false;
+    for i in 0..count {
+        let (a, b) = (polygon[i], polygon[(i + 1) % count]);
+        if (a[1] > point[1]) != (b[1] > point[1]) {
+            let x = a[0] + (point[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+            if point[0] < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+fn midpoint(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [(a[0] + b[0]) * 0.5, (a[1] + b[1]) * 0.5]
+}
+
+// two unit vectors spanning the plane with unit `normal`
+fn plane_basis(normal: [f64; 3]) -> ([f64; 3], [f64; 3]) {
+    let helper = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let u = normalize3_f64(cross3_f64(normal, helper));
+    (u, cross3_f64(normal, u))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::cuboid;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn box_sections() {
+        let part = cuboid([2.0, 3.0, 4.0], [0.0; 3]);
+        let section = part_section(&part, &ClipPlane::axis(2, 1.5));
+        assert_close(section.area, 6.0);
+        assert_close(section.perimeter, 10.0);
+        assert_eq!((section.contours, section.open_contours), (1, 0));
+
+        // through a corner: the triangle (0,0,4), (2,0,0), (0,3,0) scaled
+        let normal = [6.0f32, 4.0, 3.0];
+        let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+        let plane = ClipPlane {
+            normal: normal.map(|x| x / length),
+            offset: 12.0 / length,
+        };
+        let section = part_section(&part, &plane);
+        assert_close(
+            section.area,
+            0.5 * (6.0f64 * 6.0 + 8.0 * 8.0 + 12.0 * 12.0).sqrt(),
+        );
+
+        assert_eq!(
+            part_section(&part, &ClipPlane::axis(0, 5.0)),
+            Section::default()
+        );
+    }
+
+    #[test]
+    fn placements_and_visibility() {
+        let mut part = cuboid([1.0, 1.0, 1.0], [0.0; 3]);
+        part.model_matrix[0] = 2.0;
+        part.model_matrix[14] = 10.0;
+        let hidden = RenderablePart {
+            visible: false,
+            ..cuboid([1.0, 1.0, 1.0], [0.0; 3])
+        };
+        let section = cross_section(&[part.clone(), hidden], &ClipPlane::axis(2, 10.5));
+        assert_close(section.area, 2.0);
+        assert_close(section.perimeter, 6.0);
+        // the flipped plane is the same surface
+        let flipped = cross_section(&[part], &ClipPlane::axis(2, 10.5).flipped());
+        assert_close(flipped.area, 2.0);
+    }
+
+    #[test]
+    fn holes_are_subtracted() {
+        let square = |low: f64, high: f64| vec![[low, low], [high, low], [high, high], [low, high]];
+        // a 4x4 square with a 2x2 hole holding a 1x1 island
+        let contours = [square(0.0, 4.0), square(1.0, 3.0), square(1.5, 2.5)];
+        assert_close(nested_area(&contours), 16.0 - 4.0 + 1.0);
+
+        let segments: Vec<_> = square(0.0, 1.0)
+            .iter()
+            .zip(square(0.0, 1.0).iter().cycle().skip(1))
+            .map(|(a, b)| (*a, *b))
+            .chain([([5.0, 5.0], [6.0, 5.0])])
+            .collect();
+        let (contours, open) = chain(&segments, 1e-9);
+        assert_eq!((contours.len(), open), (1, 1));
+        assert_close(signed_area(&contours[0]).abs(), 1.0);
+    }
+
+    #[test]
+    fn offsets_span_the_bounds() {
+        let bounds = [
+            ([0.0, 0.0, 0.0], [1.0, 2.0, 3.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 1.0, 1.0]),
+        ];
+        assert_eq!(offset_range(bounds, [1.0, 0.0, 0.0]), Some((-1.0, 1.0)));
+        assert_eq!(offset_range(bounds, [0.0, 0.0, -1.0]), Some((-3.0, 0.0)));
+        assert_eq!(offset_range([], [1.0, 0.0, 0.0]), None);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::trace_span;
+use std::fmt::Write;
+
+use super::math::transform_point;
+use super::render::RenderablePart;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StlFormat {
+    Binary,
+    Ascii,
+}
+
+pub type Triangle = [[f32; 3]; 3];
+
+// Triangles of `part` in world space, wound counter-clockwise seen from
+// outside: the part's triangles are flipped as a whole when they enclose a
+// negative volume
+pub fn world_triangles(part: &RenderablePart) -> Vec<Triangle> {
+    let world: Vec<[f32; 3]> = part
+        .mesh
+        .vertices
+        .iter()
+        .map(|vertex| transform_point(&part.model_matrix, vertex.position))
+        .collect();
+    let mut triangles: Vec<Triangle> = part
+        .mesh
+        .indices
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            Some([
+                *world.get(triangle[0] as usize)?,
+                *world.get(triangle[1] as usize)?,
+                *world.get(triangle[2] as usize)?,
+            ])
+        })
+        .collect();
+    let signed_volume: f64 = triangles
+        .iter()
+        .map(|[a, b, c]| {
+            let [a, b, c] = [a, b, c].map(|p| p.map(|x| x as f64));
+            a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0])
+                + a[2] * (b[0] * c[1] - b[1] * c[0])
+        })
+        .sum();
+    if signed_volume < 0.0 {
+        for triangle in &mut triangles {
+            triangle.swap(1, 2);
+        }
+    }
+    triangles
+}
+
+// Writes the world space triangles of `parts` as one solid named `name`
+pub fn write_stl<'a>(
+    name: &str,
+    parts: impl IntoIterator<Item = &'a RenderablePart>,
+    format: StlFormat,
+) -> Vec<u8> {
+    trace_span!("write_stl");
+    let triangles: Vec<Triangle> = parts.into_iter().flat_map(world_triangles).collect();
+    match format {
+        StlFormat::Binary => write_binary(name, &triangles),
+        StlFormat::Ascii => write_ascii(name, &triangles),
+    }
+}
+
+fn write_binary(name: &str, triangles: &[Triangle]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(84 + triangles.len() * 50);
+    // 80 byte header, it must not start with "solid" or readers take it for ASCII
+    let mut header = [b' '; 80];
+    let title = format!("binary STL {name}");
+    let len = title.len().min(80);
+    header[..len].copy_from_slice(&title.as_bytes()[..len]);
+    out.extend_from_slice(&header);
+    out.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+    for triangle in triangles {
+        for value in normal(triangle).iter().chain(triangle.iter().flatten()) {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        // attribute byte count
+        out.extend_from_slice(&[0, 0]);
+    }
+    out
+}
+
+fn write_ascii(name: &str, triangles: &[Triangle]) -> Vec<u8> {
+    // names are a single token
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .collect();
+    let mut out = String::new();
+    let _ = writeln!(out, "solid {name}");
+    for triangle in triangles {
+        let [nx, ny, nz] = normal(triangle);
+        let _ = writeln!(out, "  facet normal {nx:e} {ny:e} {nz:e}");
+        let _ = writeln!(out, "    outer loop");
+        for [x, y, z] in triangle {
+            let _ = writeln!(out, "      vertex {x:e} {y:e} {z:e}");
+        }
+        let _ = writeln!(out, "    endloop");
+        let _ = writeln!(out, "  endfacet");
+    }
+    let _ = writeln!(out, "endsolid {name}");
+    out.into_bytes()
+}
+
+// unit normal from the winding, zero for degenerate triangles
+fn normal([a, b, c]: &Triangle) -> [f32; 3] {
+    let u: [f32; 3] = std::array::from_fn(|axis| b[axis] - a[axis]);
+    let v: [f32; 3] = std::array::from_fn(|axis| c[axis] - a[axis]);
+    let n = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if len > 0.0 {
+        n.map(|x| x / len)
+    } else {
+        [0.0; 3]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GpuVertex;
+    use crate::explode::translated;
+    use crate::render::PartMesh;
+    use std::sync::Arc;
+
+    // Triangles of a binary or ASCII STL file. Binary files are recognised by
+    // their size, some writers start their header with "solid" too.
+    fn read_stl(bytes: &[u8]) -> Result<Vec<Triangle>, String> {
+        if bytes.len() >= 84 {
+            let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+            if bytes.len() == 84 + count * 50 {
+                return Ok(bytes[84..]
+                    .chunks_exact(50)
+                    .map(|facet| {
+                        let value = |i: usize| {
+                            let at = 12 + i * 4;
+                            f32::from_le_bytes([
+                                facet[at],
+                                facet[at + 1],
+                                facet[at + 2],
+                                facet[at + 3],
+                            ])
+                        };
+                        std::array::from_fn(|corner| {
+                            std::array::from_fn(|axis| value(corner * 3 + axis))
+                        })
+                    })
+                    .collect());
+            }
+        }
+
+        let text = std::str::from_utf8(bytes).map_err(|_| "STL is neither binary nor ASCII")?;
+        if !text.trim_start().starts_with("solid") {
+            return Err("STL is neither binary nor ASCII".to_string());
+        }
+        let mut triangles = Vec::new();
+        let mut corners = Vec::new();
+        for line in text.lines() {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("vertex") => {
+                    let values = tokens
+                        .map(|token| token.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| format!("Bad STL vertex '{}': {e}", line.trim()))?;
+                    let [x, y, z] = values[..] else {
+                        return Err(format!("Bad STL vertex '{}'", line.trim()));
+                    };
+                    corners.push([x, y, z]);
+                }
+                Some("endloop") => {
+                    let [a, b, c] = corners[..] else {
+                        return Err(format!("STL facet with {} vertices", corners.len()));
+                    };
+                    triangles.push([a, b, c]);
+                    corners.clear();
+                }
+                _ => {}
+            }
+        }
+        Ok(triangles)
+    }
+
+    // unit tetrahedron, wound inwards when `inward`
+    fn tetrahedron(inward: bool) -> RenderablePart {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let mut indices = vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3];
+        if inward {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        RenderablePart {
+            mesh: Arc::new(PartMesh {
+                vertices: positions
+                    .map(|position| GpuVertex {
+                        position,
+                        normal: [0.0; 3],
+                    })
+                    .to_vec(),
+                indices,
+                ..Default::default()
+            }),
+            model_matrix: translated(&RenderablePart::default().model_matrix, [5.0, 0.0, -2.0]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn binary_and_ascii_round_trip() {
+        let parts = [tetrahedron(false), tetrahedron(true)];
+        let expected: Vec<Triangle> = parts.iter().flat_map(world_triangles).collect();
+        assert_eq!(expected.len(), 8);
+        // placed in the world
+        assert_eq!(expected[0][0], [5.0, 0.0, -2.0]);
+
+        let binary = write_stl("tetra", &parts, StlFormat::Binary);
+        assert_eq!(binary.len(), 84 + 8 * 50);
+        assert!(!binary.starts_with(b"solid"));
+        assert_eq!(read_stl(&binary).unwrap(), expected);
+
+        let ascii = write_stl("two tetras", &parts, StlFormat::Ascii);
+        let text = String::from_utf8(ascii.clone()).unwrap();
+        assert!(text.starts_with("solid two_tetras\n"));
+        assert!(text.ends_with("endsolid two_tetras\n"));
+        assert_eq!(read_stl(&ascii).unwrap(), expected);
+    }
+
+    #[test]
+    fn triangles_face_outwards() {
+        for inward in [false, true] {
+            let triangles = world_triangles(&tetrahedron(inward));
+            // the slanted face points away from the origin corner
+            let slanted = triangles
+                .iter()
+                .find(|triangle| triangle.iter().all(|p| p != &[5.0, 0.0, -2.0]))
+                .unwrap();
+            let n = normal(slanted);
+            assert!(n.iter().all(|x| *x > 0.0), "{n:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(read_stl(b"not a mesh").is_err());
+        assert!(read_stl(b"solid x\nfacet normal 0 0 1\nouter loop\nvertex 1 2\n").is_err());
+    }
+}
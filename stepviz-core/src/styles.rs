@@ -0,0 +1,234 @@
+use crate::trace_span;
+use std::collections::HashMap;
+
+use super::entities::{EntityIndex, param_real, param_ref, param_refs, param_str};
+
+// Presentation colors of one shell, as set in the originating CAD system
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShellStyle {
+    // STYLED_ITEM on the solid/shell itself
+    pub color: Option<[f32; 3]>,
+    // STYLED_ITEM on individual faces, aligned with the shell's face list
+    pub face_colors: Vec<Option<[f32; 3]>>,
+}
+
+impl ShellStyle {
+    // face styles can only be matched by position when no face was dropped
+    // while converting the shell, otherwise there are none
+    pub fn face_colors_for(&self, face_count: usize) -> &[Option<[f32; 3]>] {
+        if self.face_colors.len() == face_count {
+            &self.face_colors
+        } else {
+            &[]
+        }
+    }
+}
+
+// STYLED_ITEM -> PRESENTATION_STYLE_ASSIGNMENT -> SURFACE_STYLE_USAGE ->
+// SURFACE_SIDE_STYLE -> SURFACE_STYLE_FILL_AREA / SURFACE_STYLE_RENDERING ->
+// COLOUR_RGB / DRAUGHTING_PRE_DEFINED_COLOUR
+pub fn resolve_shell_styles(index: &EntityIndex, shell_ids: &[u64]) -> HashMap<u64, ShellStyle> {
+    trace_span!("resolve_shell_styles");
+    let mut item_colors: HashMap<u64, [f32; 3]> = HashMap::new();
+    // overriding styles win over the ones they override
+    for name in ["STYLED_ITEM", "OVER_RIDING_STYLED_ITEM"] {
+        for &styled in index.ids_of(name) {
+            let Some(params) = index.params(styled, name) else {
+                continue;
+            };
+            let (Some(styles), Some(item)) = (params.get(1), params.get(2).and_then(param_ref))
+            else {
+                continue;
+            };
+            if let Some(color) = param_refs(styles)
+                .into_iter()
+                .find_map(|psa| presentation_color(index, psa))
+            {
+                item_colors.insert(item, color);
+            }
+        }
+    }
+
+    let mut styles = HashMap::new();
+    if item_colors.is_empty() {
+        return styles;
+    }
+
+    // solid level: MANIFOLD_SOLID_BREP, BREP_WITH_VOIDS, SHELL_BASED_SURFACE_MODEL ...
+    let mut solid_colors: HashMap<u64, [f32; 3]> = HashMap::new();
+    for (&item, &color) in &item_colors {
+        if let Some((_, params)) = index.simple_params(item) {
+            for param in params {
+                let refs = param_ref(param)
+                    .map(|id| vec![id])
+                    .unwrap_or_else(|| param_refs(param));
+                for id in refs.into_iter().filter(|id| shell_ids.contains(id)) {
+                    solid_colors.entry(id).or_insert(color);
+                }
+            }
+        }
+    }
+
+    for &shell_id in shell_ids {
+        let face_colors: Vec<Option<[f32; 3]>> = index
+            .simple_params(shell_id)
+            .and_then(|(_, params)| params.get(1))
+            .map(param_refs)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|face| item_colors.get(&face).copied())
+            .collect();
+        let color = item_colors
+            .get(&shell_id)
+            .or_else(|| solid_colors.get(&shell_id))
+            .copied();
+        if color.is_some() || face_colors.iter().any(Option::is_some) {
+            styles.insert(shell_id, ShellStyle { color, face_colors });
+        }
+    }
+    styles
+}
+
+fn presentation_color(index: &EntityIndex, psa: u64) -> Option<[f32; 3]> {
+    let (_, params) = index.simple_params(psa)?;
+    param_refs(params.first()?)
+        .into_iter()
+        .find_map(|usage| surface_usage_color(index, usage))
+}
+
+// SURFACE_STYLE_USAGE(side, SURFACE_SIDE_STYLE(name, (elements)))
+fn surface_usage_color(index: &EntityIndex, usage: u64) -> Option<[f32; 3]> {
+    let side_style = index
+        .params(usage, "SURFACE_STYLE_USAGE")?
+        .get(1)
+        .and_then(param_ref)?;
+    let elements = index.params(side_style, "SURFACE_SIDE_STYLE")?.get(1)?;
+    param_refs(elements).into_iter().find_map(|element| {
+        let (name, params) = index.simple_params(element)?;
+        match name {
+            // SURFACE_STYLE_FILL_AREA(FILL_AREA_STYLE(name, (FILL_AREA_STYLE_COLOUR(name, colour))))
+            "SURFACE_STYLE_FILL_AREA" => {
+                let fill = params.first().and_then(param_ref)?;
+                let fill_styles = index.params(fill, "FILL_AREA_STYLE")?.get(1)?;
+                param_refs(fill_styles).into_iter().find_map(|fill_colour| {
+                    let colour = index
+                        .params(fill_colour, "FILL_AREA_STYLE_COLOUR")?
+                        .get(1)
+                        .and_then(param_ref)?;
+                    colour_rgb(index, colour)
+                })
+            }
+            // SURFACE_STYLE_RENDERING(method, colour, ...)
+            "SURFACE_STYLE_RENDERING" | "SURFACE_STYLE_RENDERING_WITH_PROPERTIES" => {
+                colour_rgb(index, params.get(1).and_then(param_ref)?)
+            }
+            _ => None,
+        }
+    })
+}
+
+fn colour_rgb(index: &EntityIndex, colour: u64) -> Option<[f32; 3]> {
+    if let Some(params) = index.params(colour, "COLOUR_RGB") {
+        let channel = |i: usize| {
+            params
+                .get(i)
+                .and_then(param_real)
+                .map(|v| v.clamp(0.0, 1.0) as f32)
+        };
+        return Some([channel(1)?, channel(2)?, channel(3)?]);
+    }
+    let name = index
+        .params(colour, "DRAUGHTING_PRE_DEFINED_COLOUR")?
+        .first()
+        .and_then(param_str)?;
+    match name.to_ascii_lowercase().as_str() {
+        "red" => Some([1.0, 0.0, 0.0]),
+        "green" => Some([0.0, 1.0, 0.0]),
+        "blue" => Some([0.0, 0.0, 1.0]),
+        "yellow" => Some([1.0, 1.0, 0.0]),
+        "magenta" => Some([1.0, 0.0, 1.0]),
+        "cyan" => Some([0.0, 1.0, 1.0]),
+        "black" => Some([0.0, 0.0, 0.0]),
+        "white" => Some([1.0, 1.0, 1.0]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::step_file;
+
+    const RED: [f32; 3] = [1.0, 0.0, 0.0];
+    const GREEN: [f32; 3] = [0.0, 1.0, 0.0];
+    const BLUE: [f32; 3] = [0.0, 0.0, 1.0];
+
+    // solid #20 with shell #21 of faces #31..#33, red through a fill area on
+    // the solid, #32 red as well but overridden to green by a rendering
+    // style, #33 a predefined blue
+    const STYLED: &str = "#1=COLOUR_RGB('',1.,0.,0.);
+        #2=FILL_AREA_STYLE_COLOUR('',#1);
+        #3=FILL_AREA_STYLE('',(#2));
+        #4=SURFACE_STYLE_FILL_AREA(#3);
+        #5=SURFACE_SIDE_STYLE('',(#4));
+        #6=SURFACE_STYLE_USAGE(.BOTH.,#5);
+        #7=PRESENTATION_STYLE_ASSIGNMENT((#6));
+        #8=STYLED_ITEM('',(#7),#20);
+        #9=STYLED_ITEM('',(#7),#32);
+        #11=COLOUR_RGB('',0.,1.,0.);
+        #12=SURFACE_STYLE_RENDERING(.CONSTANT_SHADING.,#11);
+        #13=SURFACE_SIDE_STYLE('',(#12));
+        #14=SURFACE_STYLE_USAGE(.BOTH.,#13);
+        #15=PRESENTATION_STYLE_ASSIGNMENT((#14));
+        #16=OVER_RIDING_STYLED_ITEM('',(#15),#32,#9);
+        #17=DRAUGHTING_PRE_DEFINED_COLOUR('blue');
+        #18=SURFACE_STYLE_RENDERING(.CONSTANT_SHADING.,#17);
+        #19=SURFACE_SIDE_STYLE('',(#18));
+        #22=SURFACE_STYLE_USAGE(.BOTH.,#19);
+        #23=PRESENTATION_STYLE_ASSIGNMENT((#22));
+        #24=STYLED_ITEM('',(#23),#33);
+        #20=MANIFOLD_SOLID_BREP('',#21);
+        #21=CLOSED_SHELL('',(#31,#32,#33));
+        #25=CLOSED_SHELL('',(#31));
+        #31=ADVANCED_FACE('',(#99),#99,.T.);
+        #32=ADVANCED_FACE('',(#99),#99,.T.);
+        #33=ADVANCED_FACE('',(#99),#99,.T.);
+";
+
+    fn styles(data: &str, shell_ids: &[u64]) -> HashMap<u64, ShellStyle> {
+        let exchange = ruststep::parser::parse(&step_file(&[data])).unwrap();
+        resolve_shell_styles(
+            &EntityIndex::from_data_section(&exchange.data[0]),
+            shell_ids,
+        )
+    }
+
+    #[test]
+    fn colours_and_face_overrides() {
+        let styles = styles(STYLED, &[21, 25]);
+        assert_eq!(
+            styles[&21],
+            ShellStyle {
+                color: Some(RED),
+                face_colors: vec![None, Some(GREEN), Some(BLUE)],
+            }
+        );
+        // the unstyled shell only shares an uncoloured face
+        assert!(!styles.contains_key(&25), "{styles:?}");
+    }
+
+    #[test]
+    fn unstyled_files_have_no_styles() {
+        let data = "#21=CLOSED_SHELL('',(#31));\n#31=ADVANCED_FACE('',(#99),#99,.T.);\n";
+        assert!(styles(data, &[21]).is_empty());
+    }
+
+    #[test]
+    fn face_colors_need_matching_face_counts() {
+        let style = &styles(STYLED, &[21])[&21];
+        assert_eq!(style.face_colors_for(3), style.face_colors.as_slice());
+        // a face dropped while tessellating, positions no longer line up
+        assert!(style.face_colors_for(2).is_empty());
+        assert!(style.face_colors_for(4).is_empty());
+    }
+}
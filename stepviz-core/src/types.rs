@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use super::error::StepVizError;
+use super::measure::Measurement;
+use super::render::RenderablePart;
+use super::validation::ValidationProperty;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StepHeader {
+    pub file_description: String,
+    pub implementation_level: String,
+    pub file_name: String,
+    pub time_stamp: String,
+    pub author: Vec<String>,
+    pub organization: Vec<String>,
+    pub preprocessor_version: String,
+    pub originating_system: String,
+    pub authorization: String,
+    pub file_schema: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Metadata {
+    pub header: StepHeader,
+    pub entity_count: usize,
+    // of the tessellated parts, None until they are
+    #[serde(default)]
+    pub bounding_box: Option<BoundingBox>,
+    // length unit, see parser::parse_length_unit
+    #[serde(default)]
+    pub units: Option<String>,
+    #[serde(default)]
+    pub vertex_count: usize,
+    #[serde(default)]
+    pub triangle_count: usize,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    #[serde(default)]
+    pub surface_area: Option<f64>,
+    // entities per DATA section, in file order
+    #[serde(default)]
+    pub section_entity_counts: Vec<usize>,
+    // validation properties given by the file, see validation::validate
+    #[serde(default)]
+    pub validation_properties: Vec<ValidationProperty>,
+    // what went wrong without stopping the load, e.g. shells that were skipped
+    #[serde(default)]
+    pub warnings: Vec<StepVizError>,
+}
+
+impl Metadata {
+    // Label for lengths, areas and volumes. Models saved before the length
+    // unit was resolved may hold the first SI unit of the file instead, an
+    // angle unit is no length unit.
+    pub fn length_unit(&self) -> Option<&str> {
+        self.units
+            .as_deref()
+            .filter(|unit| !matches!(*unit, "radian" | "steradian"))
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FileIndexItem {
+    pub id: String,
+    pub name: String,
+    pub entity_count: usize,
+    pub time_stamp: String,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl BoundingBox {
+    // world space box around the placed parts, None before tessellation
+    pub fn of_parts(parts: &[RenderablePart]) -> Option<BoundingBox> {
+        parts
+            .iter()
+            .filter_map(RenderablePart::world_bounds)
+            .map(|(min, max)| BoundingBox {
+                min: min.map(f64::from),
+                max: max.map(f64::from),
+            })
+            .reduce(|a, b| BoundingBox {
+                min: [0, 1, 2].map(|i| a.min[i].min(b.min[i])),
+                max: [0, 1, 2].map(|i| a.max[i].max(b.max[i])),
+            })
+    }
+}
+
+// One product occurrence in the assembly tree, ids are assigned in pre-order
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ProductNode {
+    pub id: usize,
+    pub name: String,
+    #[serde(default)]
+    pub instance_name: Option<String>,
+    // how many times this product occurs across the whole tree
+    pub instance_count: usize,
+    #[serde(default)]
+    pub children: Vec<ProductNode>,
+}
+
+impl ProductNode {
+    pub fn find(&self, id: usize) -> Option<&ProductNode> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+
+    // adds `offset` to every id of the subtree, returns its node count
+    pub fn shift_ids(&mut self, offset: usize) -> usize {
+        self.id += offset;
+        1 + self
+            .children
+            .iter_mut()
+            .map(|child| child.shift_ids(offset))
+            .sum::<usize>()
+    }
+
+    pub fn subtree_ids(&self) -> Vec<usize> {
+        let mut ids = vec![self.id];
+        for child in &self.children {
+            ids.extend(child.subtree_ids());
+        }
+        ids
+    }
+}
+
+pub fn find_product_node(roots: &[ProductNode], id: usize) -> Option<&ProductNode> {
+    roots.iter().find_map(|root| root.find(id))
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StepModel {
+    pub id: String,
+    pub metadata: Metadata,
+    pub render_parts: Vec<RenderablePart>,
+    #[serde(default)]
+    pub product_tree: Vec<ProductNode>,
+    // measurements taken by the user, kept with the file
+    #[serde(default)]
+    pub measurements: Vec<Measurement>,
+}
+
+impl StepModel {
+    // display name of a render part: its product instance, else its position
+    pub fn part_name(&self, index: usize) -> String {
+        self.render_parts
+            .get(index)
+            .and_then(|part| part.node_id)
+            .and_then(|id| find_product_node(&self.product_tree, id))
+            .map(|node| node.instance_name.clone().unwrap_or(node.name.clone()))
+            .unwrap_or_else(|| format!("Mesh {}", index + 1))
+    }
+}
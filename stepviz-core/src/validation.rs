@@ -0,0 +1,395 @@
+use crate::trace_span;
+use ruststep::ast::Parameter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use super::assembly::Transform;
+use super::entities::{EntityIndex, param_real, param_reals, param_ref, param_refs, param_str};
+use super::mass::MassProperties;
+use super::types::{StepModel, find_product_node};
+
+// tessellated curved faces lose a little volume and area, threads and small
+// radii over a percent
+const MEASURE_TOLERANCE: f64 = 0.02;
+// centroid distance over the bounding box diagonal
+const CENTROID_TOLERANCE: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ValidationValue {
+    Volume(f64),
+    SurfaceArea(f64),
+    // world space, at the first occurrence of the product
+    Centroid([f64; 3]),
+    ChildCount(usize),
+}
+
+impl ValidationValue {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ValidationValue::Volume(_) => "Volume",
+            ValidationValue::SurfaceArea(_) => "Surface area",
+            ValidationValue::Centroid(_) => "Centroid",
+            ValidationValue::ChildCount(_) => "Children",
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            ValidationValue::Volume(_) => 0,
+            ValidationValue::SurfaceArea(_) => 1,
+            ValidationValue::Centroid(_) => 2,
+            ValidationValue::ChildCount(_) => 3,
+        }
+    }
+}
+
+impl fmt::Display for ValidationValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationValue::Volume(value) | ValidationValue::SurfaceArea(value) => {
+                write!(f, "{value:.4}")
+            }
+            ValidationValue::Centroid(point) => {
+                write!(f, "({:.4}, {:.4}, {:.4})", point[0], point[1], point[2])
+            }
+            ValidationValue::ChildCount(count) => write!(f, "{count}"),
+        }
+    }
+}
+
+// A validation property given by the file for a product, on the product tree
+// node of its first occurrence
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValidationProperty {
+    pub node_id: usize,
+    pub value: ValidationValue,
+}
+
+// Reads geometric and assembly validation properties:
+// PROPERTY_DEFINITION_REPRESENTATION links a PROPERTY_DEFINITION named
+// '*_validation_property' to a REPRESENTATION holding a
+// MEASURE_REPRESENTATION_ITEM (volume, area), a CARTESIAN_POINT (centroid) or
+// a VALUE_REPRESENTATION_ITEM (number of children). The property definition
+// points at the PRODUCT_DEFINITION_SHAPE of a product, or at a SHAPE_ASPECT
+// (one solid) of it. Node ids are the ones of `node_pd`.
+pub fn collect_validation_properties(
+    index: &EntityIndex,
+    node_pd: &[u64],
+    node_transforms: &[Transform],
+) -> Vec<ValidationProperty> {
+    trace_span!("collect_validation_properties");
+    let mut first_node: HashMap<u64, usize> = HashMap::new();
+    for (node_id, pd) in node_pd.iter().enumerate() {
+        first_node.entry(*pd).or_insert(node_id);
+    }
+
+    // (node, value, given for a shape aspect)
+    let mut found = Vec::new();
+    for &pdr in index.ids_of("PROPERTY_DEFINITION_REPRESENTATION") {
+        let Some(params) = index.params(pdr, "PROPERTY_DEFINITION_REPRESENTATION") else {
+            continue;
+        };
+        let (Some(definition), Some(rep)) = (
+            params.first().and_then(param_ref),
+            params.get(1).and_then(param_ref),
+        ) else {
+            continue;
+        };
+        let Some(definition) = index.params(definition, "PROPERTY_DEFINITION") else {
+            continue;
+        };
+        let is_validation = definition
+            .first()
+            .and_then(param_str)
+            .is_some_and(|name| name.to_ascii_lowercase().ends_with("validation_property"));
+        if !is_validation {
+            continue;
+        }
+        let Some((pd, aspect)) = definition
+            .get(2)
+            .and_then(param_ref)
+            .and_then(|target| product_definition_of(index, target))
+        else {
+            continue;
+        };
+        let Some(&node_id) = first_node.get(&pd) else {
+            continue;
+        };
+        let items = index
+            .simple_params(rep)
+            .and_then(|(_, params)| params.get(1))
+            .map(param_refs)
+            .unwrap_or_default();
+        for item in items {
+            if let Some(value) = item_value(index, item, &node_transforms[node_id]) {
+                found.push((node_id, value, aspect));
+            }
+        }
+    }
+
+    // values of the whole product win over the ones of a single solid, those
+    // only count when the product has one solid
+    let mut per_kind: HashMap<(usize, u8), (usize, usize)> = HashMap::new();
+    for (node_id, value, aspect) in &found {
+        let counts = per_kind.entry((*node_id, value.kind())).or_default();
+        if *aspect {
+            counts.1 += 1;
+        } else {
+            counts.0 += 1;
+        }
+    }
+    let mut properties: Vec<ValidationProperty> = Vec::new();
+    for (node_id, value, aspect) in found {
+        let (products, aspects) = per_kind[&(node_id, value.kind())];
+        let usable = if aspect {
+            products == 0 && aspects == 1
+        } else {
+            true
+        };
+        let duplicate = properties
+            .iter()
+            .any(|p| p.node_id == node_id && p.value.kind() == value.kind());
+        if usable && !duplicate {
+            properties.push(ValidationProperty { node_id, value });
+        }
+    }
+    properties.sort_by_key(|p| (p.node_id, p.value.kind()));
+    properties
+}
+
+// (product definition, `target` is a shape aspect) for the target of a
+// validation PROPERTY_DEFINITION
+fn product_definition_of(index: &EntityIndex, target: u64) -> Option<(u64, bool)> {
+    let (pds, aspect) = match index.params(target, "SHAPE_ASPECT") {
+        Some(params) => (params.get(2).and_then(param_ref)?, true),
+        None => (target, false),
+    };
+    let pd = index
+        .params(pds, "PRODUCT_DEFINITION_SHAPE")?
+        .get(2)
+        .and_then(param_ref)?;
+    Some((pd, aspect))
+}
+
+fn item_value(index: &EntityIndex, item: u64, world: &Transform) -> Option<ValidationValue> {
+    if let Some(params) = index.params(item, "MEASURE_REPRESENTATION_ITEM") {
+        let Parameter::Typed { keyword, parameter } = params.get(1)? else {
+            return None;
+        };
+        let value = param_real(parameter)?;
+        return match keyword.as_str() {
+            "VOLUME_MEASURE" => Some(ValidationValue::Volume(value)),
+            "AREA_MEASURE" => Some(ValidationValue::SurfaceArea(value)),
+            _ => None,
+        };
+    }
+    if let Some(params) = index.params(item, "CARTESIAN_POINT") {
+        let coordinates = param_reals(params.get(1)?);
+        let local: [f64; 3] =
+            std::array::from_fn(|axis| coordinates.get(axis).copied().unwrap_or(0.0));
+        return Some(ValidationValue::Centroid(std::array::from_fn(|row| {
+            world[row] * local[0]
+                + world[4 + row] * local[1]
+                + world[8 + row] * local[2]
+                + world[12 + row]
+        })));
+    }
+    if let Some(params) = index.params(item, "VALUE_REPRESENTATION_ITEM") {
+        let name = params.first().and_then(param_str)?.to_ascii_lowercase();
+        let value = param_real(params.get(1)?)?;
+        if name.contains("children") && value >= 0.0 {
+            return Some(ValidationValue::ChildCount(value.round() as usize));
+        }
+    }
+    None
+}
+
+// One validation property compared with what the viewer computes
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationCheck {
+    pub node_id: usize,
+    pub name: String,
+    pub expected: ValidationValue,
+    pub computed: ValidationValue,
+    pub relative_error: f64,
+    pub passed: bool,
+}
+
+// Compares the model's validation properties with the mass properties of the
+// parts below each product node, and with its child count
+pub fn validate(model: &StepModel) -> Vec<ValidationCheck> {
+    trace_span!("validate");
+    let mass: Vec<MassProperties> = model
+        .render_parts
+        .iter()
+        .map(MassProperties::of_part)
+        .collect();
+    // (mass properties, bounding box diagonal) of every subtree
+    let mut subtrees: HashMap<usize, (MassProperties, f64)> = HashMap::new();
+
+    model
+        .metadata
+        .validation_properties
+        .iter()
+        .filter_map(|property| {
+            let node = find_product_node(&model.product_tree, property.node_id)?;
+            let (body, diagonal) = *subtrees.entry(node.id).or_insert_with(|| {
+                let ids = node.subtree_ids();
+                let parts: Vec<usize> = (0..model.render_parts.len())
+                    .filter(|&i| {
+                        model.render_parts[i]
+                            .node_id
+                            .is_some_and(|id| ids.contains(&id))
+                    })
+                    .collect();
+                let body =
+                    MassProperties::combine(&parts.iter().map(|&i| mass[i]).collect::<Vec<_>>());
+                (body, diagonal(model, &parts))
+            });
+
+            let (computed, relative_error, tolerance) = match property.value {
+                ValidationValue::Volume(expected) => (
+                    ValidationValue::Volume(body.volume),
+                    relative(expected, body.volume),
+                    MEASURE_TOLERANCE,
+                ),
+                ValidationValue::SurfaceArea(expected) => (
+                    ValidationValue::SurfaceArea(body.area),
+                    relative(expected, body.area),
+                    MEASURE_TOLERANCE,
+                ),
+                ValidationValue::Centroid(expected) => {
+                    let distance = (0..3)
+                        .map(|axis| (body.centroid[axis] - expected[axis]).powi(2))
+                        .sum::<f64>()
+                        .sqrt();
+                    (
+                        ValidationValue::Centroid(body.centroid),
+                        distance / diagonal.max(f64::EPSILON),
+                        CENTROID_TOLERANCE,
+                    )
+                }
+                ValidationValue::ChildCount(expected) => {
+                    let count = node.children.len();
+                    (
+                        ValidationValue::ChildCount(count),
+                        relative(expected as f64, count as f64),
+                        0.0,
+                    )
+                }
+            };
+            Some(ValidationCheck {
+                node_id: node.id,
+                name: node.name.clone(),
+                expected: property.value,
+                computed,
+                relative_error,
+                passed: relative_error <= tolerance,
+            })
+        })
+        .collect()
+}
+
+fn relative(expected: f64, computed: f64) -> f64 {
+    if expected == computed {
+        return 0.0;
+    }
+    (computed - expected).abs() / expected.abs().max(f64::EPSILON)
+}
+
+fn diagonal(model: &StepModel, parts: &[usize]) -> f64 {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for (part_min, part_max) in parts
+        .iter()
+        .filter_map(|&i| model.render_parts[i].world_bounds())
+    {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(part_min[axis]);
+            max[axis] = max[axis].max(part_max[axis]);
+        }
+    }
+    if !min[0].is_finite() {
+        return 0.0;
+    }
+    (0..3)
+        .map(|axis| ((max[axis] - min[axis]) as f64).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{parse_step, tessellate_all};
+    use crate::render::RenderablePart;
+
+    fn load(text: &str) -> StepModel {
+        let parsed = parse_step(text, "test.stp").unwrap();
+        StepModel {
+            id: parsed.id,
+            metadata: parsed.metadata,
+            product_tree: parsed.prepared.product_tree,
+            render_parts: tessellate_all(parsed.prepared.sections).0,
+            measurements: Vec::new(),
+        }
+    }
+
+    fn assert_all_pass(checks: &[ValidationCheck]) {
+        for check in checks {
+            assert!(check.passed, "{check:?}");
+        }
+    }
+
+    #[test]
+    fn assembly_matches_its_validation_properties() {
+        let model = load(include_str!("../../examples/as1-tc-214.stp"));
+        let checks = validate(&model);
+        // volume, area and centroid of the 9 products
+        assert_eq!(checks.len(), 27);
+        assert_all_pass(&checks);
+
+        // assembly values cover every part below, placed in the world
+        let root = &model.product_tree[0];
+        let whole = checks
+            .iter()
+            .find(|check| check.node_id == root.id && check.expected.label() == "Volume")
+            .unwrap();
+        let ValidationValue::Volume(volume) = whole.computed else {
+            panic!("{whole:?}");
+        };
+        let total: f64 = model
+            .render_parts
+            .iter()
+            .map(RenderablePart::calculate_volume)
+            .sum();
+        assert!((volume - total).abs() < 1e-6 * total);
+    }
+
+    #[test]
+    fn solid_properties_count_for_single_solid_parts() {
+        let model = load(include_str!("../../examples/io1-ca-214.stp"));
+        let checks = validate(&model);
+        let labels: Vec<_> = checks.iter().map(|check| check.expected.label()).collect();
+        assert_eq!(labels, ["Volume", "Surface area", "Centroid"]);
+        assert_all_pass(&checks);
+    }
+
+    #[test]
+    fn wrong_values_fail() {
+        let mut model = load(include_str!("../../examples/io1-ca-214.stp"));
+        for property in &mut model.metadata.validation_properties {
+            if let ValidationValue::Volume(volume) = &mut property.value {
+                *volume *= 1.1;
+            }
+        }
+        let failed: Vec<_> = validate(&model)
+            .into_iter()
+            .filter(|check| !check.passed)
+            .collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].expected.label(), "Volume");
+        assert!((failed[0].relative_error - 0.1 / 1.1).abs() < 0.02);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::trace_span;
+
+// Minimal zip archive writer: entries are stored uncompressed, which every
+// unzip tool reads, and meshes are exported uncompressed anyway
+pub fn write_zip<'a>(entries: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Vec<u8> {
+    trace_span!("write_zip");
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    let mut count: u16 = 0;
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        // local file header
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        push_common(&mut out, name, crc, size);
+        out.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        // central directory header
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        push_common(&mut central, name, crc, size);
+        central.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+        count += 1;
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    // end of central directory
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // this disk
+    out.extend_from_slice(&0u16.to_le_bytes()); // disk with the directory
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    out
+}
+
+// fields shared by the local and the central header, from "version needed"
+// to the file name length
+fn push_common(out: &mut Vec<u8>, name: &str, crc: u32, size: u32) {
+    out.extend_from_slice(&20u16.to_le_bytes()); // version needed
+    // bit 11: the name is UTF-8
+    out.extend_from_slice(&0x0800u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // stored
+    out.extend_from_slice(&0u16.to_le_bytes()); // time
+    out.extend_from_slice(&0x0021u16.to_le_bytes()); // date, 1980-01-01
+    out.extend_from_slice(&crc.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes()); // compressed
+    out.extend_from_slice(&size.to_le_bytes()); // uncompressed
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// file names for entries, unsafe characters replaced and repeated names
+// numbered
+pub fn unique_file_names(names: impl IntoIterator<Item = String>, extension: &str) -> Vec<String> {
+    let mut used = std::collections::HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let stem: String = name
+                .trim()
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || "-_.".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let stem = if stem.is_empty() {
+                "part".to_string()
+            } else {
+                stem
+            };
+            let mut file_name = format!("{stem}.{extension}");
+            let mut n = 2;
+            while !used.insert(file_name.to_lowercase()) {
+                file_name = format!("{stem}_{n}.{extension}");
+                n += 1;
+            }
+            file_name
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn entries_are_stored_and_listed() {
+        let zip = write_zip([("a.stl", &b"first"[..]), ("b.stl", &b"second!"[..])]);
+        // the first entry's data follows its 30 byte header and name
+        assert_eq!(u32_at(&zip, 0), 0x0403_4b50);
+        assert_eq!(&zip[30 + 5..30 + 10], b"first");
+        // end record: two entries, directory right after the data
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(&zip, end), 0x0605_4b50);
+        assert_eq!(u16::from_le_bytes([zip[end + 10], zip[end + 11]]), 2);
+        let directory = u32_at(&zip, end + 16) as usize;
+        assert_eq!(directory, 2 * 30 + 10 + 12);
+        assert_eq!(u32_at(&zip, directory), 0x0201_4b50);
+        assert_eq!(u32_at(&zip, directory + 16), crc32(b"first"));
+    }
+
+    #[test]
+    fn file_names_are_unique() {
+        let names = ["nut", "nut", "Nut", "a/b", ""].map(str::to_string);
+        assert_eq!(
+            unique_file_names(names, "stl"),
+            ["nut.stl", "nut_2.stl", "Nut_3.stl", "a_b.stl", "part.stl"]
+        );
+    }
+}
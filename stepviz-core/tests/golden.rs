@@ -0,0 +1,222 @@
+// Golden regression tests: every file in examples/ goes through parsing and
+// tessellation and the results are compared with tests/golden/<file>.json.
+// After an intended change (a truck or ruststep upgrade, a tessellation fix),
+// regenerate them and review the diff:
+//
+//     STEPVIZ_UPDATE_GOLDEN=1 cargo test -p stepviz-core --test golden
+use serde::Serialize;
+use serde_json::Value;
+use std::panic::catch_unwind;
+use std::path::{Path, PathBuf};
+use stepviz_core::mass::MassProperties;
+use stepviz_core::types::{BoundingBox, StepHeader};
+use stepviz_core::{parse_step, tessellate_all};
+
+// relative, for every floating point value (volume, area, bounding box)
+const TOLERANCE: f64 = 1e-6;
+
+#[derive(Serialize)]
+struct Golden {
+    header: Option<StepHeader>,
+    units: Option<String>,
+    entity_count: usize,
+    section_entity_counts: Vec<usize>,
+    bounding_box: Option<BoundingBox>,
+    parts: usize,
+    vertices: usize,
+    triangles: usize,
+    volume: f64,
+    surface_area: f64,
+    // shells that were skipped
+    warnings: Vec<String>,
+    // a file that fails cleanly stays in the suite, with the message it fails
+    // with, a panic is never an expected result
+    error: Option<String>,
+}
+
+fn golden(text: &str, file_name: &str) -> Golden {
+    let mut golden = Golden {
+        header: None,
+        units: None,
+        entity_count: 0,
+        section_entity_counts: Vec::new(),
+        bounding_box: None,
+        parts: 0,
+        vertices: 0,
+        triangles: 0,
+        volume: 0.0,
+        surface_area: 0.0,
+        warnings: Vec::new(),
+        error: None,
+    };
+    let parsed = match catch_unwind(|| parse_step(text, file_name)) {
+        Ok(Ok(parsed)) => parsed,
+        Ok(Err(message)) => {
+            golden.error = Some(message.to_string());
+            return golden;
+        }
+        Err(panic) => {
+            golden.error = Some(format!("parser panicked: {}", panic_message(panic)));
+            return golden;
+        }
+    };
+    let metadata = parsed.metadata;
+    golden.header = Some(metadata.header);
+    golden.units = metadata.units;
+    golden.entity_count = metadata.entity_count;
+    golden.section_entity_counts = metadata.section_entity_counts;
+
+    let sections = parsed.prepared.sections;
+    let (parts, warnings) = match catch_unwind(|| tessellate_all(sections)) {
+        Ok(tessellated) => tessellated,
+        Err(panic) => {
+            golden.error = Some(format!("tessellation panicked: {}", panic_message(panic)));
+            return golden;
+        }
+    };
+    golden.warnings = metadata
+        .warnings
+        .iter()
+        .chain(&warnings)
+        .map(ToString::to_string)
+        .collect();
+    let bodies: Vec<MassProperties> = parts.iter().map(MassProperties::of_part).collect();
+    let mass = MassProperties::combine(&bodies);
+    golden.bounding_box = BoundingBox::of_parts(&parts);
+    golden.parts = parts.len();
+    golden.vertices = parts.iter().map(|part| part.mesh.vertices.len()).sum();
+    golden.triangles = parts.iter().map(|part| part.mesh.indices.len() / 3).sum();
+    golden.volume = mass.volume;
+    golden.surface_area = mass.area;
+    golden
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+// Collects every difference as "path: expected X, got Y"
+fn compare(path: &str, expected: &Value, actual: &Value, differences: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for key in expected
+                .keys()
+                .chain(actual.keys().filter(|k| !expected.contains_key(*k)))
+            {
+                let null = Value::Null;
+                compare(
+                    &format!("{path}.{key}"),
+                    expected.get(key).unwrap_or(&null),
+                    actual.get(key).unwrap_or(&null),
+                    differences,
+                );
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            for (index, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                compare(&format!("{path}[{index}]"), expected, actual, differences);
+            }
+        }
+        (Value::Number(e), Value::Number(a)) if e.is_f64() || a.is_f64() => {
+            let (e, a) = (
+                e.as_f64().unwrap_or(f64::NAN),
+                a.as_f64().unwrap_or(f64::NAN),
+            );
+            if (e - a).abs() > TOLERANCE * e.abs().max(a.abs()).max(1.0) {
+                differences.push(format!("{path}: expected {e}, got {a}"));
+            }
+        }
+        _ if expected != actual => {
+            differences.push(format!("{path}: expected {expected}, got {actual}"));
+        }
+        _ => {}
+    }
+}
+
+fn directory(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+}
+
+#[test]
+fn examples_match_their_goldens() {
+    let update = std::env::var_os("STEPVIZ_UPDATE_GOLDEN").is_some();
+    let golden_directory = directory("tests/golden");
+    let mut examples: Vec<PathBuf> = std::fs::read_dir(directory("../examples"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "stp"))
+        .collect();
+    examples.sort();
+    assert!(!examples.is_empty());
+
+    let mut failures = Vec::new();
+    for example in &examples {
+        let file_name = example.file_name().unwrap().to_string_lossy().into_owned();
+        let bytes = std::fs::read(example).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        let actual = serde_json::to_value(golden(&text, &file_name)).unwrap();
+        let golden_path = golden_directory.join(format!("{file_name}.json"));
+        if let Some(error) = actual["error"].as_str().filter(|e| e.contains("panicked")) {
+            failures.push(format!(
+                "{file_name}: {error}, fix the panic instead of recording it"
+            ));
+            continue;
+        }
+
+        if update {
+            std::fs::create_dir_all(&golden_directory).unwrap();
+            let json = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+            std::fs::write(&golden_path, json).unwrap();
+            continue;
+        }
+        let Ok(expected) = std::fs::read_to_string(&golden_path) else {
+            failures.push(format!(
+                "{file_name}: no golden file, run with STEPVIZ_UPDATE_GOLDEN=1"
+            ));
+            continue;
+        };
+        let expected: Value = serde_json::from_str(&expected).unwrap();
+        let mut differences = Vec::new();
+        compare(&file_name, &expected, &actual, &mut differences);
+        failures.extend(differences);
+    }
+
+    // goldens whose example is gone would never be checked again
+    for entry in std::fs::read_dir(&golden_directory).unwrap() {
+        let golden = entry.unwrap().file_name().to_string_lossy().into_owned();
+        let example = golden.strip_suffix(".json").unwrap_or(&golden);
+        if !examples.iter().any(|path| path.ends_with(example)) {
+            failures.push(format!("{golden}: no such example, delete the golden"));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} difference(s) from the goldens, if intended rerun with STEPVIZ_UPDATE_GOLDEN=1:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn comparison_reports_paths_and_respects_the_tolerance() {
+    let expected = serde_json::json!({ "a": { "b": [1.0, 2] }, "c": "x" });
+    let close = serde_json::json!({ "a": { "b": [1.0 + 1e-9, 2] }, "c": "x" });
+    let far = serde_json::json!({ "a": { "b": [1.1, 3] }, "d": true });
+    let mut differences = Vec::new();
+    compare("g", &expected, &close, &mut differences);
+    assert!(differences.is_empty(), "{differences:?}");
+    compare("g", &expected, &far, &mut differences);
+    assert_eq!(
+        differences,
+        [
+            "g.a.b[0]: expected 1, got 1.1",
+            "g.a.b[1]: expected 2, got 3",
+            "g.c: expected \"x\", got null",
+            "g.d: expected null, got true",
+        ]
+    );
+}